@@ -0,0 +1,157 @@
+//! A window frontend for the same engine the terminal game runs: every tick and every
+//! frame comes from [`Game`], and this file only turns keys into [`Inputs`] and the
+//! rendered cells into colored rectangles. Built with `--features gui`.
+//!
+//! Player B is yours (Up/Down or W/S), Player A is the AI; Enter or Space launches a
+//! serve waiting with `wait_for_serve`, and Escape quits.
+
+use anyhow::{Result, bail};
+use clap::Parser;
+use minifb::{Key, Window, WindowOptions};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use duckdb_pong::config::GameConfig;
+use duckdb_pong::palette::Palette;
+use duckdb_pong::render::{Cell, RenderMode, Rendered, Tile};
+use duckdb_pong::{Game, Inputs, PlayerInput, SIM_DT, Win};
+
+/// Pixels per field cell, about the shape of a terminal character.
+const CELL_W: usize = 8;
+const CELL_H: usize = 16;
+
+/// Upper bound on catch-up ticks per frame, as in the terminal game.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+const BACKGROUND: u32 = 0x10_10_10;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Pong with all game logic running in DuckDB SQL, in a window"
+)]
+struct Args {
+    /// Read game settings from this TOML file, like the terminal game's --config
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+}
+
+/// The color a cell is filled with: the config's `[colors]` where set, the built-in
+/// scheme otherwise.
+fn rgb(cell: Cell, palette: &Palette) -> u32 {
+    if let Some(color) = palette.get(cell) {
+        return color.packed();
+    }
+    match cell {
+        Cell::Empty => BACKGROUND,
+        Cell::Border | Cell::Wall | Cell::CenterLine => 0x60_60_60,
+        Cell::PaddleA => 0x00_c0_c0,
+        Cell::PaddleB => 0xc0_00_c0,
+        Cell::Ball => 0xff_ff_ff,
+        Cell::Countdown => 0xe0_e0_00,
+        Cell::PowerUp => 0x00_c0_00,
+        Cell::Trail(0) => 0xa0_a0_a0,
+        Cell::Trail(_) => 0x50_50_50,
+    }
+}
+
+/// Paint one tile. Half-block glyphs (the border, and everything in hi-res mode) fill
+/// only their half of the cell; anything else fills it whole.
+fn paint(buffer: &mut [u32], stride: usize, x: usize, y: usize, tile: Tile, palette: &Palette) {
+    let rows = match tile.glyph {
+        ' ' => return,
+        '▀' => 0..CELL_H / 2,
+        '▄' => CELL_H / 2..CELL_H,
+        _ => 0..CELL_H,
+    };
+    let color = rgb(tile.cell, palette);
+    for row in rows {
+        let start = (y * CELL_H + row) * stride + x * CELL_W;
+        buffer[start..start + CELL_W].fill(color);
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let config = match &args.config {
+        Some(path) => GameConfig::load(path)?,
+        None => GameConfig::default(),
+    };
+    config.validate()?;
+
+    let (width, height) = (usize::from(config.width), usize::from(config.height));
+    let (stride, pixel_rows) = (width * CELL_W, height * CELL_H);
+    let mut window = Window::new("DuckDB Pong", stride, pixel_rows, WindowOptions::default())?;
+    window.set_target_fps(config.fps as usize);
+
+    let palette = config.colors;
+    let background = palette
+        .background
+        .map_or(BACKGROUND, |color| color.packed());
+    let mut game = Game::new(config)?;
+    let mut buffer = vec![background; stride * pixel_rows];
+    let mut score = None;
+    let mut sim_time = Duration::ZERO;
+    let mut last_frame = Instant::now();
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let input = if window.is_key_down(Key::Up) || window.is_key_down(Key::W) {
+            PlayerInput::Up
+        } else if window.is_key_down(Key::Down) || window.is_key_down(Key::S) {
+            PlayerInput::Down
+        } else {
+            PlayerInput::None
+        };
+
+        if window.is_key_down(Key::Enter) || window.is_key_down(Key::Space) {
+            game.serve()?;
+        }
+
+        let now = Instant::now();
+        sim_time += now - last_frame;
+        last_frame = now;
+        let mut ticks = 0;
+        while sim_time >= SIM_DT && ticks < MAX_TICKS_PER_FRAME {
+            game.tick(Inputs {
+                b: input,
+                ..Inputs::default()
+            })?;
+            sim_time -= SIM_DT;
+            ticks += 1;
+        }
+        if sim_time >= SIM_DT {
+            sim_time = Duration::ZERO;
+        }
+
+        let Rendered::Cells(lines) = game.render(RenderMode::Cells)? else {
+            bail!("cell render returned a full screen");
+        };
+        buffer.fill(background);
+        for (y, line) in lines.iter().enumerate() {
+            for (x, &tile) in line.iter().enumerate() {
+                paint(&mut buffer, stride, x, y, tile, &palette);
+            }
+        }
+        window.update_with_buffer(&buffer, stride, pixel_rows)?;
+
+        let state = game.state()?;
+        if score != Some((state.score_a, state.score_b)) {
+            score = Some((state.score_a, state.score_b));
+            window.set_title(&format!(
+                "DuckDB Pong - A {} : {} B",
+                state.score_a, state.score_b
+            ));
+        }
+        if game.lives()? == Some(0) {
+            game.restart()?;
+        }
+        match game.winner()? {
+            Some(Win::Set(_)) => {
+                game.next_set()?;
+            }
+            // No game over screen here: the next match starts right away
+            Some(Win::Match(_)) => game.restart()?,
+            None => {}
+        }
+    }
+    Ok(())
+}
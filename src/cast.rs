@@ -0,0 +1,96 @@
+//! Exporting recordings as asciinema casts (format v2) for sharing: a JSON header
+//! line, then one `[seconds, "o", output]` line per frame that changed. Play one back
+//! with `asciinema play match.cast` or upload it with `asciinema upload match.cast`.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// What the cast's header says about it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CastHeader {
+    /// The terminal size the frames need, in columns and rows.
+    pub width: u16,
+    pub height: u16,
+    pub title: Option<String>,
+    /// Pauses longer than this many seconds are shortened to it on playback.
+    pub idle_time_limit: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct HeaderLine<'a> {
+    version: u32,
+    width: u16,
+    height: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_time_limit: Option<f64>,
+}
+
+/// Writes frames of text lines to a cast file. A frame repeating the last one is
+/// left out, so a still field turns into idle time that `idle_time_limit` can cut.
+pub struct CastWriter {
+    out: BufWriter<File>,
+    last: Vec<String>,
+    /// The terminal output for a frame, reused between frames.
+    data: String,
+}
+
+impl CastWriter {
+    pub fn create(path: &Path, header: &CastHeader) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("creating cast {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        let line = HeaderLine {
+            version: 2,
+            width: header.width,
+            height: header.height,
+            title: header.title.as_deref(),
+            idle_time_limit: header.idle_time_limit,
+        };
+        serde_json::to_writer(&mut out, &line)?;
+        writeln!(out)?;
+        Ok(CastWriter {
+            out,
+            last: Vec::new(),
+            data: String::new(),
+        })
+    }
+
+    /// Show `lines` from `time` into the cast on. Returns whether the frame was
+    /// written, i.e. differs from the last one.
+    pub fn frame(&mut self, time: Duration, lines: &[String]) -> Result<bool> {
+        if lines == self.last {
+            return Ok(false);
+        }
+        self.data.clear();
+        if self.last.is_empty() {
+            // Start from a blank screen without a cursor over the field
+            self.data.push_str("\x1b[?25l\x1b[2J");
+        }
+        self.data.push_str("\x1b[H");
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                self.data.push_str("\r\n");
+            }
+            // Lines come without trailing blanks, so clear what the last frame left
+            self.data.push_str(line);
+            self.data.push_str("\x1b[K");
+        }
+        serde_json::to_writer(&mut self.out, &(time.as_secs_f64(), "o", &self.data))?;
+        writeln!(self.out)?;
+        self.last.clear();
+        self.last.extend_from_slice(lines);
+        Ok(true)
+    }
+
+    /// Write out whatever is still buffered.
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
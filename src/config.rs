@@ -0,0 +1,507 @@
+//! Game settings, read from an optional TOML file and overridden by command line flags.
+//!
+//! Every key is optional; missing ones keep their defaults:
+//!
+//! ```toml
+//! width = 100
+//! height = 30
+//! paddle_height = 9
+//! paddle_width = 2
+//! paddle_speed = 2
+//! ball_speed = 1
+//! target_score = 5
+//! sets = 3
+//! fps = 60
+//! color = false
+//! sound = "bell"
+//! border = "box"
+//! ascii = true
+//! difficulty = "hard"
+//! first_serve = "b"
+//! serve = "loser"
+//! serve_angle = "flat"
+//! rally_timeout = 600
+//! on_rally_timeout = "speedup"
+//! ramp = true
+//! wrap = true
+//! big_ball = true
+//! zones = 7
+//! aspect_correct = true
+//!
+//! [keys]
+//! quit = ["q", "esc"]
+//!
+//! [colors]
+//! ball = "#ffd700"
+//! ```
+//!
+//! See [`crate::keys`] for the key names and [`crate::palette`] for the colors.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::keys::KeyBindings;
+use crate::palette::Palette;
+use crate::render::{BorderStyle, FIELD_TOP, GlyphSet};
+use crate::sound::SoundMode;
+use crate::{
+    AiStyle, Difficulty, FirstServe, IdlePaddle, PlayMode, SIM_HZ, ServeAngle, ServeRule, Side,
+    TimeoutAction, sql,
+};
+
+/// Smallest field that still leaves room for paddles, ball and center line.
+pub const MIN_WIDTH: u16 = 20;
+pub const MIN_HEIGHT: u16 = 10;
+/// Largest field, far beyond any terminal, so the rows around it still fit in a u16.
+pub const MAX_WIDTH: u16 = 1000;
+pub const MAX_HEIGHT: u16 = 1000;
+/// Most balls in play at once; more than this turns the field into noise.
+pub const MAX_BALLS: u32 = 9;
+/// Thickest paddle, in columns; wider ones would crowd the middle of a narrow field.
+pub const MAX_PADDLE_WIDTH: u16 = 5;
+/// Longest ball trail, in ticks.
+pub const MAX_TRAIL: u32 = 12;
+/// Fastest horizontal ball speed, in columns per tick.
+pub const MAX_BALL_SPEED: u32 = 5;
+/// Most hit zones on a paddle; the steepest of 9 sends the ball 4 rows per column.
+pub const MAX_ZONES: u32 = 9;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub width: u16,
+    pub height: u16,
+    /// Derived from the height when not set, see [`GameConfig::paddle_height`].
+    pub paddle_height: Option<u16>,
+    /// Columns each paddle is thick, counted inward from its edge of the field.
+    pub paddle_width: u16,
+    pub paddle_speed: u32,
+    pub ball_speed: u32,
+    pub max_ball_speed: u32,
+    pub speedup_hits: u32,
+    /// Paddles moving at impact add one step of vertical speed in their direction.
+    pub spin: bool,
+    /// Paddles speed up while pushed and coast to a stop, see [`sql::SETUP`].
+    pub momentum: bool,
+    /// Pickups that grow or shrink a paddle for a while, see [`sql::POWERUPS`].
+    pub powerups: bool,
+    /// Milliseconds Player A's AI takes to react: it only decides where to go this
+    /// often and sticks to that in between. 0 reacts every tick.
+    pub ai_reaction_a: u32,
+    /// The same for Player B's AI, so the two sides can be handicapped differently.
+    pub ai_reaction_b: u32,
+    /// How both AIs play, see [`AiStyle`].
+    pub ai: AiStyle,
+    /// Trick shots get bolder when an AI is behind and tamer when it is ahead, see
+    /// [`sql::TICK`] for how much.
+    pub adaptive_ai: bool,
+    /// The AIs track the ball more often and react sooner the longer a match runs,
+    /// see [`sql::TICK`] for the schedule.
+    pub ramp: bool,
+    /// Take the randomness out of the reactive AI: it always tracks the ball and goes
+    /// through the trick shot zones in turn, see [`sql::TICK`].
+    pub deterministic_ai: bool,
+    /// Most rows the predictive AI misjudges where a ball lands by; 0 never misses a
+    /// ball it can reach in time.
+    pub ai_error: u16,
+    /// Milliseconds the balls are held after a point while a countdown runs, 0 to
+    /// serve right away.
+    pub serve_delay: u32,
+    /// Milliseconds of the "READY? 3, 2, 1, GO!" intro before a local match, 0 to
+    /// start right away.
+    pub intro_delay: u32,
+    /// Who the opening serve heads for; with `wait_for_serve` it is the player's.
+    pub first_serve: FirstServe,
+    pub serve: ServeRule,
+    /// How steep serves start, see [`ServeAngle`] for the chances of each angle.
+    pub serve_angle: ServeAngle,
+    /// Hold the player's serves, the first one included, at their paddle until the
+    /// serve key launches them.
+    pub wait_for_serve: bool,
+    /// The top and bottom edges wrap the ball around to the other one instead of
+    /// bouncing it back.
+    pub wrap: bool,
+    /// Draw the ball as a 2x2 block, and bounce and score it as one, for visibility.
+    pub big_ball: bool,
+    /// Slow the ball's vertical movement to half, so angles look on terminal cells
+    /// the way they would on square ones. `hires` already does that by simulating
+    /// two pixel rows per cell, so it is ignored there.
+    pub aspect_correct: bool,
+    /// Zones a paddle is split into, each sending the ball off at its own angle: an odd
+    /// number from 3 to [`MAX_ZONES`], the middle one straight across. They are spread
+    /// over however tall the paddle is.
+    pub zones: u32,
+    /// Ticks a rally may go on without a point before `on_rally_timeout` happens, 0
+    /// for no limit.
+    pub rally_timeout: u32,
+    pub on_rally_timeout: TimeoutAction,
+    pub target_score: u32,
+    /// Best of this many sets wins the match; each set goes to `target_score` points.
+    pub sets: u32,
+    pub balls: u32,
+    /// Frame rate cap, 0 for uncapped.
+    pub fps: u32,
+    pub color: bool,
+    /// Dim every other field row like the scanlines of an old CRT.
+    pub crt: bool,
+    /// Hex colors for the ball, paddles, border, center line and background, see
+    /// [`crate::palette`].
+    pub colors: Palette,
+    /// How paddle hits and points sound, see [`SoundMode`].
+    pub sound: SoundMode,
+    /// How the top and bottom edges of the field are drawn.
+    pub border: BorderStyle,
+    /// Draw with 7-bit characters only; the border is drawn in the `ascii` style then,
+    /// whatever `border` says.
+    pub ascii: bool,
+    /// Draw the dotted line down the middle of the field.
+    pub center_line: bool,
+    /// Which paddle the keys and mouse steer; the AI plays the other one.
+    pub human_side: Side,
+    /// Solo against the AI, or two players at one keyboard.
+    pub mode: PlayMode,
+    /// What an untouched paddle does in local versus play.
+    pub idle_paddle: IdlePaddle,
+    /// Practice alone: Player A is replaced by a wall the ball bounces off, and every
+    /// ball you miss costs one of `lives`.
+    pub training: bool,
+    pub lives: u32,
+    /// Ticks of ball positions drawn as a fading trail behind it, 0 for none.
+    pub trail: u32,
+    /// Simulate two pixel rows per terminal row and draw them with half-blocks.
+    /// `height`, `paddle_height` and `paddle_speed` stay in terminal rows.
+    pub hires: bool,
+    pub difficulty: Difficulty,
+    pub keys: KeyBindings,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            width: 80,
+            height: 25,
+            paddle_height: None,
+            paddle_width: 1,
+            paddle_speed: 2,
+            ball_speed: 1,
+            max_ball_speed: 4,
+            speedup_hits: 4,
+            spin: false,
+            momentum: false,
+            powerups: false,
+            ai_reaction_a: 0,
+            ai_reaction_b: 0,
+            ai: AiStyle::Reactive,
+            adaptive_ai: false,
+            ramp: false,
+            deterministic_ai: false,
+            ai_error: 4,
+            serve_delay: 1000,
+            intro_delay: 2500,
+            first_serve: FirstServe::Random,
+            serve: ServeRule::Winner,
+            serve_angle: ServeAngle::Random,
+            wait_for_serve: false,
+            wrap: false,
+            big_ball: false,
+            aspect_correct: false,
+            zones: 5,
+            rally_timeout: 0,
+            on_rally_timeout: TimeoutAction::Let,
+            target_score: 11,
+            sets: 1,
+            balls: 1,
+            fps: 120,
+            color: true,
+            crt: false,
+            colors: Palette::default(),
+            sound: SoundMode::Off,
+            border: BorderStyle::Blocks,
+            ascii: false,
+            center_line: true,
+            human_side: Side::Right,
+            mode: PlayMode::Solo,
+            idle_paddle: IdlePaddle::Hold,
+            training: false,
+            lives: 3,
+            trail: 0,
+            hires: false,
+            difficulty: Difficulty::Normal,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config {}", path.display()))?;
+        let config =
+            toml::from_str(&text).with_context(|| format!("parsing config {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// These settings as pretty-printed JSON, for `--print-config`, with the paddle
+    /// height worked out when it follows from the field's.
+    pub fn to_json(&self) -> Result<String> {
+        let resolved = GameConfig {
+            paddle_height: Some(self.paddle_height()),
+            ..self.clone()
+        };
+        Ok(serde_json::to_string_pretty(&resolved)?)
+    }
+
+    /// Check the ranges the command line enforces for flags, plus the paddle fitting
+    /// on the field and every action having keys of its own. Every offending field is
+    /// listed in the error.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: String| {
+            if !ok {
+                problems.push(problem);
+            }
+        };
+        check(
+            (MIN_WIDTH..=MAX_WIDTH).contains(&self.width),
+            format!(
+                "width = {} must be between {MIN_WIDTH} and {MAX_WIDTH}",
+                self.width
+            ),
+        );
+        check(
+            (MIN_HEIGHT..=MAX_HEIGHT).contains(&self.height),
+            format!(
+                "height = {} must be between {MIN_HEIGHT} and {MAX_HEIGHT}",
+                self.height
+            ),
+        );
+        let paddle_height = self.paddle_height();
+        check(
+            paddle_height >= 1 && paddle_height <= self.height.saturating_sub(2),
+            format!(
+                "paddle_height = {paddle_height} must be between 1 and {} for height = {}",
+                self.height.saturating_sub(2),
+                self.height
+            ),
+        );
+        check(
+            (1..=MAX_PADDLE_WIDTH).contains(&self.paddle_width),
+            format!(
+                "paddle_width = {} must be between 1 and {MAX_PADDLE_WIDTH}",
+                self.paddle_width
+            ),
+        );
+        check(
+            self.paddle_speed >= 1,
+            format!("paddle_speed = {} must be at least 1", self.paddle_speed),
+        );
+        check(
+            (1..=MAX_BALL_SPEED).contains(&self.ball_speed),
+            format!(
+                "ball_speed = {} must be between 1 and {MAX_BALL_SPEED}",
+                self.ball_speed
+            ),
+        );
+        check(
+            (1..=MAX_BALL_SPEED).contains(&self.max_ball_speed),
+            format!(
+                "max_ball_speed = {} must be between 1 and {MAX_BALL_SPEED}",
+                self.max_ball_speed
+            ),
+        );
+        check(
+            self.speedup_hits >= 1,
+            format!("speedup_hits = {} must be at least 1", self.speedup_hits),
+        );
+        check(
+            self.target_score >= 1,
+            format!("target_score = {} must be at least 1", self.target_score),
+        );
+        check(
+            self.sets >= 1,
+            format!("sets = {} must be at least 1", self.sets),
+        );
+        check(
+            self.trail <= MAX_TRAIL,
+            format!("trail = {} must be at most {MAX_TRAIL}", self.trail),
+        );
+        check(
+            !(self.training && self.human_side == Side::Left),
+            "training replaces the left paddle, so human_side = \"left\" can't train".to_string(),
+        );
+        check(
+            !(self.mode == PlayMode::LocalVs && (self.training || self.human_side == Side::Left)),
+            "mode = \"local-vs\" has a player on each side, so it can't train or take \
+             human_side = \"left\""
+                .to_string(),
+        );
+        check(
+            self.lives >= 1,
+            format!("lives = {} must be at least 1", self.lives),
+        );
+        check(
+            (3..=MAX_ZONES).contains(&self.zones) && self.zones % 2 == 1,
+            format!(
+                "zones = {} must be odd and between 3 and {MAX_ZONES}",
+                self.zones
+            ),
+        );
+        check(
+            (1..=MAX_BALLS).contains(&self.balls),
+            format!("balls = {} must be between 1 and {MAX_BALLS}", self.balls),
+        );
+        problems.extend(self.keys.problems());
+        if !problems.is_empty() {
+            bail!("invalid config:\n  {}", problems.join("\n  "));
+        }
+        Ok(())
+    }
+
+    /// Paddle height scales with the field: 7 on the classic 25-row field, never below 3.
+    /// Worked out in u32, so even unchecked heights don't overflow on the way;
+    /// [`Self::validate`] still checks that it fits.
+    pub fn paddle_height(&self) -> u16 {
+        self.paddle_height
+            .unwrap_or_else(|| (u32::from(self.height) * 7 / 25).max(3) as u16)
+    }
+
+    /// Terminal size needed to show the field: the score header above it and the
+    /// status line below it each take one extra row.
+    pub fn required_size(&self) -> (u16, u16) {
+        (self.width, self.height + 2)
+    }
+
+    /// The terminal row under the field that the status line goes on, the last one
+    /// [`required_size`](Self::required_size) counts.
+    pub fn status_row(&self) -> u16 {
+        FIELD_TOP + self.height
+    }
+
+    pub fn fits(&self, cols: u16, rows: u16) -> bool {
+        let (min_cols, min_rows) = self.required_size();
+        cols >= min_cols && rows >= min_rows
+    }
+
+    /// Simulated rows per terminal row: 2 with `hires`, otherwise 1.
+    pub fn pixels_per_row(&self) -> u16 {
+        if self.hires { 2 } else { 1 }
+    }
+
+    /// The characters the field is drawn with: [`GlyphSet::Ascii`] with `ascii`.
+    pub fn glyphs(&self) -> GlyphSet {
+        if self.ascii {
+            GlyphSet::Ascii
+        } else {
+            GlyphSet::Unicode
+        }
+    }
+
+    /// The settings the simulation runs with, where every vertical value is in pixels.
+    pub fn settings(&self) -> sql::Settings {
+        let scale = self.pixels_per_row();
+        sql::Settings {
+            width: self.width.into(),
+            height: u32::from(self.height) * u32::from(scale),
+            paddle_h: u32::from(self.paddle_height()) * u32::from(scale),
+            paddle_w: self.paddle_width.into(),
+            paddle_speed: self.paddle_speed * u32::from(scale),
+            ball_speed: self.ball_speed,
+            max_ball_speed: self.max_ball_speed,
+            speedup_hits: self.speedup_hits,
+            spin: self.spin,
+            momentum: self.momentum,
+            ai_reaction_a: ms_to_ticks(self.ai_reaction_a),
+            ai_reaction_b: ms_to_ticks(self.ai_reaction_b),
+            serve_ticks: ms_to_ticks(self.serve_delay),
+            ai: self.ai,
+            ai_error: u32::from(self.ai_error) * u32::from(scale),
+            first_serve: self.first_serve,
+            serve: self.serve,
+            serve_angle: self.serve_angle,
+            target_score: self.target_score,
+            sets: self.sets,
+            balls: self.balls,
+            powerups: self.powerups,
+            powerup_rows: 2 * u32::from(scale),
+            border: if self.ascii {
+                BorderStyle::Ascii
+            } else {
+                self.border
+            },
+            center_line: self.center_line,
+            deterministic_ai: self.deterministic_ai,
+            human_side: self.human_side,
+            training: self.training,
+            lives: self.lives,
+            rally_timeout: self.rally_timeout,
+            on_rally_timeout: self.on_rally_timeout,
+            wait_for_serve: self.wait_for_serve,
+            ramp: self.ramp,
+            wrap: self.wrap,
+            big_ball: self.big_ball,
+            aspect_correct: self.aspect_correct && !self.hires,
+            zones: self.zones,
+        }
+    }
+
+    /// Take over the settings of a saved game, converting pixels back to rows for the
+    /// current `hires` choice.
+    pub fn adopt(&mut self, settings: &sql::Settings) {
+        let scale = self.pixels_per_row();
+        // The params table stores them as INTEGER, and they were u16s when saved
+        self.width = settings.width as u16;
+        self.height = settings.height as u16 / scale;
+        self.paddle_height = Some(settings.paddle_h as u16 / scale);
+        self.paddle_width = settings.paddle_w as u16;
+        self.paddle_speed = (settings.paddle_speed / u32::from(scale)).max(1);
+        self.ball_speed = settings.ball_speed;
+        self.max_ball_speed = settings.max_ball_speed;
+        self.speedup_hits = settings.speedup_hits;
+        self.spin = settings.spin;
+        self.momentum = settings.momentum;
+        self.ai_reaction_a = ticks_to_ms(settings.ai_reaction_a);
+        self.ai_reaction_b = ticks_to_ms(settings.ai_reaction_b);
+        self.serve_delay = ticks_to_ms(settings.serve_ticks);
+        self.ai = settings.ai;
+        self.ai_error = settings.ai_error as u16 / scale;
+        self.serve = settings.serve;
+        self.first_serve = settings.first_serve;
+        self.serve_angle = settings.serve_angle;
+        self.target_score = settings.target_score;
+        self.sets = settings.sets;
+        self.balls = settings.balls;
+        self.powerups = settings.powerups;
+        self.border = settings.border;
+        self.center_line = settings.center_line;
+        self.deterministic_ai = settings.deterministic_ai;
+        self.human_side = settings.human_side;
+        self.training = settings.training;
+        self.lives = settings.lives;
+        self.rally_timeout = settings.rally_timeout;
+        self.on_rally_timeout = settings.on_rally_timeout;
+        self.wait_for_serve = settings.wait_for_serve;
+        self.ramp = settings.ramp;
+        self.wrap = settings.wrap;
+        self.big_ball = settings.big_ball;
+        self.aspect_correct = settings.aspect_correct;
+        self.zones = settings.zones;
+    }
+
+    /// The [`sql::SETUP`] batch for these settings; `persistent` keeps the tables in
+    /// the database file instead of making them temporary.
+    pub fn setup_sql(&self, persistent: bool) -> String {
+        sql::build_setup(&self.settings(), persistent)
+    }
+}
+
+/// Whole simulation ticks in `ms` milliseconds, rounded down. Worked out in u64, so
+/// even `u32::MAX` milliseconds don't overflow on the way.
+fn ms_to_ticks(ms: u32) -> u32 {
+    (u64::from(ms) * SIM_HZ / 1000) as u32
+}
+
+/// Milliseconds in `ticks` simulation ticks, at most `u32::MAX`.
+fn ticks_to_ms(ticks: u32) -> u32 {
+    u32::try_from(u64::from(ticks) * 1000 / SIM_HZ).unwrap_or(u32::MAX)
+}
@@ -0,0 +1,146 @@
+//! How often the ball was on each cell of the field, added up tick by tick in the
+//! `heatmap` table over every match of a run, for analysis. Written out as a CSV grid
+//! of counts, one row per cell, or drawn in shades:
+//!
+//! ```sql
+//! SELECT y, sum(count) FROM read_csv_auto('heatmap.csv') GROUP BY y ORDER BY y;
+//! ```
+
+use anyhow::{Context, Result};
+use duckdb::Connection;
+use std::path::Path;
+
+/// Shades from the least visited cells to the most visited ones.
+pub const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+
+/// Create the empty `heatmap` table. It isn't part of the setup, so restarting a
+/// match keeps what the ones before added up.
+pub fn create(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE OR REPLACE TEMP TABLE heatmap(
+            x INTEGER,
+            y INTEGER,
+            count INTEGER,  -- Ticks a ball spent on the cell
+            PRIMARY KEY (x, y)
+        )",
+    )
+    .context("creating the heatmap table")?;
+    Ok(())
+}
+
+/// Count the cells every ball covers now, after a tick. Balls held for a serve
+/// aren't in play and don't count, and neither does a ball beyond the field's edge.
+pub fn record(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "INSERT INTO heatmap
+        SELECT b.ball_x, b.ball_y, 1
+        FROM ball_cells b, params p, state s
+        WHERE s.serve_timer = 0 AND NOT s.awaiting_serve
+            AND b.ball_x BETWEEN 0 AND p.W-1 AND b.ball_y BETWEEN 0 AND p.H-1
+        ON CONFLICT (x, y) DO UPDATE SET count = count + 1",
+    )
+    .context("adding to the heatmap")?;
+    Ok(())
+}
+
+/// Write the heatmap to `path` as CSV with an `x,y,count` header, a row for every
+/// cell of the field, unvisited ones with 0, ordered by row and then column.
+pub fn write_csv(conn: &Connection, path: &Path) -> Result<()> {
+    let quoted = path.to_string_lossy().replace('\'', "''");
+    conn.execute_batch(&format!(
+        "COPY (
+            SELECT t_x.x, t_y.y, coalesce(h.count, 0) AS count
+            FROM params p
+            CROSS JOIN range(p.W) AS t_x(x)
+            CROSS JOIN range(p.H) AS t_y(y)
+            LEFT JOIN heatmap h ON h.x = t_x.x AND h.y = t_y.y
+            ORDER BY t_y.y, t_x.x
+        ) TO '{quoted}' (HEADER)"
+    ))
+    .with_context(|| format!("writing heatmap {}", path.display()))?;
+    Ok(())
+}
+
+/// The heatmap as one line of text per field row: unvisited cells blank, the others
+/// in [`SHADES`] by their count against the most visited cell's, in quarters.
+pub fn shade(conn: &Connection) -> Result<Vec<String>> {
+    let (width, height): (usize, usize) = conn
+        .query_row("SELECT W, H FROM params", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .context("reading the heatmap")?;
+    let mut grid = vec![vec![' '; width]; height];
+    let mut stmt = conn
+        .prepare(
+            "SELECT x, y, (count * 4 - 1) // max(count) OVER () AS shade
+        FROM heatmap
+        WHERE count > 0",
+        )
+        .context("reading the heatmap")?;
+    let cells = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                row.get::<_, usize>(1)?,
+                row.get::<_, usize>(2)?,
+            ))
+        })
+        .context("reading the heatmap")?;
+    for cell in cells {
+        let (x, y, shade) = cell.context("reading the heatmap")?;
+        if let Some(slot) = grid.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *slot = SHADES[shade.min(SHADES.len() - 1)];
+        }
+    }
+    Ok(grid.into_iter().map(String::from_iter).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckdb::params;
+
+    use crate::sql;
+
+    #[test]
+    fn heatmap_counts_the_cells_balls_are_on() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        create(&conn)?;
+        let place = |ball_x: i32, serve_timer: i32| {
+            conn.execute(
+                "UPDATE state SET ball_x = ?, ball_y = 5, serve_timer = ?, extra_balls = NULL",
+                params![ball_x, serve_timer],
+            )
+        };
+        place(10, 0)?;
+        for _ in 0..4 {
+            record(&conn)?;
+        }
+        place(20, 0)?;
+        record(&conn)?;
+        // Neither a ball held for the serve nor one past the edge counts
+        place(30, 5)?;
+        record(&conn)?;
+        place(-1, 0)?;
+        record(&conn)?;
+
+        let lines = shade(&conn)?;
+        assert_eq!(lines.len(), 25);
+        let row: Vec<char> = lines[5].chars().collect();
+        assert_eq!((row[10], row[20], row[30]), ('█', '░', ' '));
+        assert!(lines[4].trim().is_empty());
+
+        let path =
+            std::env::temp_dir().join(format!("duckdb-pong-heatmap-{}.csv", std::process::id()));
+        write_csv(&conn, &path)?;
+        let csv = std::fs::read_to_string(&path)?;
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + 80 * 25);
+        assert_eq!(lines[0], "x,y,count");
+        assert_eq!(lines[1], "0,0,0");
+        assert!(lines.contains(&"10,5,4") && lines.contains(&"20,5,1"));
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Results of finished matches, with who they were against, appended to a CSV file
+//! across runs so the all-time record against the AI can be shown. The file is plain
+//! CSV like a recording:
+//!
+//! ```sql
+//! SELECT opponent, winner, count(*) FROM read_csv_auto('history.csv') GROUP BY ALL;
+//! ```
+
+use anyhow::{Context, Result};
+use duckdb::{Connection, params};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The header line, in the order [`append`] writes the columns.
+const HEADER: &str = "finished_at,winner,score_a,score_b,seconds,opponent";
+
+/// Who the player played a match against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opponent {
+    /// The AI, in a solo match
+    Ai,
+    /// A second player at the same keyboard
+    Local,
+    /// A player who joined over the network
+    Network,
+}
+
+impl Opponent {
+    /// The opponent as the `opponent` column spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Opponent::Ai => "ai",
+            Opponent::Local => "local",
+            Opponent::Network => "network",
+        }
+    }
+}
+
+/// How a match ended. The player is B and the opponent is A, whichever side the
+/// player took.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchResult {
+    pub winner: char,
+    pub score_a: i32,
+    pub score_b: i32,
+    pub duration: Duration,
+    pub opponent: Opponent,
+}
+
+/// Add `result` to the history at `path`, creating the file with its header if it
+/// doesn't exist yet. `conn` only formats the timestamp, in UTC.
+pub fn append(conn: &Connection, path: &Path, result: &MatchResult) -> Result<()> {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    let finished_at: String = conn.query_row(
+        "SELECT strftime(epoch_ms(?), '%Y-%m-%d %H:%M:%S')",
+        params![now_ms],
+        |row| row.get(0),
+    )?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening history {}", path.display()))?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "{HEADER}")?;
+    }
+    writeln!(
+        file,
+        "{finished_at},{},{},{},{:.1},{}",
+        result.winner,
+        result.score_a,
+        result.score_b,
+        result.duration.as_secs_f64(),
+        result.opponent.name()
+    )?;
+    Ok(())
+}
+
+/// The matches against the AI in the history added up, the player's side first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    pub matches: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub points_won: i64,
+    pub points_lost: i64,
+    pub seconds: f64,
+    /// When the latest match ended, `None` without any.
+    pub last_played: Option<String>,
+}
+
+/// Add up the matches against the AI in the history at `path` in DuckDB. A missing or
+/// empty file is no matches. Files from before the `opponent` column only hold
+/// matches against the AI, so its rows without one count as those.
+pub fn load(conn: &Connection, path: &Path) -> Result<History> {
+    let empty = std::fs::metadata(path).map_or(true, |meta| meta.len() == 0);
+    if empty {
+        return Ok(History::default());
+    }
+    let quoted = path.to_string_lossy().replace('\'', "''");
+    let history = conn
+        .query_row(
+            &format!(
+                "SELECT
+                count(*),
+                count(*) FILTER (WHERE winner = 'B'),
+                count(*) FILTER (WHERE winner = 'A'),
+                coalesce(sum(score_b), 0),
+                coalesce(sum(score_a), 0),
+                coalesce(sum(seconds), 0),
+                CAST(max(finished_at) AS VARCHAR)
+            FROM read_csv('{quoted}', header = true, null_padding = true, columns = {{
+                'finished_at': 'TIMESTAMP', 'winner': 'VARCHAR', 'score_a': 'INTEGER',
+                'score_b': 'INTEGER', 'seconds': 'DOUBLE', 'opponent': 'VARCHAR'
+            }})
+            WHERE coalesce(opponent, 'ai') = 'ai'"
+            ),
+            [],
+            |row| {
+                Ok(History {
+                    matches: row.get(0)?,
+                    wins: row.get(1)?,
+                    losses: row.get(2)?,
+                    points_won: row.get(3)?,
+                    points_lost: row.get(4)?,
+                    seconds: row.get(5)?,
+                    last_played: row.get(6)?,
+                })
+            },
+        )
+        .with_context(|| format!("reading history {}", path.display()))?;
+    Ok(history)
+}
+
+impl History {
+    /// Wins and losses in one line, e.g. `Your record vs AI: 12-7`.
+    pub fn record(&self) -> String {
+        format!("Your record vs AI: {}-{}", self.wins, self.losses)
+    }
+
+    /// The history as a small table, like
+    /// [`MatchStats::summary`](crate::stats::MatchStats::summary).
+    pub fn summary(&self) -> String {
+        if self.matches == 0 {
+            return "No matches against the AI yet\n".to_string();
+        }
+        let rows = [
+            ("Matches", self.matches.to_string()),
+            ("Won", self.wins.to_string()),
+            ("Lost", self.losses.to_string()),
+            ("Points won", self.points_won.to_string()),
+            ("Points lost", self.points_lost.to_string()),
+            ("Time played", format!("{:.0} min", self.seconds / 60.0)),
+            (
+                "Last played",
+                self.last_played.clone().unwrap_or_else(|| "-".to_string()),
+            ),
+        ];
+        let mut summary = format!("{}\n", self.record());
+        for (label, value) in rows {
+            summary.push_str(&format!("  {label:<15}{value:>20}\n"));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_adds_up_the_matches_appended_to_it() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("duckdb-pong-history-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open_in_memory()?;
+        assert_eq!(load(&conn, &path)?, History::default());
+
+        for (winner, score_a, score_b) in [('B', 9, 11), ('A', 11, 4), ('B', 12, 10)] {
+            let result = MatchResult {
+                winner,
+                score_a,
+                score_b,
+                duration: Duration::from_secs(90),
+                opponent: Opponent::Ai,
+            };
+            append(&conn, &path, &result)?;
+        }
+        let history = load(&conn, &path)?;
+        assert_eq!((history.matches, history.wins, history.losses), (3, 2, 1));
+        assert_eq!((history.points_won, history.points_lost), (25, 32));
+        assert_eq!(history.seconds, 270.0);
+        assert!(history.last_played.is_some());
+        assert_eq!(history.record(), "Your record vs AI: 2-1");
+
+        // Matches against people are kept but stay out of the record against the AI
+        for opponent in [Opponent::Local, Opponent::Network] {
+            let result = MatchResult {
+                winner: 'B',
+                score_a: 0,
+                score_b: 11,
+                duration: Duration::from_secs(60),
+                opponent,
+            };
+            append(&conn, &path, &result)?;
+        }
+        assert_eq!(load(&conn, &path)?.record(), "Your record vs AI: 2-1");
+        let _ = std::fs::remove_file(&path);
+
+        // Files from before the opponent column hold matches against the AI only
+        std::fs::write(
+            &path,
+            "finished_at,winner,score_a,score_b,seconds\n2024-01-02 03:04:05,A,11,7,60.0\n",
+        )?;
+        let result = MatchResult {
+            winner: 'B',
+            score_a: 5,
+            score_b: 11,
+            duration: Duration::from_secs(60),
+            opponent: Opponent::Ai,
+        };
+        append(&conn, &path, &result)?;
+        assert_eq!(load(&conn, &path)?.record(), "Your record vs AI: 1-1");
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+}
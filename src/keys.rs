@@ -0,0 +1,319 @@
+//! Key bindings, set in the `[keys]` table of the config file or with `--bind`:
+//!
+//! ```toml
+//! [keys]
+//! up = ["k", "up"]
+//! down = ["j", "down"]
+//! quit = ["q", "esc"]
+//! fps_up = ["+", "pageup"]
+//! ```
+//!
+//! A key is named by its character (letters match either case), `f1` to `f12`, or one
+//! of the names in [`NAMED_KEYS`]. Bindings left out keep their defaults.
+
+use clap::ValueEnum;
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Key names besides single characters and function keys. Where several names mean
+/// the same key, the first one is what [`Key`] displays.
+pub const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("return", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("escape", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("insert", KeyCode::Insert),
+    ("delete", KeyCode::Delete),
+];
+
+/// One key that can be bound, parsed from its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Key(KeyCode);
+
+impl Key {
+    /// Whether pressing `code` means this key. Letters match regardless of Shift.
+    pub fn matches(self, code: KeyCode) -> bool {
+        match (self.0, code) {
+            (KeyCode::Char(bound), KeyCode::Char(pressed)) => bound == pressed.to_ascii_lowercase(),
+            (bound, pressed) => bound == pressed,
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key(KeyCode::Char(c.to_ascii_lowercase())));
+        }
+        let lower = name.to_ascii_lowercase();
+        if let Some(&(_, code)) = NAMED_KEYS.iter().find(|(named, _)| *named == lower) {
+            return Ok(Key(code));
+        }
+        if let Some(n) = lower
+            .strip_prefix('f')
+            .and_then(|n| n.parse().ok())
+            .filter(|n| (1..=12).contains(n))
+        {
+            return Ok(Key(KeyCode::F(n)));
+        }
+        let names: Vec<_> = NAMED_KEYS.iter().map(|(named, _)| *named).collect();
+        Err(format!(
+            "unknown key {name:?}: use a single character, f1 to f12, or one of {}",
+            names.join(", ")
+        ))
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        name.parse()
+    }
+}
+
+impl From<Key> for String {
+    /// The key's name as the config file writes it, in lower case.
+    fn from(key: Key) -> String {
+        key.to_string().to_ascii_lowercase()
+    }
+}
+
+impl fmt::Display for Key {
+    /// Upper case, the way the status line names keys: `ESC`, `SPACE`, `W`, `F1`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, code)| *code == self.0) {
+            return write!(f, "{}", name.to_ascii_uppercase());
+        }
+        match self.0 {
+            KeyCode::Char(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// What a bound key does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Action {
+    /// Move the paddle up.
+    Up,
+    /// Move the paddle down.
+    Down,
+    /// Pause and resume, also during replays.
+    Pause,
+    /// Leave the game from anywhere.
+    Quit,
+    /// Play again from the game over screen.
+    Restart,
+    /// Switch slow motion on and off.
+    Slowmo,
+    /// Raise the frame rate cap while playing.
+    #[value(name = "fps_up")]
+    FpsUp,
+    /// Lower the frame rate cap while playing.
+    #[value(name = "fps_down")]
+    FpsDown,
+    /// Move the left paddle up in local versus play.
+    #[value(name = "left_up")]
+    LeftUp,
+    /// Move the left paddle down in local versus play.
+    #[value(name = "left_down")]
+    LeftDown,
+    /// Launch a serve waiting at your paddle (`--wait-for-serve`).
+    Serve,
+    /// Run the rally at full speed until the next point, and stop doing so early.
+    #[value(name = "fast_forward")]
+    FastForward,
+    /// Watch the last few seconds again in slow motion, and go back to the match early.
+    #[value(name = "instant_replay")]
+    InstantReplay,
+}
+
+/// The keys for every [`Action`]; each action can have several.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub up: Vec<Key>,
+    pub down: Vec<Key>,
+    pub pause: Vec<Key>,
+    pub quit: Vec<Key>,
+    pub restart: Vec<Key>,
+    pub slowmo: Vec<Key>,
+    pub fps_up: Vec<Key>,
+    pub fps_down: Vec<Key>,
+    pub left_up: Vec<Key>,
+    pub left_down: Vec<Key>,
+    pub serve: Vec<Key>,
+    pub fast_forward: Vec<Key>,
+    pub instant_replay: Vec<Key>,
+}
+
+impl Default for KeyBindings {
+    /// Arrows or W/S to move, Space to pause, Esc to quit, Enter to play again, M
+    /// for slow motion, F to fast-forward, R for an instant replay and +/- for the
+    /// frame rate. In local versus play W/S move the left paddle instead, leaving the
+    /// arrows to the right one. Enter also serves, as there is never a serve to launch
+    /// on the game over screen.
+    fn default() -> Self {
+        KeyBindings {
+            up: vec![Key(KeyCode::Up), Key(KeyCode::Char('w'))],
+            down: vec![Key(KeyCode::Down), Key(KeyCode::Char('s'))],
+            pause: vec![Key(KeyCode::Char(' '))],
+            quit: vec![Key(KeyCode::Esc)],
+            restart: vec![Key(KeyCode::Enter)],
+            slowmo: vec![Key(KeyCode::Char('m'))],
+            fps_up: vec![Key(KeyCode::Char('+'))],
+            fps_down: vec![Key(KeyCode::Char('-'))],
+            left_up: vec![Key(KeyCode::Char('w'))],
+            left_down: vec![Key(KeyCode::Char('s'))],
+            serve: vec![Key(KeyCode::Enter)],
+            fast_forward: vec![Key(KeyCode::Char('f'))],
+            instant_replay: vec![Key(KeyCode::Char('r'))],
+        }
+    }
+}
+
+impl KeyBindings {
+    fn all(&self) -> [(Action, &Vec<Key>); 13] {
+        [
+            (Action::Up, &self.up),
+            (Action::Down, &self.down),
+            (Action::Pause, &self.pause),
+            (Action::Quit, &self.quit),
+            (Action::Restart, &self.restart),
+            (Action::Slowmo, &self.slowmo),
+            (Action::FpsUp, &self.fps_up),
+            (Action::FpsDown, &self.fps_down),
+            (Action::LeftUp, &self.left_up),
+            (Action::LeftDown, &self.left_down),
+            (Action::Serve, &self.serve),
+            (Action::FastForward, &self.fast_forward),
+            (Action::InstantReplay, &self.instant_replay),
+        ]
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        match action {
+            Action::Up => &self.up,
+            Action::Down => &self.down,
+            Action::Pause => &self.pause,
+            Action::Quit => &self.quit,
+            Action::Restart => &self.restart,
+            Action::Slowmo => &self.slowmo,
+            Action::FpsUp => &self.fps_up,
+            Action::FpsDown => &self.fps_down,
+            Action::LeftUp => &self.left_up,
+            Action::LeftDown => &self.left_down,
+            Action::Serve => &self.serve,
+            Action::FastForward => &self.fast_forward,
+            Action::InstantReplay => &self.instant_replay,
+        }
+    }
+
+    /// Replace the keys for `action`.
+    pub fn bind(&mut self, action: Action, keys: Vec<Key>) {
+        match action {
+            Action::Up => self.up = keys,
+            Action::Down => self.down = keys,
+            Action::Pause => self.pause = keys,
+            Action::Quit => self.quit = keys,
+            Action::Restart => self.restart = keys,
+            Action::Slowmo => self.slowmo = keys,
+            Action::FpsUp => self.fps_up = keys,
+            Action::FpsDown => self.fps_down = keys,
+            Action::LeftUp => self.left_up = keys,
+            Action::LeftDown => self.left_down = keys,
+            Action::Serve => self.serve = keys,
+            Action::FastForward => self.fast_forward = keys,
+            Action::InstantReplay => self.instant_replay = keys,
+        }
+    }
+
+    /// The action a key press triggers, if any. The left paddle's keys come last, so
+    /// keys they share with `up` and `down` move the only paddle in solo play.
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.all()
+            .into_iter()
+            .find(|(_, keys)| keys.iter().any(|key| key.matches(code)))
+            .map(|(action, _)| action)
+    }
+
+    /// Whether `code` is one of the keys for `action`, for actions that share keys
+    /// with others, which [`action`](Self::action) finds first.
+    pub fn triggers(&self, action: Action, code: KeyCode) -> bool {
+        self.keys(action).iter().any(|key| key.matches(code))
+    }
+
+    /// [`Action::LeftUp`] or [`Action::LeftDown`] if `code` is bound to one; in local
+    /// versus play these come before `up` and `down`.
+    pub fn left_action(&self, code: KeyCode) -> Option<Action> {
+        [Action::LeftUp, Action::LeftDown]
+            .into_iter()
+            .find(|&action| self.triggers(action, code))
+    }
+
+    /// The keys for `action` as the status line shows them, e.g. `UP/W`.
+    pub fn label(&self, action: Action) -> String {
+        let names: Vec<_> = self.keys(action).iter().map(Key::to_string).collect();
+        names.join("/")
+    }
+
+    /// Everything wrong with these bindings: actions without a key, and keys bound
+    /// to more than one action.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let all = self.all();
+        for (i, (action, keys)) in all.iter().enumerate() {
+            if keys.is_empty() {
+                problems.push(format!("keys.{} has no key", name(*action)));
+            }
+            for key in keys.iter() {
+                for (other, other_keys) in &all[i + 1..] {
+                    if other_keys.contains(key) && !may_share(*action, *other) {
+                        problems.push(format!(
+                            "key {key} is bound to both {} and {}",
+                            name(*action),
+                            name(*other)
+                        ));
+                    }
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Whether two actions may have keys in common: the left paddle's keys only count in
+/// local versus play, where they win over `up` and `down`, and there is nothing to
+/// serve on the game over screen, where `restart` counts.
+fn may_share(a: Action, b: Action) -> bool {
+    let moves = |action| matches!(action, Action::Up | Action::Down);
+    let left = |action| matches!(action, Action::LeftUp | Action::LeftDown);
+    let either = |x, y| (x == a && y == b) || (x == b && y == a);
+    (moves(a) && left(b)) || (left(a) && moves(b)) || either(Action::Restart, Action::Serve)
+}
+
+/// The action's name as written in the config file and `--bind`.
+fn name(action: Action) -> String {
+    action
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
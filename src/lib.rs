@@ -0,0 +1,2782 @@
+//! The Pong engine: every rule runs as DuckDB SQL (see [`sql`]), and this crate binds
+//! the input, runs the queries and reads the results back. [`Game`] wraps it all for
+//! embedding; the `duckdb-pong` binary drives it from a terminal.
+//!
+//! ```no_run
+//! use duckdb_pong::{Game, Inputs, PlayerInput, config::GameConfig};
+//!
+//! let mut game = Game::new(GameConfig::default())?;
+//! game.tick(Inputs { b: PlayerInput::Up, ..Inputs::default() })?;
+//! for line in game.render_lines()? {
+//!     println!("{line}");
+//! }
+//! # anyhow::Ok(())
+//! ```
+
+use anyhow::{Context, Result, ensure};
+use clap::ValueEnum;
+use crossterm::event;
+use duckdb::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+pub mod cast;
+pub mod config;
+pub mod heatmap;
+pub mod history;
+pub mod keys;
+pub mod net;
+pub mod palette;
+pub mod record;
+pub mod render;
+pub mod script;
+pub mod sound;
+pub mod sql;
+pub mod stats;
+pub mod status;
+pub mod telemetry;
+pub mod timing;
+
+use config::GameConfig;
+use keys::{Action, KeyBindings};
+use render::{FIELD_TOP, RenderMode, Rendered};
+
+/// Fixed simulation rate: TICK runs this many times per second regardless of the frame rate.
+pub const SIM_HZ: u64 = 60;
+pub const SIM_DT: Duration = Duration::from_nanos(1_000_000_000 / SIM_HZ);
+
+/// Movement requested by the human player for Player B's paddle this frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlayerInput {
+    Up,
+    Down,
+    /// Head for this paddle row, following the mouse
+    Toward(i32),
+    /// Keep the paddle where it is, where `None` leaves it to the AI
+    Hold,
+    #[default]
+    None,
+}
+
+impl PlayerInput {
+    /// The paddle movement `code` is bound to in `keys`.
+    pub fn from_key(code: event::KeyCode, keys: &KeyBindings) -> Self {
+        match keys.action(code) {
+            Some(Action::Up) => PlayerInput::Up,
+            Some(Action::Down) => PlayerInput::Down,
+            _ => PlayerInput::None,
+        }
+    }
+
+    /// The left paddle's movement `code` is bound to in `keys`, for local versus play.
+    pub fn from_left_key(code: event::KeyCode, keys: &KeyBindings) -> Self {
+        match keys.left_action(code) {
+            Some(Action::LeftUp) => PlayerInput::Up,
+            Some(Action::LeftDown) => PlayerInput::Down,
+            _ => PlayerInput::None,
+        }
+    }
+
+    /// What a paddle in local versus play gets while none of its keys are pressed.
+    pub fn idle(idle: IdlePaddle) -> Self {
+        match idle {
+            IdlePaddle::Hold => PlayerInput::Hold,
+            IdlePaddle::Ai => PlayerInput::None,
+        }
+    }
+
+    /// Center the paddle on the mouse pointer. Pointers outside the field are ignored.
+    pub fn from_mouse(column: u16, row: u16, config: &GameConfig) -> Self {
+        let field_rows = FIELD_TOP..FIELD_TOP + config.height;
+        if column >= config.width || !field_rows.contains(&row) {
+            return PlayerInput::None;
+        }
+        // The paddle moves in simulated pixels, which hi-res mode packs two to a row
+        let scale = i32::from(config.pixels_per_row());
+        let paddle_h = i32::from(config.paddle_height()) * scale;
+        let top = i32::from(row - FIELD_TOP) * scale - paddle_h / 2;
+        PlayerInput::Toward(top.clamp(1, i32::from(config.height) * scale - paddle_h - 1))
+    }
+
+    /// Direction bound into [`sql::TICK`]: -1 moves up, +1 moves down, 0 holds still
+    /// and `None` lets the AI play, or the mouse with [`PlayerInput::Toward`].
+    pub fn direction(self) -> Option<i32> {
+        match self {
+            PlayerInput::Up => Some(-1),
+            PlayerInput::Down => Some(1),
+            PlayerInput::Hold => Some(0),
+            PlayerInput::Toward(_) | PlayerInput::None => None,
+        }
+    }
+
+    /// Paddle row bound into [`sql::TICK`] for mouse control.
+    pub fn target(self) -> Option<i32> {
+        match self {
+            PlayerInput::Toward(row) => Some(row),
+            _ => None,
+        }
+    }
+}
+
+/// AI strength. Each level sets how often the AI follows a far-away ball (accuracy)
+/// and how many rows from either paddle end the ball may get before it reacts
+/// (margin); the rows in between are the dead zone where the paddle stays put.
+///
+/// | level  | accuracy | margin | dead zone on a 7-row paddle |
+/// |--------|----------|--------|-----------------------------|
+/// | easy   | 0.60     | 1      | 5 rows                      |
+/// | normal | 0.85     | 2      | 3 rows                      |
+/// | hard   | 0.97     | 3      | 1 row                       |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// How the AI decides where to put its paddle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiStyle {
+    /// Follow the ball's current row, with the [`Difficulty`]'s accuracy, and aim for
+    /// trick shots once it is close
+    #[default]
+    Reactive,
+    /// Work out where the ball will reach the paddle, bounces included, and wait
+    /// there, off by up to `ai_error` rows. The [`Difficulty`] doesn't change it.
+    Predictive,
+}
+
+impl AiStyle {
+    /// The style as the `AI` parameter of [`sql::SETUP`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            AiStyle::Reactive => "reactive",
+            AiStyle::Predictive => "predictive",
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn accuracy(self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.60,
+            Difficulty::Normal => 0.85,
+            Difficulty::Hard => 0.97,
+        }
+    }
+
+    pub fn margin(self) -> i32 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Normal => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+}
+
+/// Which way the opening serve of a match goes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirstServe {
+    /// Toward Player A, on the left
+    A,
+    /// Toward Player B, on the right
+    B,
+    /// Either way with equal chances
+    #[default]
+    Random,
+}
+
+impl FirstServe {
+    /// The choice as the `FIRST_SERVE` parameter of [`sql::SETUP`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            FirstServe::A => "a",
+            FirstServe::B => "b",
+            FirstServe::Random => "random",
+        }
+    }
+}
+
+/// Which way the ball is served after a point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServeRule {
+    /// Toward whoever scored
+    #[default]
+    Winner,
+    /// Toward whoever lost the point, as in classic Pong
+    Loser,
+    /// Toward each side in turn, whoever scored
+    Alternate,
+    /// Either way with equal chances
+    Random,
+}
+
+impl ServeRule {
+    /// The rule as the `SERVE` parameter of [`sql::SETUP`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            ServeRule::Winner => "winner",
+            ServeRule::Loser => "loser",
+            ServeRule::Alternate => "alternate",
+            ServeRule::Random => "random",
+        }
+    }
+}
+
+/// What happens to a rally that reaches the `rally_timeout` without a point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeoutAction {
+    /// Call a let: every ball is served again from the middle and nobody scores
+    #[default]
+    Let,
+    /// Play on with every ball one column per tick faster, up to the top speed; the
+    /// next timeout speeds them up again
+    Speedup,
+}
+
+impl TimeoutAction {
+    /// The action as the `ON_RALLY_TIMEOUT` parameter of [`sql::SETUP`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeoutAction::Let => "let",
+            TimeoutAction::Speedup => "speedup",
+        }
+    }
+}
+
+/// Who plays whom.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlayMode {
+    /// One player against the AI, or against a remote player with --host
+    #[default]
+    Solo,
+    /// Two players at one keyboard: the left paddle on its own keys (W/S), the right
+    /// one on the usual keys (the arrows)
+    LocalVs,
+}
+
+/// What a paddle in local versus play does while none of its keys are pressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdlePaddle {
+    /// Stay where it is
+    #[default]
+    Hold,
+    /// Leave it to the AI until a key is pressed again
+    Ai,
+}
+
+/// Which paddle the local player steers; the AI plays the other one. Player A stays
+/// on the left and B on the right either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    /// Player A's paddle
+    Left,
+    /// Player B's paddle
+    #[default]
+    Right,
+}
+
+impl Side {
+    /// The side as the `HUMAN_SIDE` parameter of [`sql::SETUP`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+
+    /// The player on this side, `'A'` or `'B'`.
+    pub fn player(self) -> char {
+        match self {
+            Side::Left => 'A',
+            Side::Right => 'B',
+        }
+    }
+}
+
+/// How steep a serve starts, the opening one and a new set's included. Each mode picks
+/// the vertical speed, in steps of the ball speed, with these chances:
+///
+/// | mode     | -2  | -1  |  0  | +1  | +2  |
+/// |----------|-----|-----|-----|-----|-----|
+/// | `flat`   |  5% | 20% | 50% | 20% |  5% |
+/// | `random` | 20% | 20% | 20% | 20% | 20% |
+/// | `wide`   | 35% | 10% | 10% | 10% | 35% |
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServeAngle {
+    /// Mostly straight across, for gentler serves
+    Flat,
+    /// Every angle equally likely
+    #[default]
+    Random,
+    /// Mostly the steepest angles
+    Wide,
+}
+
+impl ServeAngle {
+    /// The mode as the `SERVE_ANGLE` parameter of [`sql::SETUP`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            ServeAngle::Flat => "flat",
+            ServeAngle::Random => "random",
+            ServeAngle::Wide => "wide",
+        }
+    }
+}
+
+/// Advance the game one tick with Player A played by the AI.
+pub fn tick(conn: &Connection, input: PlayerInput, difficulty: Difficulty) -> Result<()> {
+    tick_versus(conn, None, input, difficulty)
+}
+
+/// Like [`tick`], but with Player A steered by a remote player when `remote_a` is set:
+/// -1 up, +1 down, 0 to hold still.
+pub fn tick_versus(
+    conn: &Connection,
+    remote_a: Option<i32>,
+    input: PlayerInput,
+    difficulty: Difficulty,
+) -> Result<()> {
+    let rolls = Rolls::draw(conn)?;
+    tick_rolled(conn, remote_a, input, difficulty, rolls, 0)
+}
+
+/// Like [`tick_versus`], with the tick's random numbers given instead of drawn, and
+/// the points Player A trails by for the adaptive AI (0 to play the same at any
+/// score), see [`sql::TICK`].
+pub fn tick_rolled(
+    conn: &Connection,
+    remote_a: Option<i32>,
+    input: PlayerInput,
+    difficulty: Difficulty,
+    rolls: Rolls,
+    score_gap: i32,
+) -> Result<()> {
+    conn.execute(
+        sql::TICK,
+        params![
+            input.direction(),
+            difficulty.accuracy(),
+            difficulty.margin(),
+            input.target(),
+            remote_a,
+            rolls.ai_a,
+            rolls.ai_b,
+            rolls.serve_row,
+            rolls.serve_angle,
+            rolls.serve_side,
+            score_gap
+        ],
+    )
+    .context("running the tick query (TICK)")?;
+    Ok(())
+}
+
+/// The random numbers one tick consumes, each uniform in `[0, 1)`. See
+/// [`sql::TICK`] for what each one decides.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rolls {
+    pub ai_a: f64,
+    pub ai_b: f64,
+    pub serve_row: f64,
+    pub serve_angle: f64,
+    pub serve_side: f64,
+}
+
+impl Rolls {
+    /// Draw from DuckDB's generator, so `--seed` still makes a game repeatable.
+    pub fn draw(conn: &Connection) -> Result<Self> {
+        let rolls = conn
+            .query_row(
+                "SELECT random(), random(), random(), random(), random()",
+                [],
+                |row| {
+                    Ok(Rolls {
+                        ai_a: row.get(0)?,
+                        ai_b: row.get(1)?,
+                        serve_row: row.get(2)?,
+                        serve_angle: row.get(3)?,
+                        serve_side: row.get(4)?,
+                    })
+                },
+            )
+            .context("drawing the tick's random rolls")?;
+        Ok(rolls)
+    }
+}
+
+/// Run [`sql::POWERUPS`] and [`sql::SPAWN_POWERUP`] for the tick that just ran.
+pub fn update_powerups(conn: &Connection, rolls: PowerUpRolls) -> Result<()> {
+    conn.execute_batch(sql::POWERUPS)
+        .context("running the power-up queries (POWERUPS)")?;
+    conn.execute(
+        sql::SPAWN_POWERUP,
+        params![rolls.spawn, rolls.x, rolls.y, rolls.kind],
+    )
+    .context("running the power-up spawn query (SPAWN_POWERUP)")?;
+    Ok(())
+}
+
+/// The random numbers [`sql::SPAWN_POWERUP`] consumes, each uniform in `[0, 1)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerUpRolls {
+    pub spawn: f64,
+    pub x: f64,
+    pub y: f64,
+    pub kind: f64,
+}
+
+impl PowerUpRolls {
+    /// Draw from DuckDB's generator, like [`Rolls::draw`].
+    pub fn draw(conn: &Connection) -> Result<Self> {
+        let rolls = conn
+            .query_row("SELECT random(), random(), random(), random()", [], |row| {
+                Ok(PowerUpRolls {
+                    spawn: row.get(0)?,
+                    x: row.get(1)?,
+                    y: row.get(2)?,
+                    kind: row.get(3)?,
+                })
+            })
+            .context("drawing the power-up rolls")?;
+        Ok(rolls)
+    }
+}
+
+/// Columns of the `state` table that make up a [`State`], in order.
+pub const STATE_COLUMNS: &str = "tick, ax, bx, ball_x, ball_y, vx, vy, score_a, score_b";
+
+/// A snapshot of the `state` row (primary ball only in multi-ball mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    pub tick: i32,
+    pub ax: i32,
+    pub bx: i32,
+    pub ball_x: i32,
+    pub ball_y: i32,
+    pub vx: i32,
+    pub vy: i32,
+    pub score_a: i32,
+    pub score_b: i32,
+}
+
+impl State {
+    /// Build a `State` from a row selecting [`STATE_COLUMNS`].
+    pub fn from_row(row: &duckdb::Row) -> duckdb::Result<Self> {
+        Ok(State {
+            tick: row.get(0)?,
+            ax: row.get(1)?,
+            bx: row.get(2)?,
+            ball_x: row.get(3)?,
+            ball_y: row.get(4)?,
+            vx: row.get(5)?,
+            vy: row.get(6)?,
+            score_a: row.get(7)?,
+            score_b: row.get(8)?,
+        })
+    }
+}
+
+/// Read the current [`State`] row.
+pub fn read_state(conn: &Connection) -> Result<State> {
+    let state = conn
+        .query_row(
+            &format!("SELECT {STATE_COLUMNS} FROM state"),
+            [],
+            State::from_row,
+        )
+        .context("reading the game state")?;
+    Ok(state)
+}
+
+/// Whether the game tables already exist, e.g. in a database file saved earlier.
+pub fn state_exists(conn: &Connection) -> Result<bool> {
+    let exists = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM duckdb_tables() WHERE table_name = 'state'",
+            [],
+            |row| row.get(0),
+        )
+        .context("looking for the game tables")?;
+    Ok(exists)
+}
+
+/// Read back the settings a game was set up with from its `params` table.
+pub fn read_settings(conn: &Connection) -> Result<sql::Settings> {
+    let (
+        mut settings,
+        [
+            serve,
+            serve_angle,
+            border,
+            ai,
+            human_side,
+            timeout,
+            first_serve,
+        ],
+    ) = conn
+        .query_row(
+            "SELECT W, H, PADDLE_H, PADDLE_W, PADDLE_SPEED, BALL_SPEED, MAX_BALL_SPEED,
+                SPEEDUP_HITS, SPIN, MOMENTUM, AI_REACTION_A, AI_REACTION_B, SERVE_TICKS,
+                TARGET_SCORE, SETS, BALLS, POWERUPS, POWERUP_ROWS, CENTER_LINE, AI_ERROR,
+                TRAINING, LIVES, DETERMINISTIC_AI, RALLY_TIMEOUT, WAIT_FOR_SERVE, RAMP,
+                WRAP, BIG_BALL, ASPECT_CORRECT, ZONES, SERVE, SERVE_ANGLE, BORDER, AI,
+                HUMAN_SIDE, ON_RALLY_TIMEOUT, FIRST_SERVE
+         FROM params",
+            [],
+            |row| {
+                let settings = sql::Settings {
+                    width: row.get(0)?,
+                    height: row.get(1)?,
+                    paddle_h: row.get(2)?,
+                    paddle_w: row.get(3)?,
+                    paddle_speed: row.get(4)?,
+                    ball_speed: row.get(5)?,
+                    max_ball_speed: row.get(6)?,
+                    speedup_hits: row.get(7)?,
+                    spin: row.get(8)?,
+                    momentum: row.get(9)?,
+                    ai_reaction_a: row.get(10)?,
+                    ai_reaction_b: row.get(11)?,
+                    serve_ticks: row.get(12)?,
+                    target_score: row.get(13)?,
+                    sets: row.get(14)?,
+                    balls: row.get(15)?,
+                    powerups: row.get(16)?,
+                    powerup_rows: row.get(17)?,
+                    center_line: row.get(18)?,
+                    ai_error: row.get(19)?,
+                    training: row.get(20)?,
+                    lives: row.get(21)?,
+                    deterministic_ai: row.get(22)?,
+                    rally_timeout: row.get(23)?,
+                    wait_for_serve: row.get(24)?,
+                    ramp: row.get(25)?,
+                    wrap: row.get(26)?,
+                    big_ball: row.get(27)?,
+                    aspect_correct: row.get(28)?,
+                    zones: row.get(29)?,
+                    first_serve: FirstServe::default(),
+                    serve: ServeRule::default(),
+                    serve_angle: ServeAngle::default(),
+                    border: render::BorderStyle::default(),
+                    ai: AiStyle::default(),
+                    human_side: Side::default(),
+                    on_rally_timeout: TimeoutAction::default(),
+                };
+                // The enums are stored by name and parsed below
+                let names: [String; 7] = [
+                    row.get(30)?,
+                    row.get(31)?,
+                    row.get(32)?,
+                    row.get(33)?,
+                    row.get(34)?,
+                    row.get(35)?,
+                    row.get(36)?,
+                ];
+                Ok((settings, names))
+            },
+        )
+        .context("reading the game settings (params)")?;
+    settings.serve = ServeRule::from_str(&serve, true).map_err(anyhow::Error::msg)?;
+    settings.serve_angle = ServeAngle::from_str(&serve_angle, true).map_err(anyhow::Error::msg)?;
+    settings.border = render::BorderStyle::from_str(&border, true).map_err(anyhow::Error::msg)?;
+    settings.ai = AiStyle::from_str(&ai, true).map_err(anyhow::Error::msg)?;
+    settings.human_side = Side::from_str(&human_side, true).map_err(anyhow::Error::msg)?;
+    settings.on_rally_timeout =
+        TimeoutAction::from_str(&timeout, true).map_err(anyhow::Error::msg)?;
+    settings.first_serve = FirstServe::from_str(&first_serve, true).map_err(anyhow::Error::msg)?;
+    Ok(settings)
+}
+
+/// Check that a game fresh from [`sql::SETUP`] starts sane: paddles inside the field,
+/// the ball in the center column heading left or right at the ball speed at one of the
+/// five serve angles, and nobody scored yet. The error names the first thing that
+/// isn't, so changes to how the setup scales with the field show up right away.
+pub fn check_initial_state(conn: &Connection) -> Result<()> {
+    let settings = read_settings(conn)?;
+    let state = read_state(conn)?;
+    let (width, height) = (settings.width as i32, settings.height as i32);
+    let (paddle_h, speed) = (settings.paddle_h as i32, settings.ball_speed as i32);
+    // A big ball's top row stays a row higher, so its lower one is inside too
+    let lowest = if settings.big_ball {
+        height - 3
+    } else {
+        height - 2
+    };
+    ensure!(state.tick == 0, "tick is {} instead of 0", state.tick);
+    for (player, top) in [('A', state.ax), ('B', state.bx)] {
+        ensure!(
+            top >= 1 && top + paddle_h < height,
+            "paddle {player} covers rows {top} to {} of a field with rows 1 to {}",
+            top + paddle_h - 1,
+            height - 2
+        );
+    }
+    // An odd width has two columns equally close to the middle
+    ensure!(
+        (width / 2..=(width + 1) / 2).contains(&state.ball_x),
+        "ball in column {} instead of the center column {} of {width}",
+        state.ball_x,
+        width / 2
+    );
+    ensure!(
+        (1..=lowest).contains(&state.ball_y),
+        "ball in row {} outside the field's rows 1 to {lowest}",
+        state.ball_y
+    );
+    ensure!(
+        state.vx.abs() == speed,
+        "ball vx is {} instead of ±{speed}",
+        state.vx
+    );
+    ensure!(
+        state.vy % speed == 0 && (-2..=2).contains(&(state.vy / speed)),
+        "ball vy is {} instead of -2 to 2 times {speed}",
+        state.vy
+    );
+    ensure!(
+        (state.score_a, state.score_b) == (0, 0),
+        "score is {}-{} before the first serve",
+        state.score_a,
+        state.score_b
+    );
+    Ok(())
+}
+
+/// Seed DuckDB's random() so every following query draws the same sequence.
+pub fn seed_rng(conn: &Connection, seed: f64) -> Result<()> {
+    conn.query_row("SELECT setseed(?)", params![seed], |_| Ok(()))
+        .context("seeding the random generator")?;
+    Ok(())
+}
+
+/// A player reaching the target score with a two-point lead, with the winning side
+/// ('A' or 'B').
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Win {
+    /// Won a set, but the match goes on.
+    Set(char),
+    /// Won the set that gave them more than half of `--sets`.
+    Match(char),
+}
+
+/// Returns who won what once a player has reached the target score two points clear
+/// of the other. From one point short of the target each, play goes on until one of
+/// them pulls two ahead, see [`Deuce`].
+pub fn check_winner(conn: &Connection) -> Result<Option<Win>> {
+    let (winner, decided): (Option<String>, bool) = conn
+        .query_row(
+            "WITH won AS (
+             SELECT s.score_a >= p.TARGET_SCORE AND s.score_a - s.score_b >= 2 AS a,
+                    s.score_b >= p.TARGET_SCORE AND s.score_b - s.score_a >= 2 AS b
+             FROM state s, params p
+         )
+         SELECT CASE WHEN won.a THEN 'A' WHEN won.b THEN 'B' END,
+                -- Counting the set being won now, first to ceil(SETS / 2) takes the match
+                greatest(s.set_a + won.a::INTEGER, s.set_b + won.b::INTEGER) >= (p.SETS + 1) // 2
+         FROM state s, params p, won",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .context("checking for a winner")?;
+    Ok(winner
+        .and_then(|w| w.chars().next())
+        .map(|w| if decided { Win::Match(w) } else { Win::Set(w) }))
+}
+
+/// A close finish to a set: both players are within a point of the target, so the
+/// set goes on until one of them leads by two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deuce {
+    /// Level scores.
+    Deuce,
+    /// One point ahead, one more wins the set.
+    Advantage(char),
+}
+
+impl Deuce {
+    /// The deuce situation at these scores, or `None` while the set is not that close.
+    pub fn from_scores(score_a: i32, score_b: i32, target_score: u32) -> Option<Self> {
+        let close = i64::from(target_score) - 1;
+        if i64::from(score_a.min(score_b)) < close {
+            return None;
+        }
+        match score_a - score_b {
+            0 => Some(Deuce::Deuce),
+            1 => Some(Deuce::Advantage('A')),
+            -1 => Some(Deuce::Advantage('B')),
+            // Two clear: the set is already won
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Deuce {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Deuce::Deuce => write!(f, "DEUCE"),
+            Deuce::Advantage(side) => write!(f, "ADVANTAGE {side}"),
+        }
+    }
+}
+
+/// Count the set that was just won and serve the next one; returns the set counts.
+pub fn start_next_set(conn: &Connection) -> Result<(i32, i32)> {
+    conn.execute(sql::NEW_SET, [])
+        .context("running the new set query (NEW_SET)")?;
+    let sets = conn
+        .query_row("SELECT set_a, set_b FROM state", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .context("reading the set counts")?;
+    Ok(sets)
+}
+
+/// What steers the paddles for one tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Inputs {
+    /// Player A: `None` lets the AI play, otherwise -1 up, +1 down or 0 to hold still.
+    pub a: Option<i32>,
+    /// The local player, steering Player B, or Player A with [`Side::Left`] as the
+    /// `human_side`: [`PlayerInput::None`] lets the AI play.
+    pub b: PlayerInput,
+}
+
+/// A match in a DuckDB database, with the settings it was set up with.
+pub struct Game {
+    conn: Connection,
+    config: GameConfig,
+    /// The tables live in a database file instead of being temporary.
+    persistent: bool,
+}
+
+impl Game {
+    /// Start a match in an in-memory database.
+    pub fn new(config: GameConfig) -> Result<Self> {
+        Game::open(config, None, false, None)
+    }
+
+    /// Start a match stored in the database file at `path`, or in memory without one.
+    /// With `resume` a match already in that file is continued with the settings it
+    /// was saved with, which replace those in `config`. `seed` seeds DuckDB's
+    /// `random()` before the first serve.
+    pub fn open(
+        mut config: GameConfig,
+        path: Option<&Path>,
+        resume: bool,
+        seed: Option<f64>,
+    ) -> Result<Self> {
+        let conn = match path {
+            Some(path) => Connection::open(path)?,
+            None => Connection::open_in_memory()?,
+        };
+        if let Some(seed) = seed {
+            seed_rng(&conn, seed)?;
+        }
+        let persistent = path.is_some();
+        if resume && state_exists(&conn)? {
+            config.adopt(&read_settings(&conn)?);
+        } else {
+            conn.execute(&config.setup_sql(persistent), [])
+                .context("running the setup batch (SETUP)")?;
+        }
+        Ok(Game {
+            conn,
+            config,
+            persistent,
+        })
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// The database holding the game, for queries beyond what `Game` offers.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Advance the simulation by one tick.
+    pub fn tick(&mut self, input: Inputs) -> Result<()> {
+        let rolls = Rolls::draw(&self.conn)?;
+        self.tick_with_rolls(input, rolls)
+    }
+
+    /// Like [`tick`](Self::tick), with the tick's random numbers given instead of drawn.
+    pub fn tick_with_rolls(&mut self, input: Inputs, rolls: Rolls) -> Result<()> {
+        let score_gap = if self.config.adaptive_ai {
+            let state = self.state()?;
+            state.score_b - state.score_a
+        } else {
+            0
+        };
+        tick_rolled(
+            &self.conn,
+            input.a,
+            input.b,
+            self.config.difficulty,
+            rolls,
+            score_gap,
+        )?;
+        if self.config.powerups {
+            update_powerups(&self.conn, PowerUpRolls::draw(&self.conn)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn state(&self) -> Result<State> {
+        read_state(&self.conn)
+    }
+
+    /// The rows players A's and B's AI last decided to move their paddles to.
+    pub fn ai_targets(&self) -> Result<(i32, i32)> {
+        let targets = self
+            .conn
+            .query_row("SELECT ai_target_a, ai_target_b FROM state", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .context("reading the AI targets")?;
+        Ok(targets)
+    }
+
+    /// Fetch the field with the render query `mode` picks.
+    pub fn render(&self, mode: RenderMode) -> Result<Rendered> {
+        render::query(
+            &self.conn,
+            mode,
+            self.config.hires,
+            self.config.glyphs(),
+            self.config.width,
+            self.config.height,
+        )
+    }
+
+    /// The field as plain text, one string per terminal row, in the configured glyphs.
+    pub fn render_lines(&self) -> Result<Vec<String>> {
+        let field = render::query_field(
+            &self.conn,
+            self.config.hires,
+            self.config.width,
+            self.config.height,
+        )?;
+        Ok(field
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|&tile| self.config.glyphs().glyph(tile))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Who won what, once a player has reached the target score.
+    pub fn winner(&self) -> Result<Option<Win>> {
+        check_winner(&self.conn)
+    }
+
+    /// Balls the player may still miss in training, `None` outside of it. A session
+    /// is over at 0.
+    pub fn lives(&self) -> Result<Option<i32>> {
+        if !self.config.training {
+            return Ok(None);
+        }
+        let lives = self
+            .conn
+            .query_row("SELECT lives FROM state", [], |row| row.get(0))
+            .context("reading the lives left")?;
+        Ok(Some(lives))
+    }
+
+    /// Count the set that was just won and serve the next one; returns the set counts.
+    pub fn next_set(&mut self) -> Result<(i32, i32)> {
+        start_next_set(&self.conn)
+    }
+
+    /// Launch a serve waiting at the player's paddle with `wait_for_serve`; the ball
+    /// leaves at the angle the serve was given. Does nothing when no serve waits.
+    pub fn serve(&mut self) -> Result<()> {
+        self.conn
+            .execute("UPDATE state SET awaiting_serve = false", [])
+            .context("launching the serve")?;
+        Ok(())
+    }
+
+    /// Whether a serve waits for the player to launch it, see [`Game::serve`].
+    pub fn awaiting_serve(&self) -> Result<bool> {
+        let awaiting = self
+            .conn
+            .query_row("SELECT awaiting_serve FROM state", [], |row| row.get(0))
+            .context("checking for a waiting serve")?;
+        Ok(awaiting)
+    }
+
+    /// Start a new match with the same settings.
+    pub fn restart(&mut self) -> Result<()> {
+        // Re-running the setup replaces params and state, resetting scores, sets and
+        // positions
+        self.conn
+            .execute(&self.config.setup_sql(self.persistent), [])
+            .context("running the setup batch (SETUP)")?;
+        Ok(())
+    }
+
+    /// Switch to `settings`, e.g. the ones a network host sent, and start over.
+    pub fn adopt(&mut self, settings: &sql::Settings) -> Result<()> {
+        self.config.adopt(settings);
+        self.restart()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn up_input_moves_player_b_paddle_up() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        conn.execute("UPDATE state SET bx = 10", [])?;
+
+        tick(&conn, PlayerInput::Up, Difficulty::Normal)?;
+
+        let bx: i32 = conn.query_row("SELECT bx FROM state", [], |row| row.get(0))?;
+        assert!(bx < 10, "expected paddle to move up, got bx = {bx}");
+        Ok(())
+    }
+
+    #[test]
+    fn human_side_left_steers_player_a_and_leaves_b_to_the_ai() -> Result<()> {
+        let settings = sql::Settings {
+            human_side: Side::Left,
+            ..sql::Settings::default()
+        };
+        for input in [PlayerInput::Up, PlayerInput::Toward(3)] {
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            // The ball is in the middle of B's dead zone, so B's AI stays put
+            conn.execute(
+                "UPDATE state SET ax = 10, bx = 10, ball_x = 40, ball_y = 13, vx = 1, vy = 0",
+                [],
+            )?;
+            tick_rolled(&conn, None, input, Difficulty::Normal, NEUTRAL_ROLLS, 0)?;
+            let state = read_state(&conn)?;
+            assert_eq!((state.ax, state.bx), (8, 10), "{input:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn local_versus_paddles_hold_or_fall_back_to_the_ai() -> Result<()> {
+        // The ball is far above both paddles, so either AI would go after it
+        for (a, b, ax, bx) in [
+            (Some(0), PlayerInput::Hold, 10, 10),
+            (Some(1), PlayerInput::Up, 12, 8),
+            (Some(-1), PlayerInput::None, 8, 8),
+        ] {
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+            conn.execute(
+                "UPDATE state SET ax = 10, bx = 10, ball_x = 40, ball_y = 3, vx = 1, vy = 0",
+                [],
+            )?;
+            tick_rolled(&conn, a, b, Difficulty::Normal, NEUTRAL_ROLLS, 0)?;
+            let state = read_state(&conn)?;
+            assert_eq!((state.ax, state.bx), (ax, bx), "{a:?} {b:?}");
+        }
+
+        let keys = KeyBindings::default();
+        assert!(keys.problems().is_empty());
+        let w = event::KeyCode::Char('W');
+        assert_eq!(PlayerInput::from_left_key(w, &keys), PlayerInput::Up);
+        assert_eq!(PlayerInput::from_key(w, &keys), PlayerInput::Up);
+        let arrow = event::KeyCode::Down;
+        assert_eq!(PlayerInput::from_left_key(arrow, &keys), PlayerInput::None);
+        assert_eq!(PlayerInput::idle(IdlePaddle::Ai), PlayerInput::None);
+        Ok(())
+    }
+
+    #[test]
+    fn setup_starts_sane_at_any_seed_and_size() -> Result<()> {
+        for (width, height, ball_speed) in [(80, 25, 1), (20, 10, 1), (81, 31, 3)] {
+            let settings = sql::Settings {
+                width,
+                height,
+                ball_speed,
+                ..sql::Settings::default()
+            };
+            for seed in [-0.9, -0.3, 0.0, 0.25, 0.5, 0.99] {
+                let conn = Connection::open_in_memory()?;
+                seed_rng(&conn, seed)?;
+                conn.execute(&sql::build_setup(&settings, false), [])?;
+                check_initial_state(&conn)?;
+            }
+        }
+
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        conn.execute("UPDATE state SET vx = 2", [])?;
+        let error = check_initial_state(&conn).unwrap_err().to_string();
+        assert_eq!(error, "ball vx is 2 instead of ±1");
+        Ok(())
+    }
+
+    #[test]
+    fn opening_serves_stay_within_two_steps_of_flat() -> Result<()> {
+        let settings = sql::Settings {
+            balls: 9,
+            ..sql::Settings::default()
+        };
+        // Rounding instead of flooring the roll turns about one draw in ten into a 3,
+        // so a few hundred of them are all but sure to show it
+        for n in 0..40 {
+            let conn = Connection::open_in_memory()?;
+            seed_rng(&conn, f64::from(n) / 20.0 - 1.0)?;
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            let steepest: i32 = conn.query_row(
+                "SELECT greatest(abs(vy), list_max(list_transform(extra_balls, lambda b: abs(b.vy))))
+                FROM state",
+                [],
+                |row| row.get(0),
+            )?;
+            assert!(steepest <= 2, "seed {n} served vy {steepest}");
+        }
+
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        conn.execute("UPDATE state SET vy = 3", [])?;
+        let error = check_initial_state(&conn).unwrap_err().to_string();
+        assert_eq!(error, "ball vy is 3 instead of -2 to 2 times 1");
+        Ok(())
+    }
+
+    #[test]
+    fn first_serve_heads_for_the_chosen_side_at_any_seed() -> Result<()> {
+        for (first_serve, dir) in [(FirstServe::A, -1), (FirstServe::B, 1)] {
+            let settings = sql::Settings {
+                first_serve,
+                balls: 3,
+                ..sql::Settings::default()
+            };
+            for seed in [-0.9, -0.3, 0.0, 0.25, 0.5, 0.99] {
+                let conn = Connection::open_in_memory()?;
+                seed_rng(&conn, seed)?;
+                conn.execute(&sql::build_setup(&settings, false), [])?;
+                // The primary ball, the serve and both extra balls
+                let dirs: (i32, i32, i64) = conn.query_row(
+                    "SELECT sign(vx), serve_dir,
+                        len(list_filter(extra_balls, lambda b: sign(b.vx) = ?))
+                    FROM state",
+                    params![dir],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )?;
+                assert_eq!(dirs, (dir, dir, 2), "{first_serve:?} at seed {seed}");
+                assert_eq!(read_settings(&conn)?.first_serve, first_serve);
+            }
+        }
+        Ok(())
+    }
+
+    fn seeded_run(seed: f64, ticks: usize) -> Result<String> {
+        let conn = Connection::open_in_memory()?;
+        seed_rng(&conn, seed)?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        for _ in 0..ticks {
+            tick(&conn, PlayerInput::None, Difficulty::Normal)?;
+        }
+        state_row(&conn)
+    }
+
+    fn state_row(conn: &Connection) -> Result<String> {
+        let state = conn.query_row(
+            "SELECT concat_ws(',', tick, ax, bx, ball_x, ball_y, vx, vy, score_a, score_b) FROM state",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(state)
+    }
+
+    #[test]
+    fn same_seed_replays_identical_game() -> Result<()> {
+        assert_eq!(seeded_run(0.42, 100)?, seeded_run(0.42, 100)?);
+        Ok(())
+    }
+
+    #[test]
+    fn scripts_steer_both_paddles_and_play_out_the_same_every_time() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("duckdb-pong-script-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "tick,a,b,ax,bx,ball_x,ball_y,vx,vy\n0,1,-1,5,10,40,12,-1,1\n3,0,\n5,,\n",
+        )?;
+        let play = || -> Result<Vec<State>> {
+            let mut game = Game::open(GameConfig::default(), None, false, Some(0.0))?;
+            let mut script = script::Script::load(game.connection(), &path)?;
+            let mut states = Vec::new();
+            while let Some(inputs) = script.step(game.connection(), game.state()?.tick)? {
+                game.tick_with_rolls(inputs, script::Script::ROLLS)?;
+                states.push(game.state()?);
+            }
+            Ok(states)
+        };
+        let states = play()?;
+        let _ = std::fs::remove_file(&path);
+        // Ticks 0 to 5 run, the first from the positions the script put things at
+        assert_eq!(states.len(), 6);
+        assert_eq!((states[0].ball_x, states[0].ball_y), (39, 13));
+        // A moves down for three ticks and then holds, B keeps moving up
+        let paddles: Vec<_> = states.iter().map(|s| (s.ax, s.bx)).collect();
+        assert_eq!(
+            paddles,
+            [(7, 8), (9, 6), (11, 4), (11, 2), (11, 1), (11, 1)]
+        );
+        assert_eq!(play()?, states);
+        Ok(())
+    }
+
+    /// Put Player A's paddle at rows 10..=16 and a ball at `ball_x` on row 13 heading
+    /// straight for it, then run one tick.
+    fn ball_towards_paddle_a(ball_speed: u32, ball_x: i32) -> Result<State> {
+        let conn = Connection::open_in_memory()?;
+        let settings = sql::Settings {
+            ball_speed,
+            ..sql::Settings::default()
+        };
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        conn.execute(
+            "UPDATE state SET ax = 10, ball_x = ?, ball_y = 13, vx = -?, vy = 0",
+            params![ball_x, ball_speed],
+        )?;
+        tick(&conn, PlayerInput::None, Difficulty::Normal)?;
+        read_state(&conn)
+    }
+
+    #[test]
+    fn fast_ball_bounces_off_paddle_instead_of_passing_through() -> Result<()> {
+        // Each of these would end the tick behind the paddle column at x = 1
+        for (ball_speed, ball_x) in [(2, 2), (3, 2), (3, 3), (5, 4)] {
+            let state = ball_towards_paddle_a(ball_speed, ball_x)?;
+            assert_eq!(
+                state.score_b, 0,
+                "speed {ball_speed} from x = {ball_x} scored"
+            );
+            assert_eq!(state.ball_x, 1);
+            assert_eq!(state.vx, ball_speed as i32);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ball_speeds_up_every_few_hits_and_resets_on_serve() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+
+        // The fourth hit of the rally adds one to the ball's horizontal speed
+        conn.execute(
+            "UPDATE state SET ax = 10, ball_x = 2, ball_y = 13, vx = -1, vy = 0, hits = 3",
+            [],
+        )?;
+        tick(&conn, PlayerInput::None, Difficulty::Normal)?;
+        let (vx, hits): (i32, i32) = conn.query_row("SELECT vx, hits FROM state", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        assert_eq!((vx, hits), (2, 4));
+
+        // A point serves a fresh ball at the base speed
+        conn.execute(
+            "UPDATE state SET bx = 1, ball_x = 78, ball_y = 20, vx = 2, vy = 0, hits = 7",
+            [],
+        )?;
+        tick(&conn, PlayerInput::Up, Difficulty::Normal)?;
+        let (vx, hits, score_a): (i32, i32, i32) =
+            conn.query_row("SELECT vx, hits, score_a FROM state", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+        assert_eq!((vx, hits, score_a), (-1, 0, 1));
+        Ok(())
+    }
+
+    /// Set up a classic field, overwrite `state` columns with `assignments` and run
+    /// one tick with Player B holding `input`.
+    fn tick_from(assignments: &str, input: PlayerInput) -> Result<State> {
+        let settings = sql::Settings {
+            balls: 2,
+            ..sql::Settings::default()
+        };
+        tick_with(&settings, assignments, input)
+    }
+
+    fn tick_with(settings: &sql::Settings, assignments: &str, input: PlayerInput) -> Result<State> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(settings, false), [])?;
+        conn.execute(&format!("UPDATE state SET {assignments}"), [])?;
+        tick(&conn, input, Difficulty::Normal)?;
+        read_state(&conn)
+    }
+
+    /// Player B's paddle pinned to rows 1..=7 by holding Up, with the ball one column
+    /// in front of it. The extra ball is parked mid-field out of the way.
+    fn ball_towards_paddle_b(ball_y: i32) -> Result<State> {
+        tick_from(
+            &format!(
+                "bx = 1, ball_x = 77, ball_y = {ball_y}, vx = 1, vy = 0, hits = 0,
+                 extra_balls = [{{'id': 1, 'ball_x': 40, 'ball_y': 12, 'vx': -1, 'vy': 0, 'hits': 0}}]"
+            ),
+            PlayerInput::Up,
+        )
+    }
+
+    #[test]
+    fn hit_on_top_of_paddle_bounces_steeply_up() -> Result<()> {
+        let state = ball_towards_paddle_b(1)?;
+        assert_eq!((state.ball_x, state.vx, state.vy), (78, -1, -2));
+        Ok(())
+    }
+
+    #[test]
+    fn hit_on_center_of_paddle_bounces_straight() -> Result<()> {
+        let state = ball_towards_paddle_b(4)?;
+        assert_eq!((state.ball_x, state.vx, state.vy), (78, -1, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn paddle_zones_spread_the_angles_over_the_paddle() -> Result<()> {
+        // The angle off every row of Player B's paddle, held on rows 1 and down
+        let angles = |zones, paddle_h| -> Result<Vec<i32>> {
+            let settings = sql::Settings {
+                zones,
+                paddle_h,
+                ..sql::Settings::default()
+            };
+            (1..=paddle_h as i32)
+                .map(|ball_y| {
+                    let state = tick_with(
+                        &settings,
+                        &format!(
+                            "bx = 1, ball_x = 77, ball_y = {ball_y}, vx = 1, vy = 0, extra_balls = NULL"
+                        ),
+                        PlayerInput::Hold,
+                    )?;
+                    assert_eq!(state.vx, -1, "row {ball_y}");
+                    Ok(state.vy)
+                })
+                .collect()
+        };
+        // The default 5 zones on a 7-row paddle, as they always were
+        assert_eq!(angles(5, 7)?, [-2, -1, -1, 0, 0, 1, 2]);
+        // Only the edges angle the ball with 3, and 9 give every row its own angle
+        assert_eq!(angles(3, 7)?, [-1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(angles(9, 9)?, [-4, -3, -2, -1, 0, 1, 2, 3, 4]);
+        // A taller paddle spreads the same zones over more rows
+        assert_eq!(angles(5, 11)?, [-2, -1, -1, -1, 0, 0, 0, 1, 1, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn ball_past_left_edge_scores_for_player_b() -> Result<()> {
+        // Player A's AI always reaches the ball it watches once it is close, so the
+        // point comes from the extra ball it isn't watching
+        let state = tick_from(
+            "score_b = 0, ball_x = 2, ball_y = 3, vx = -1, vy = 0,
+             extra_balls = [{'id': 1, 'ball_x': 1, 'ball_y': 20, 'vx': -1, 'vy': 0, 'hits': 0}]",
+            PlayerInput::None,
+        )?;
+        assert_eq!(state.score_b, 1);
+        assert_eq!(state.vx, 1, "the watched ball should have been returned");
+        Ok(())
+    }
+
+    #[test]
+    fn thick_paddle_stops_the_ball_at_its_front_column() -> Result<()> {
+        let settings = sql::Settings {
+            paddle_w: 3,
+            ..sql::Settings::default()
+        };
+        // Player B's paddle covers columns 76..=78; this ball would end up on 77
+        let state = tick_with(
+            &settings,
+            "bx = 1, ball_x = 75, ball_y = 4, vx = 2, vy = 0",
+            PlayerInput::Up,
+        )?;
+        assert_eq!((state.ball_x, state.vx, state.score_a), (76, -1, 0));
+
+        // A ball that got in beside the paddle only scores once it leaves the back
+        let state = tick_with(
+            &settings,
+            "bx = 1, ball_x = 77, ball_y = 20, vx = 1, vy = 0",
+            PlayerInput::Up,
+        )?;
+        assert_eq!((state.ball_x, state.score_a), (78, 0));
+        let state = tick_with(
+            &settings,
+            "bx = 1, ball_x = 78, ball_y = 20, vx = 1, vy = 0",
+            PlayerInput::Up,
+        )?;
+        assert_eq!(state.score_a, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn ball_already_on_the_paddle_columns_goes_on_to_score() -> Result<()> {
+        // Player A's AI moves onto a close ball, but this one already got past the
+        // front of the paddle
+        let state = tick_from(
+            "score_b = 0, ball_x = 1, ball_y = 3, vx = -1, vy = 0, extra_balls = NULL",
+            PlayerInput::None,
+        )?;
+        assert_eq!(state.score_b, 1);
+
+        // Player B's thick paddle holds still on rows 10..=16, level with the ball
+        let settings = sql::Settings {
+            paddle_w: 3,
+            ..sql::Settings::default()
+        };
+        for (ball_x, vx) in [(77, 1), (76, 2), (76, 1)] {
+            let state = tick_with(
+                &settings,
+                &format!("bx = 10, ball_x = {ball_x}, ball_y = 12, vx = {vx}, vy = 0"),
+                PlayerInput::Hold,
+            )?;
+            assert!(state.vx > 0, "ball from {ball_x} was sent back");
+            assert_eq!(state.ball_x, ball_x + vx);
+        }
+        let state = tick_with(
+            &settings,
+            "bx = 10, ball_x = 78, ball_y = 12, vx = 1, vy = 0",
+            PlayerInput::Hold,
+        )?;
+        assert_eq!(state.score_a, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn thick_paddles_are_drawn_across_their_columns() -> Result<()> {
+        let config = GameConfig {
+            paddle_width: 3,
+            ..GameConfig::default()
+        };
+        let game = Game::new(config)?;
+        // Both paddles start centered on rows 9..=15
+        let row: Vec<char> = game.render_lines()?[12].chars().collect();
+        assert!(row[1..=3].iter().chain(&row[76..=78]).all(|&c| c != ' '));
+        assert_eq!((row[4], row[75]), (' ', ' '));
+        Ok(())
+    }
+
+    #[test]
+    fn caught_powerups_resize_paddles_until_they_wear_off() -> Result<()> {
+        let game = Game::new(GameConfig {
+            powerups: true,
+            ..GameConfig::default()
+        })?;
+        let conn = game.connection();
+        let no_spawn = PowerUpRolls {
+            spawn: 0.99,
+            x: 0.5,
+            y: 0.5,
+            kind: 0.5,
+        };
+        let heights = || -> Result<(i32, i32, i32, i32)> {
+            let row = conn.query_row(
+                "SELECT pa_h, pb_h, pa_h_timer, pb_h_timer FROM state",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+            Ok(row)
+        };
+        let pickups = || -> Result<i64> {
+            Ok(conn.query_row("SELECT count(*) FROM powerups", [], |row| row.get(0))?)
+        };
+
+        // A ball that was only served passes over the pickup
+        conn.execute_batch(
+            "INSERT INTO powerups VALUES (40, 12, 'grow', 100);
+             UPDATE state SET ball_x = 41, ball_y = 12, vx = 1, vy = 0, hits = 0;",
+        )?;
+        update_powerups(conn, no_spawn)?;
+        assert_eq!((heights()?, pickups()?), ((7, 7, 0, 0), 1));
+
+        // Hit by A, it grows A's paddle
+        conn.execute("UPDATE state SET hits = 1", [])?;
+        update_powerups(conn, no_spawn)?;
+        assert_eq!((heights()?, pickups()?), ((9, 7, 480, 0), 0));
+
+        // Hit by B, a shrink pickup shrinks A's paddle instead, with a fresh timer
+        conn.execute_batch(
+            "INSERT INTO powerups VALUES (40, 12, 'shrink', 100);
+             UPDATE state SET ball_x = 39, vx = -1;",
+        )?;
+        update_powerups(conn, no_spawn)?;
+        assert_eq!(heights()?, (5, 7, 480, 0));
+        let row: Vec<char> = game.render_lines()?[12].chars().collect();
+        assert_eq!(row[40], ' ');
+
+        conn.execute("UPDATE state SET pa_h_timer = 1", [])?;
+        update_powerups(conn, no_spawn)?;
+        assert_eq!(heights()?, (7, 7, 0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn powerups_spawn_only_when_enabled_and_none_is_out() -> Result<()> {
+        let spawn = PowerUpRolls {
+            spawn: 0.0,
+            x: 0.0,
+            y: 0.5,
+            kind: 0.2,
+        };
+        let game = Game::new(GameConfig::default())?;
+        update_powerups(game.connection(), spawn)?;
+        assert_eq!(game.render_lines()?[12].chars().nth(20), Some(' '));
+
+        let game = Game::new(GameConfig {
+            powerups: true,
+            ..GameConfig::default()
+        })?;
+        update_powerups(game.connection(), spawn)?;
+        update_powerups(game.connection(), PowerUpRolls { x: 0.9, ..spawn })?;
+        let field = game.render_lines()?;
+        // Columns start at a quarter of the width, rows cover 1..=23
+        assert_eq!(field[12].chars().nth(20), Some('+'));
+        assert_eq!(field.concat().matches('+').count(), 1);
+        Ok(())
+    }
+
+    /// Set up a classic one-ball field, overwrite `state` columns with `assignments`
+    /// and run one tick with the given rolls.
+    fn tick_rolled_from(assignments: &str, remote_a: Option<i32>, rolls: Rolls) -> Result<State> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        conn.execute(&format!("UPDATE state SET {assignments}"), [])?;
+        tick_rolled(
+            &conn,
+            remote_a,
+            PlayerInput::None,
+            Difficulty::Normal,
+            rolls,
+            0,
+        )?;
+        read_state(&conn)
+    }
+
+    const NEUTRAL_ROLLS: Rolls = Rolls {
+        ai_a: 0.5,
+        ai_b: 0.5,
+        serve_row: 0.5,
+        serve_angle: 0.5,
+        serve_side: 0.5,
+    };
+
+    #[test]
+    fn ramp_sharpens_the_ai_as_the_match_goes_on() -> Result<()> {
+        // Player A's paddle is on rows 10..=16 and watches a far-away ball on row 11,
+        // within the easy AI's dead zone of rows 11..=15
+        let ax_after = |ramp: bool, tick: i32, roll: f64| -> Result<i32> {
+            let conn = Connection::open_in_memory()?;
+            let settings = sql::Settings {
+                ramp,
+                ..sql::Settings::default()
+            };
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            conn.execute(
+                &format!(
+                    "UPDATE state SET tick = {tick}, ai_tick_a = {tick}, ax = 10,
+                     ball_x = 40, ball_y = 11, vx = -1, vy = 0"
+                ),
+                [],
+            )?;
+            let rolls = Rolls {
+                ai_a: roll,
+                ..NEUTRAL_ROLLS
+            };
+            tick_rolled(&conn, None, PlayerInput::None, Difficulty::Easy, rolls, 0)?;
+            Ok(read_state(&conn)?.ax)
+        };
+        // One step in, the dead zone has shrunk to rows 12..=14
+        assert_eq!(ax_after(false, 1200, 0.0)?, 10);
+        assert_eq!(ax_after(true, 0, 0.0)?, 10);
+        assert_eq!(ax_after(true, 1200, 0.0)?, 8);
+        // Five steps in, the AI tracks 75% of the time instead of 60%
+        assert_eq!(ax_after(true, 1200, 0.7)?, 10);
+        assert_eq!(ax_after(true, 6000, 0.7)?, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn ai_roll_picks_the_trick_shot_zone() -> Result<()> {
+        for (roll, vy) in [(0.1, -2), (0.3, -1), (0.52, 0), (0.6, 1), (0.9, 2)] {
+            let rolls = Rolls {
+                ai_a: roll,
+                ..NEUTRAL_ROLLS
+            };
+            let state = tick_rolled_from("ball_x = 2, ball_y = 12, vx = -1, vy = 0", None, rolls)?;
+            assert_eq!((state.vx, state.vy), (1, vy), "roll {roll}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn trick_shots_aim_for_the_zones_of_the_paddle_in_play() -> Result<()> {
+        let trick_vy = |zones, paddle_h, roll| -> Result<i32> {
+            let conn = Connection::open_in_memory()?;
+            // Tall enough for the tallest paddle to reach either edge onto the ball
+            let settings = sql::Settings {
+                height: 40,
+                zones,
+                paddle_h,
+                ..sql::Settings::default()
+            };
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            conn.execute(
+                "UPDATE state SET ball_x = 2, ball_y = 20, vx = -1, vy = 0",
+                [],
+            )?;
+            let rolls = Rolls {
+                ai_a: roll,
+                ..NEUTRAL_ROLLS
+            };
+            tick_rolled(&conn, None, PlayerInput::None, Difficulty::Normal, rolls, 0)?;
+            let state = read_state(&conn)?;
+            assert_eq!(state.vx, 1, "{zones} zones, paddle {paddle_h}, roll {roll}");
+            Ok(state.vy)
+        };
+        let rolls = [0.1, 0.3, 0.45, 0.52, 0.6, 0.7, 0.9];
+        let angles = |zones, paddle_h| -> Result<Vec<i32>> {
+            rolls
+                .iter()
+                .map(|&roll| trick_vy(zones, paddle_h, roll))
+                .collect()
+        };
+        // The diagonals spread over the zones between the edges and the center
+        assert_eq!(angles(9, 9)?, [-4, -3, -1, 0, 3, 1, 4]);
+        // With 3 zones there are no diagonals between, so those rolls take the edges
+        assert_eq!(angles(3, 7)?, [-1, -1, -1, 0, 1, 1, 1]);
+        // A taller paddle, as with --hires or a power-up, still gets the angles picked
+        assert_eq!(angles(5, 11)?, [-2, -1, -1, 0, 1, 1, 2]);
+        assert_eq!(angles(5, 14)?, [-2, -1, -1, 0, 1, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic_ai_takes_the_zones_in_turn_and_always_tracks() -> Result<()> {
+        let settings = sql::Settings {
+            deterministic_ai: true,
+            ..sql::Settings::default()
+        };
+        let tick_from = |assignments: &str| -> Result<State> {
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            conn.execute(&format!("UPDATE state SET {assignments}"), [])?;
+            // Rolls that would pick the bottom edge and skip tracking
+            let rolls = Rolls {
+                ai_a: 0.99,
+                ..NEUTRAL_ROLLS
+            };
+            tick_rolled(&conn, None, PlayerInput::None, Difficulty::Normal, rolls, 0)?;
+            read_state(&conn)
+        };
+        for (tick, vy) in [(0, -2), (1, -1), (2, 0), (3, 1), (4, 2), (5, -2)] {
+            let state = tick_from(&format!(
+                "tick = {tick}, ball_x = 2, ball_y = 12, vx = -1, vy = 0"
+            ))?;
+            assert_eq!((state.vx, state.vy), (1, vy), "tick {tick}");
+        }
+        let far_ball = "ax = 1, ball_x = 40, ball_y = 20, vx = -1, vy = 0";
+        assert_eq!(tick_from(far_ball)?.ax, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn score_gap_moves_trick_shots_to_the_edges_for_the_side_behind() -> Result<()> {
+        // Behind by 3 or more the top edge takes rolls up to 0.40, ahead only up to 0.10
+        for (roll, score_gap, vy) in [
+            (0.3, 0, -1),
+            (0.3, 3, -2),
+            (0.39, 10, -2),
+            (0.12, 0, -2),
+            (0.12, -3, -1),
+        ] {
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+            conn.execute(
+                "UPDATE state SET ball_x = 2, ball_y = 12, vx = -1, vy = 0",
+                [],
+            )?;
+            let rolls = Rolls {
+                ai_a: roll,
+                ..NEUTRAL_ROLLS
+            };
+            tick_rolled(
+                &conn,
+                None,
+                PlayerInput::None,
+                Difficulty::Normal,
+                rolls,
+                score_gap,
+            )?;
+            let state = read_state(&conn)?;
+            assert_eq!(state.vy, vy, "roll {roll}, gap {score_gap}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ai_roll_above_accuracy_leaves_a_far_ball_alone() -> Result<()> {
+        // The ball is far below Player A's paddle at rows 1..=7
+        let far_ball = "ax = 1, ball_x = 40, ball_y = 20, vx = -1, vy = 0";
+        let tracking = Rolls {
+            ai_a: Difficulty::Normal.accuracy() - 0.01,
+            ..NEUTRAL_ROLLS
+        };
+        assert_eq!(tick_rolled_from(far_ball, None, tracking)?.ax, 3);
+        let distracted = Rolls {
+            ai_a: Difficulty::Normal.accuracy() + 0.01,
+            ..NEUTRAL_ROLLS
+        };
+        assert_eq!(tick_rolled_from(far_ball, None, distracted)?.ax, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn slow_ai_sticks_to_its_last_decision_until_it_reacts_again() -> Result<()> {
+        let settings = sql::Settings {
+            ai_reaction_a: 10,
+            ..sql::Settings::default()
+        };
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        let ai = |conn: &Connection| -> Result<(i32, i32, i32)> {
+            tick_rolled(
+                conn,
+                None,
+                PlayerInput::None,
+                Difficulty::Normal,
+                NEUTRAL_ROLLS,
+                0,
+            )?;
+            let row =
+                conn.query_row("SELECT ax, ai_target_a, ai_tick_a FROM state", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?;
+            Ok(row)
+        };
+
+        // Five ticks after deciding to go to the top, the ball far below is ignored
+        conn.execute(
+            "UPDATE state SET tick = 5, ax = 9, ai_target_a = 1, ai_tick_a = 0,
+                              ball_x = 40, ball_y = 20, vx = -1, vy = 0",
+            [],
+        )?;
+        assert_eq!(ai(&conn)?, (7, 1, 0));
+
+        // Ten ticks after it, the AI looks again and heads for the ball
+        conn.execute("UPDATE state SET tick = 10, ball_x = 40", [])?;
+        assert_eq!(ai(&conn)?, (9, 17, 10));
+        Ok(())
+    }
+
+    #[test]
+    fn serve_rule_picks_who_each_point_is_served_to() -> Result<()> {
+        // Both paddles stay at the top, so a ball along row 20 gets past either
+        let to_b = "ax = 1, bx = 1, ball_x = 1, ball_y = 20, vx = -1, vy = 0, serve_timer = 0";
+        let to_a = "ax = 1, bx = 1, ball_x = 78, ball_y = 20, vx = 1, vy = 0, serve_timer = 0";
+        let serves = |serve: ServeRule, side_rolls: &[f64]| -> Result<Vec<(i32, i32)>> {
+            let settings = sql::Settings {
+                serve,
+                ..sql::Settings::default()
+            };
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            conn.execute("UPDATE state SET serve_dir = 1", [])?;
+            let mut serves = Vec::new();
+            for (point, &serve_side) in [to_b, to_a, to_a, to_b].iter().zip(side_rolls) {
+                conn.execute(&format!("UPDATE state SET {point}"), [])?;
+                let rolls = Rolls {
+                    serve_side,
+                    ..NEUTRAL_ROLLS
+                };
+                tick_rolled(
+                    &conn,
+                    Some(0),
+                    PlayerInput::Up,
+                    Difficulty::Normal,
+                    rolls,
+                    0,
+                )?;
+                let state = read_state(&conn)?;
+                serves.push((state.ball_x, state.vx));
+            }
+            Ok(serves)
+        };
+
+        // Points go to B, A, A, B; a serve toward A starts right of the center
+        let rolls = [0.5; 4];
+        let (toward_a, toward_b) = ((41, -1), (39, 1));
+        assert_eq!(
+            serves(ServeRule::Winner, &rolls)?,
+            [toward_b, toward_a, toward_a, toward_b]
+        );
+        assert_eq!(
+            serves(ServeRule::Loser, &rolls)?,
+            [toward_a, toward_b, toward_b, toward_a]
+        );
+        assert_eq!(
+            serves(ServeRule::Alternate, &rolls)?,
+            [toward_a, toward_b, toward_a, toward_b]
+        );
+        assert_eq!(
+            serves(ServeRule::Random, &[0.2, 0.2, 0.7, 0.9])?,
+            [toward_a, toward_a, toward_b, toward_b]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn predictive_ai_waits_where_the_ball_will_land() -> Result<()> {
+        // Up to the top wall at row 1 after 4 ticks, then down: it would get to row 36
+        // by column 1, which the bottom wall at row 23 reflects to row 10
+        let incoming = "ax = 9, ball_x = 40, ball_y = 5, vx = -1, vy = -1";
+        let paddle_after = |ai: AiStyle| -> Result<i32> {
+            let settings = sql::Settings {
+                ai,
+                ai_error: 0,
+                ..sql::Settings::default()
+            };
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            conn.execute(&format!("UPDATE state SET {incoming}"), [])?;
+            for _ in 0..20 {
+                tick_rolled(
+                    &conn,
+                    None,
+                    PlayerInput::None,
+                    Difficulty::Normal,
+                    NEUTRAL_ROLLS,
+                    0,
+                )?;
+            }
+            Ok(read_state(&conn)?.ax)
+        };
+
+        // Centered on row 10 long before the ball gets there
+        assert_eq!(paddle_after(AiStyle::Predictive)?, 7);
+        // The reactive AI follows the ball down to row 17 instead
+        let ax = paddle_after(AiStyle::Reactive)?;
+        assert!(!(ax..ax + 7).contains(&10), "reactive paddle at {ax}");
+        Ok(())
+    }
+
+    #[test]
+    fn training_wall_returns_every_ball_and_misses_cost_lives() -> Result<()> {
+        let settings = sql::Settings {
+            training: true,
+            ..sql::Settings::default()
+        };
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        let lives = || -> Result<i32> {
+            Ok(conn.query_row("SELECT lives FROM state", [], |row| row.get(0))?)
+        };
+        let tick = || {
+            tick_rolled(
+                &conn,
+                None,
+                PlayerInput::None,
+                Difficulty::Normal,
+                NEUTRAL_ROLLS,
+                0,
+            )
+        };
+
+        // Far from where Player A's paddle would be, the wall still sends it back
+        conn.execute(
+            "UPDATE state SET ax = 1, ball_x = 2, ball_y = 20, vx = -1, vy = 1",
+            [],
+        )?;
+        tick()?;
+        let state = read_state(&conn)?;
+        assert_eq!((state.ball_x, state.vx, state.vy), (1, 1, 1));
+        assert_eq!((state.score_a, state.score_b), (0, 0));
+
+        // Player B missing costs a life instead of giving A a point
+        conn.execute(
+            "UPDATE state SET bx = 15, ball_x = 78, ball_y = 5, vx = 1, vy = 0",
+            [],
+        )?;
+        tick()?;
+        assert_eq!(read_state(&conn)?.score_a, 0);
+        assert_eq!(lives()?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn rally_timeout_calls_a_let_or_speeds_the_ball_up() -> Result<()> {
+        for (action, ball_x, vx) in [(TimeoutAction::Let, 39, 1), (TimeoutAction::Speedup, 41, 2)] {
+            let settings = sql::Settings {
+                rally_timeout: 100,
+                on_rally_timeout: action,
+                ..sql::Settings::default()
+            };
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            conn.execute(
+                "UPDATE state SET serve_timer = 0, serve_dir = 1, rally_ticks = 100,
+                    ball_x = 40, ball_y = 12, vx = 1, vy = 0",
+                [],
+            )?;
+            tick_rolled(
+                &conn,
+                None,
+                PlayerInput::None,
+                Difficulty::Normal,
+                NEUTRAL_ROLLS,
+                0,
+            )?;
+            let state = read_state(&conn)?;
+            assert_eq!((state.ball_x, state.vx), (ball_x, vx), "{action:?}");
+            assert_eq!((state.score_a, state.score_b), (0, 0), "{action:?}");
+            let rally_ticks: i32 =
+                conn.query_row("SELECT rally_ticks FROM state", [], |row| row.get(0))?;
+            assert_eq!(rally_ticks, 0, "{action:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn waiting_serve_follows_the_players_paddle_until_launched() -> Result<()> {
+        let mut game = Game::new(GameConfig {
+            wait_for_serve: true,
+            ..GameConfig::default()
+        })?;
+        assert!(game.awaiting_serve()?);
+        // The player serves first, toward the AI, from in front of paddle B
+        for input in [PlayerInput::Up, PlayerInput::Up, PlayerInput::Hold] {
+            game.tick(Inputs {
+                b: input,
+                ..Inputs::default()
+            })?;
+            let state = game.state()?;
+            assert_eq!(state.ball_x, 77);
+            assert_eq!(state.ball_y, state.bx + 3);
+            assert!(state.vx < 0);
+        }
+        assert_eq!(game.state()?.bx, 5);
+
+        game.serve()?;
+        game.tick(Inputs::default())?;
+        assert!(!game.awaiting_serve()?);
+        assert_eq!(game.state()?.ball_x, 76);
+
+        // By the winner rule a point for A sends the next serve toward A, away from
+        // the player, so it is theirs and waits; one for B heads their way and doesn't
+        let point = |game: &mut Game, assignments: &str| -> Result<bool> {
+            game.serve()?;
+            let update = format!("UPDATE state SET {assignments}, vy = 0, serve_timer = 0");
+            game.connection().execute(&update, [])?;
+            game.tick(Inputs::default())?;
+            game.awaiting_serve()
+        };
+        assert!(point(
+            &mut game,
+            "ball_x = 78, ball_y = 12, vx = 1, bx = 1"
+        )?);
+        assert!(!point(
+            &mut game,
+            "ball_x = 1, ball_y = 5, vx = -1, ax = 15"
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn serve_rolls_pick_row_and_angle() -> Result<()> {
+        // A remote Player A holding still at rows 1..=7 misses the ball at row 20. The
+        // serve row counts from row 12, the middle of the 25 rows rounded down, 3 up
+        // for a roll of 0 and none for 0.5.
+        let missed = "ax = 1, ball_x = 1, ball_y = 20, vx = -1, vy = 0";
+        for (serve_row, serve_angle, ball_y, vy) in
+            [(0.5, 0.0, 12, -2), (0.0, 0.4, 9, 0), (0.5, 0.6, 12, 1)]
+        {
+            let rolls = Rolls {
+                serve_row,
+                serve_angle,
+                ..NEUTRAL_ROLLS
+            };
+            let state = tick_rolled_from(missed, Some(0), rolls)?;
+            assert_eq!(state.score_b, 1);
+            assert_eq!(
+                (state.ball_y, state.vy),
+                (ball_y, vy),
+                "rolls {serve_row}, {serve_angle}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn serve_angle_modes_weight_the_serve_angles() -> Result<()> {
+        const SERVES: usize = 1000;
+        let missed = "ax = 1, ball_x = 1, ball_y = 20, vx = -1, vy = 0, serve_timer = 0";
+        // The chances of vy -2 to +2 from the table on ServeAngle
+        for (serve_angle, weights) in [
+            (ServeAngle::Flat, [0.05, 0.20, 0.50, 0.20, 0.05]),
+            (ServeAngle::Random, [0.20; 5]),
+            (ServeAngle::Wide, [0.35, 0.10, 0.10, 0.10, 0.35]),
+        ] {
+            let settings = sql::Settings {
+                serve_angle,
+                ..sql::Settings::default()
+            };
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&sql::build_setup(&settings, false), [])?;
+            seed_rng(&conn, 0.57)?;
+            let mut counts = [0; 5];
+            for _ in 0..SERVES {
+                conn.execute(&format!("UPDATE state SET {missed}"), [])?;
+                let rolls = Rolls::draw(&conn)?;
+                tick_rolled(
+                    &conn,
+                    Some(0),
+                    PlayerInput::None,
+                    Difficulty::Normal,
+                    rolls,
+                    0,
+                )?;
+                let vy = read_state(&conn)?.vy;
+                assert!((-2..=2).contains(&vy), "{serve_angle:?} served vy {vy}");
+                counts[(vy + 2) as usize] += 1;
+            }
+            for (count, weight) in counts.iter().zip(weights) {
+                // Five standard deviations of a binomial count, so a fair run never fails
+                let share = f64::from(*count) / SERVES as f64;
+                let tolerance = 5.0 * (weight * (1.0 - weight) / SERVES as f64).sqrt();
+                assert!(
+                    (share - weight).abs() < tolerance,
+                    "{serve_angle:?} served vy counts {counts:?}, expected shares {weights:?}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn winning_a_set_resets_the_scores_until_the_match_is_decided() -> Result<()> {
+        let settings = sql::Settings {
+            target_score: 5,
+            sets: 3,
+            balls: 2,
+            ..sql::Settings::default()
+        };
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        assert_eq!(check_winner(&conn)?, None);
+
+        conn.execute(
+            "UPDATE state SET score_a = 5, score_b = 3, ax = 1, ball_x = 3",
+            [],
+        )?;
+        assert_eq!(check_winner(&conn)?, Some(Win::Set('A')));
+        assert_eq!(start_next_set(&conn)?, (1, 0));
+        let state = read_state(&conn)?;
+        assert_eq!((state.score_a, state.score_b), (0, 0));
+        assert_eq!((state.ax, state.ball_x), (9, 40));
+        let served: i32 = conn.query_row(
+            "SELECT count(*) FROM (SELECT unnest(extra_balls) AS b FROM state) WHERE b.ball_x = 40",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(served, 1, "the extra ball should be served again too");
+
+        conn.execute("UPDATE state SET score_b = 5", [])?;
+        assert_eq!(check_winner(&conn)?, Some(Win::Set('B')));
+        assert_eq!(start_next_set(&conn)?, (1, 1));
+
+        // The third set decides the best of three
+        conn.execute("UPDATE state SET score_b = 5", [])?;
+        assert_eq!(check_winner(&conn)?, Some(Win::Match('B')));
+        Ok(())
+    }
+
+    #[test]
+    fn new_sets_serve_within_two_steps_of_flat() -> Result<()> {
+        let settings = sql::Settings {
+            sets: 99,
+            balls: 9,
+            ..sql::Settings::default()
+        };
+        let conn = Connection::open_in_memory()?;
+        seed_rng(&conn, 0.3)?;
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        // A few hundred serves, where rounding the roll would make about one in ten a 3
+        for set in 0..40 {
+            conn.execute("UPDATE state SET score_a = 11", [])?;
+            start_next_set(&conn)?;
+            let steepest: i32 = conn.query_row(
+                "SELECT greatest(abs(vy), list_max(list_transform(extra_balls, lambda b: abs(b.vy))))
+                FROM state",
+                [],
+                |row| row.get(0),
+            )?;
+            assert!(steepest <= 2, "set {set} served vy {steepest}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn set_at_deuce_needs_a_two_point_lead() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        let at = |score_a: i32, score_b: i32| -> Result<Option<Win>> {
+            conn.execute(
+                "UPDATE state SET score_a = ?, score_b = ?",
+                params![score_a, score_b],
+            )?;
+            assert_eq!(
+                Deuce::from_scores(score_a, score_b, 11).is_some(),
+                score_a.min(score_b) >= 10 && (score_a - score_b).abs() < 2
+            );
+            check_winner(&conn)
+        };
+
+        // 10-10 → 12-10: reaching 11 isn't enough, pulling two ahead is
+        assert_eq!(at(10, 10)?, None);
+        assert_eq!(at(11, 10)?, None);
+        assert_eq!(at(12, 10)?, Some(Win::Match('A')));
+
+        // Advantage B, then back to deuce, then B wins two points clear
+        assert_eq!(at(10, 11)?, None);
+        assert_eq!(at(11, 11)?, None);
+        assert_eq!(at(11, 12)?, None);
+        assert_eq!(at(11, 13)?, Some(Win::Match('B')));
+        assert_eq!(
+            start_next_set(&conn)?,
+            (0, 1),
+            "only the winner gets the set"
+        );
+
+        // Without a deuce the target still wins outright
+        assert_eq!(at(11, 9)?, Some(Win::Match('A')));
+        Ok(())
+    }
+
+    #[test]
+    fn deuce_indicator_follows_the_lead() {
+        let sequence = [(10, 10), (11, 10), (11, 11), (11, 12), (12, 12)];
+        let shown: Vec<_> = sequence
+            .iter()
+            .map(|&(a, b)| Deuce::from_scores(a, b, 11).map(|d| d.to_string()))
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                Some("DEUCE".to_string()),
+                Some("ADVANTAGE A".to_string()),
+                Some("DEUCE".to_string()),
+                Some("ADVANTAGE B".to_string()),
+                Some("DEUCE".to_string()),
+            ]
+        );
+        assert_eq!(Deuce::from_scores(9, 10, 11), None);
+        assert_eq!(Deuce::from_scores(12, 10, 11), None);
+    }
+
+    #[test]
+    fn serve_countdown_holds_the_ball_after_a_point() -> Result<()> {
+        let settings = sql::Settings {
+            serve_ticks: 3,
+            ..sql::Settings::default()
+        };
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        // A remote Player A holding still at rows 1..=7 misses the ball at row 20
+        conn.execute(
+            "UPDATE state SET ax = 1, ball_x = 1, ball_y = 20, vx = -1, vy = 0",
+            [],
+        )?;
+        let hold_a = || tick_versus(&conn, Some(0), PlayerInput::None, Difficulty::Normal);
+        let countdown = || -> Result<char> {
+            let field = render::query_field(&conn, false, 80, 25)?;
+            Ok(field[12][40].glyph)
+        };
+
+        hold_a()?;
+        let served = read_state(&conn)?;
+        assert_eq!((served.score_b, served.ball_x), (1, 39));
+        assert_eq!(countdown()?, '3');
+        for digit in ['2', '1', ' '] {
+            hold_a()?;
+            let state = read_state(&conn)?;
+            assert_eq!((state.ball_x, state.ball_y), (served.ball_x, served.ball_y));
+            assert_eq!(countdown()?, digit);
+        }
+
+        hold_a()?;
+        assert_eq!(read_state(&conn)?.ball_x, served.ball_x + served.vx);
+        Ok(())
+    }
+
+    #[test]
+    fn failing_queries_say_which_one_failed() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        conn.execute_batch("DROP TABLE state")?;
+        let err = tick(&conn, PlayerInput::None, Difficulty::Normal).unwrap_err();
+        assert!(format!("{err:#}").starts_with("running the tick query (TICK): "));
+        let err = render::render_sparse(&conn).unwrap_err();
+        assert!(format!("{err:#}").starts_with("running the render query (RENDER_SPARSE): "));
+        Ok(())
+    }
+
+    #[test]
+    fn wall_bounce_flips_vy() -> Result<()> {
+        // Landing right on the top row bounces without any overshoot to reflect
+        let state = tick_from(
+            "ball_x = 40, ball_y = 3, vx = 1, vy = -2",
+            PlayerInput::None,
+        )?;
+        assert_eq!((state.ball_x, state.ball_y, state.vy), (41, 1, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn wrapped_field_sends_the_ball_around_at_the_same_speed() -> Result<()> {
+        let settings = sql::Settings {
+            wrap: true,
+            ..sql::Settings::default()
+        };
+        // Rows 1..=23 are playable: two rows down from 22 is row 1, two up from 1 is 22
+        for (ball_y, vy, wrapped) in [(22, 2, 1), (1, -2, 22), (3, -2, 1)] {
+            let state = tick_with(
+                &settings,
+                &format!("ball_x = 40, ball_y = {ball_y}, vx = 1, vy = {vy}, extra_balls = NULL"),
+                PlayerInput::None,
+            )?;
+            assert_eq!((state.ball_y, state.vy), (wrapped, vy), "from row {ball_y}");
+        }
+
+        // Player B's paddle holds still on rows 17..=23, and the ball leaves past the
+        // top on its way into the paddle column, coming back in level with the paddle
+        let state = tick_with(
+            &settings,
+            "bx = 17, ball_x = 77, ball_y = 1, vx = 1, vy = -2, extra_balls = NULL",
+            PlayerInput::Hold,
+        )?;
+        assert_eq!((state.ball_x, state.ball_y, state.score_a), (78, 22, 0));
+        assert!(state.vx < 0);
+
+        // Fast balls never end up on a border row, and are drawn where they wrapped to
+        let game = Game::new(GameConfig {
+            wrap: true,
+            ascii: true,
+            ball_speed: 3,
+            ..GameConfig::default()
+        })?;
+        for _ in 0..300 {
+            game.connection()
+                .execute("UPDATE state SET extra_balls = NULL", [])?;
+            tick(game.connection(), PlayerInput::None, Difficulty::Normal)?;
+            let state = game.state()?;
+            assert!((1..=23).contains(&state.ball_y), "{state:?}");
+            // Paddles and the serve countdown in the center may cover the ball
+            if !(2..=77).contains(&state.ball_x) || state.ball_x == 40 {
+                continue;
+            }
+            let lines = game.render_lines()?;
+            let row: Vec<char> = lines[state.ball_y as usize].chars().collect();
+            assert_eq!(row[state.ball_x as usize], 'O', "{state:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn serves_land_between_the_paddles_on_narrow_fields() -> Result<()> {
+        // On 20 columns with the thickest paddles columns 6..=13 are in front of both;
+        // on 14 only 6..=7 are, fewer than the serve's usual step off the center
+        for (width, big_ball, columns) in
+            [(20, false, 6..=13), (14, false, 6..=7), (14, true, 6..=6)]
+        {
+            let settings = sql::Settings {
+                width,
+                paddle_w: 5,
+                big_ball,
+                ..sql::Settings::default()
+            };
+            // Past the left edge B scores and serves toward itself, past the right one
+            // A does
+            for (ball_x, vx) in [(0, -1), (width as i32 - 1, 1)] {
+                let state = tick_with(
+                    &settings,
+                    &format!(
+                        "ball_x = {ball_x}, ball_y = 12, vx = {vx}, vy = 0, extra_balls = NULL"
+                    ),
+                    PlayerInput::Hold,
+                )?;
+                assert_eq!(state.score_a + state.score_b, 1);
+                assert!(
+                    columns.contains(&state.ball_x),
+                    "{width} columns, big ball {big_ball}: served from {ball_x} to {}",
+                    state.ball_x
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn big_ball_collides_and_scores_as_a_block() -> Result<()> {
+        let settings = sql::Settings {
+            big_ball: true,
+            ..sql::Settings::default()
+        };
+        // Its lower row stays on row 23, so its top row bounces off row 22
+        let state = tick_with(
+            &settings,
+            "ball_x = 40, ball_y = 21, vx = 1, vy = 2, extra_balls = NULL",
+            PlayerInput::None,
+        )?;
+        assert_eq!((state.ball_y, state.vy), (21, -2));
+
+        // Player B's paddle on rows 10..=16 only meets the lower row of a ball on rows
+        // 9..=10, with its right column, and stops it there
+        let state = tick_with(
+            &settings,
+            "bx = 10, ball_x = 76, ball_y = 9, vx = 1, vy = 0, extra_balls = NULL",
+            PlayerInput::Hold,
+        )?;
+        assert_eq!((state.ball_x, state.ball_y, state.vy), (77, 9, -2));
+        assert!(state.vx < 0);
+
+        // Past the left paddle, its right column still on column 1 doesn't score yet
+        let state = tick_with(
+            &settings,
+            "ball_x = 1, ball_y = 20, vx = -1, vy = 0, extra_balls = NULL",
+            PlayerInput::Hold,
+        )?;
+        assert_eq!((state.ball_x, state.score_b), (0, 0));
+        let state = tick_with(
+            &settings,
+            "ball_x = 0, ball_y = 20, vx = -1, vy = 0, extra_balls = NULL",
+            PlayerInput::Hold,
+        )?;
+        assert_eq!(state.score_b, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn big_ball_is_drawn_as_four_cells_and_fits_the_smallest_field() -> Result<()> {
+        let game = Game::new(GameConfig {
+            big_ball: true,
+            ascii: true,
+            ..GameConfig::default()
+        })?;
+        game.connection().execute(
+            "UPDATE state SET ball_x = 30, ball_y = 5, extra_balls = NULL",
+            [],
+        )?;
+        let lines = game.render_lines()?;
+        for row in [5, 6] {
+            assert_eq!(&lines[row][29..33], " OO ", "row {row}");
+        }
+        assert!(!lines[4].contains('O') && !lines[7].contains('O'));
+
+        // On a 20x10 field its top row keeps to rows 1..=7, and it never covers a border
+        let game = Game::new(GameConfig {
+            big_ball: true,
+            ascii: true,
+            width: config::MIN_WIDTH,
+            height: config::MIN_HEIGHT,
+            ball_speed: 2,
+            ..GameConfig::default()
+        })?;
+        for _ in 0..500 {
+            tick(game.connection(), PlayerInput::None, Difficulty::Normal)?;
+            let state = game.state()?;
+            assert!((1..=7).contains(&state.ball_y), "{state:?}");
+            let lines = game.render_lines()?;
+            assert!(
+                !lines[0].contains('O') && !lines[9].contains('O'),
+                "{state:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn aspect_correction_moves_balls_vertically_every_other_tick() -> Result<()> {
+        let settings = sql::Settings {
+            aspect_correct: true,
+            ..sql::Settings::default()
+        };
+        for (tick, ball_y) in [(0, 12), (1, 10), (2, 12)] {
+            let state = tick_with(
+                &settings,
+                &format!(
+                    "tick = {tick}, ball_x = 40, ball_y = 10, vx = 1, vy = 2, extra_balls = NULL"
+                ),
+                PlayerInput::None,
+            )?;
+            assert_eq!(
+                (state.ball_x, state.ball_y, state.vy),
+                (41, ball_y, 2),
+                "tick {tick}"
+            );
+        }
+
+        // A ball that bounced off the top row waits there for a tick, heading away
+        let state = tick_with(
+            &settings,
+            "tick = 1, ball_x = 40, ball_y = 1, vx = 1, vy = 2, extra_balls = NULL",
+            PlayerInput::None,
+        )?;
+        assert_eq!((state.ball_y, state.vy), (1, 2));
+
+        // Hi-res pixels are already about square
+        let config = GameConfig {
+            aspect_correct: true,
+            ..GameConfig::default()
+        };
+        assert!(config.settings().aspect_correct);
+        let hires = GameConfig {
+            hires: true,
+            ..config
+        };
+        assert!(!hires.settings().aspect_correct);
+        Ok(())
+    }
+
+    #[test]
+    fn wall_bounce_reflects_the_overshoot() -> Result<()> {
+        // One row past the top row (row 0) comes back one row inside it
+        let state = tick_from(
+            "ball_x = 40, ball_y = 2, vx = 1, vy = -2",
+            PlayerInput::None,
+        )?;
+        assert_eq!((state.ball_y, state.vy), (2, 2));
+
+        // Two rows past it, with a fast ball, comes back two rows inside
+        let settings = sql::Settings {
+            ball_speed: 2,
+            ..sql::Settings::default()
+        };
+        let state = tick_with(
+            &settings,
+            "ball_x = 40, ball_y = 3, vx = 2, vy = -4",
+            PlayerInput::None,
+        )?;
+        assert_eq!((state.ball_y, state.vy), (3, 4));
+
+        // The bottom wall (last playable row 23) mirrors it
+        let state = tick_from(
+            "ball_x = 40, ball_y = 22, vx = 1, vy = 2",
+            PlayerInput::None,
+        )?;
+        assert_eq!((state.ball_y, state.vy), (22, -2));
+        Ok(())
+    }
+
+    #[test]
+    fn ball_arriving_in_a_corner_bounces_off_the_paddle() -> Result<()> {
+        // Bottom corner: holding Down pins the paddle to rows 17..=23, and the ball
+        // would cross the paddle column below the last playable row
+        for (ball_x, ball_y, vx) in [(77, 22, 1), (77, 23, 1), (76, 22, 2)] {
+            let state = tick_from(
+                &format!(
+                    "bx = 17, ball_x = {ball_x}, ball_y = {ball_y}, vx = {vx}, vy = 2,
+                     extra_balls = NULL"
+                ),
+                PlayerInput::Down,
+            )?;
+            assert_eq!(
+                state.score_a, 0,
+                "ball from ({ball_x}, {ball_y}) got through"
+            );
+            assert_eq!((state.ball_x, state.ball_y), (78, 23));
+            assert!(state.vx < 0);
+        }
+
+        // Top corner, mirrored
+        let state = tick_from(
+            "bx = 1, ball_x = 77, ball_y = 2, vx = 1, vy = -2, extra_balls = NULL",
+            PlayerInput::Up,
+        )?;
+        assert_eq!(state.score_a, 0);
+        assert_eq!((state.ball_x, state.ball_y, state.vy), (78, 1, -2));
+        Ok(())
+    }
+
+    #[test]
+    fn steep_ball_clipping_a_paddle_end_bounces() -> Result<()> {
+        // Player B's paddle holds still on rows 10..=16. Each ball ends the tick a row
+        // past one of its ends, but crosses into the paddle column level with that end
+        for (ball_x, ball_y, vx, vy, hit_y) in [
+            (77, 11, 1, -2, 10),
+            (77, 15, 1, 2, 16),
+            (76, 11, 2, -2, 10),
+            (76, 15, 2, 2, 16),
+        ] {
+            let state = tick_from(
+                &format!(
+                    "bx = 10, ball_x = {ball_x}, ball_y = {ball_y}, vx = {vx}, vy = {vy},
+                     extra_balls = NULL"
+                ),
+                PlayerInput::Hold,
+            )?;
+            assert_eq!(
+                (state.ball_x, state.ball_y),
+                (78, hit_y),
+                "ball from ({ball_x}, {ball_y}) with vy = {vy}"
+            );
+            assert!(state.vx < 0, "ball from ({ball_x}, {ball_y}) got through");
+        }
+
+        // A ball that only passes the paddle's end still gets by
+        let state = tick_from(
+            "bx = 10, ball_x = 77, ball_y = 9, vx = 1, vy = -2, extra_balls = NULL",
+            PlayerInput::Hold,
+        )?;
+        assert!(state.vx > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn spin_bends_the_bounce_in_the_paddles_direction() -> Result<()> {
+        // Player B moves up from row 5 to 3 while the ball hits the paddle's center
+        let hit_center_moving_up = |spin| {
+            let settings = sql::Settings {
+                spin,
+                ..sql::Settings::default()
+            };
+            tick_with(
+                &settings,
+                "bx = 5, prev_bx = 5, ball_x = 77, ball_y = 6, vx = 1, vy = 0",
+                PlayerInput::Up,
+            )
+        };
+        assert_eq!(hit_center_moving_up(false)?.vy, 0);
+        assert_eq!(hit_center_moving_up(true)?.vy, -1);
+
+        // The top edge already gives the steepest angle, spin can't add to it
+        let settings = sql::Settings {
+            spin: true,
+            ..sql::Settings::default()
+        };
+        let state = tick_with(
+            &settings,
+            "bx = 5, prev_bx = 5, ball_x = 77, ball_y = 3, vx = 1, vy = 0",
+            PlayerInput::Up,
+        )?;
+        assert_eq!(state.vy, -2);
+        Ok(())
+    }
+
+    #[test]
+    fn momentum_paddle_speeds_up_coasts_and_stops_at_the_wall() -> Result<()> {
+        let settings = sql::Settings {
+            momentum: true,
+            ..sql::Settings::default()
+        };
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        conn.execute("UPDATE state SET ax = 15", [])?;
+        // Player A steered like a remote player, so no AI gets in the way
+        let push = |direction| {
+            tick_versus(
+                &conn,
+                Some(direction),
+                PlayerInput::None,
+                Difficulty::Normal,
+            )
+        };
+        let paddle_a = || -> Result<(i32, f64)> {
+            Ok(conn.query_row("SELECT ax, pa_vel FROM state", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?)
+        };
+
+        // One push only gets the paddle going
+        push(-1)?;
+        assert_eq!(paddle_a()?, (15, -0.25));
+        // Held long enough it reaches the paddle speed and no more
+        for _ in 0..9 {
+            push(-1)?;
+        }
+        let (ax, velocity) = paddle_a()?;
+        assert_eq!(velocity, -2.0);
+        assert!(ax < 10, "paddle only got to row {ax}");
+
+        // Let go and it coasts a little further before stopping
+        conn.execute("UPDATE state SET ax = 12", [])?;
+        push(0)?;
+        assert_eq!(paddle_a()?, (10, -1.75));
+
+        // Running into the top wall stops it dead
+        conn.execute("UPDATE state SET ax = 2, pa_vel = -2", [])?;
+        push(-1)?;
+        assert_eq!(paddle_a()?, (1, 0.0));
+        Ok(())
+    }
+
+    #[test]
+    fn full_render_returns_every_line_in_order() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+        conn.execute("UPDATE state SET ball_x = 30, ball_y = 20", [])?;
+
+        let render::Rendered::Full(screen) = render::query(
+            &conn,
+            RenderMode::Full,
+            false,
+            render::GlyphSet::Unicode,
+            80,
+            25,
+        )?
+        else {
+            panic!("expected a full screen");
+        };
+        let lines: Vec<Vec<char>> = screen.split("\r\n").map(|l| l.chars().collect()).collect();
+        assert_eq!(lines.len(), 25);
+        assert!(lines.iter().all(|line| line.len() == 80));
+        assert!(lines[0].iter().all(|&c| c == '▄'));
+        assert!(lines[24].iter().all(|&c| c == '▀'));
+        assert_eq!(lines[20][30], '█');
+        assert_eq!(lines[20][29], ' ');
+        Ok(())
+    }
+
+    #[test]
+    fn hires_render_packs_two_pixel_rows_into_each_line() -> Result<()> {
+        let config = GameConfig {
+            hires: true,
+            ..GameConfig::default()
+        };
+        let settings = config.settings();
+        assert_eq!((settings.height, settings.paddle_h), (50, 14));
+
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        // Paddle A covers pixels 3..=16, the ball sits in the lower half of line 20
+        conn.execute("UPDATE state SET ax = 3, ball_x = 30, ball_y = 41", [])?;
+        let field = render::query_field(&conn, true, config.width, config.height)?;
+        assert_eq!(field.len(), 25);
+        let glyph = |y: usize, x: usize| field[y][x].glyph;
+        assert_eq!((glyph(0, 0), glyph(24, 0)), ('▀', '▄'));
+        assert_eq!((glyph(1, 1), glyph(4, 1), glyph(8, 1)), ('▄', '█', '▀'));
+        assert_eq!(field[20][30].cell, render::Cell::Ball);
+        assert_eq!((glyph(20, 30), glyph(21, 30)), ('▄', ' '));
+        Ok(())
+    }
+
+    #[test]
+    fn border_styles_draw_the_edges_at_the_same_size() -> Result<()> {
+        use render::BorderStyle;
+
+        for (border, top, bottom) in [
+            (BorderStyle::Blocks, "▄▄▄▄", "▀▀▀▀"),
+            (BorderStyle::Box, "┌──┐", "└──┘"),
+            (BorderStyle::Ascii, "+--+", "+--+"),
+        ] {
+            for hires in [false, true] {
+                let config = GameConfig {
+                    width: 20,
+                    height: 10,
+                    border,
+                    hires,
+                    ..GameConfig::default()
+                };
+                let edges = |lines: &[String]| -> (String, String) {
+                    let ends = |line: &String| {
+                        let chars: Vec<char> = line.chars().collect();
+                        [chars[0], chars[1], chars[18], chars[19]].iter().collect()
+                    };
+                    (ends(&lines[0]), ends(&lines[9]))
+                };
+                let lines = Game::new(config.clone())?.render_lines()?;
+                assert_eq!(lines.len(), 10);
+                assert!(lines.iter().all(|line| line.chars().count() == 20));
+                let expected = if hires && border == BorderStyle::Blocks {
+                    // Pixel rows 0 and 19 are the upper half of the first line and the
+                    // lower half of the last one
+                    ("▀▀▀▀".to_string(), "▄▄▄▄".to_string())
+                } else {
+                    (top.to_string(), bottom.to_string())
+                };
+                assert_eq!(edges(&lines), expected, "{border:?}, hires {hires}");
+
+                // The whole grid query draws the same edges
+                let conn = Connection::open_in_memory()?;
+                conn.execute(&config.setup_sql(false), [])?;
+                let grid = render::query_grid(&conn, hires, 20, 10)?;
+                let grid: Vec<String> = grid
+                    .iter()
+                    .map(|line| line.iter().map(|tile| tile.glyph).collect())
+                    .collect();
+                assert_eq!(edges(&grid), expected, "{border:?}, hires {hires}, grid");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn center_line_can_be_left_out() -> Result<()> {
+        for center_line in [true, false] {
+            let config = GameConfig {
+                center_line,
+                ..GameConfig::default()
+            };
+            let lines = Game::new(config.clone())?.render_lines()?;
+            let center: String = lines
+                .iter()
+                .map(|line| line.chars().nth(40).unwrap())
+                .collect();
+            // The ball starts in the center column too, but covers one row only
+            assert_eq!(
+                center.matches('█').count() > 1,
+                center_line,
+                "center column {center:?}"
+            );
+
+            let conn = Connection::open_in_memory()?;
+            conn.execute(&config.setup_sql(false), [])?;
+            let grid = render::query_grid(&conn, false, 80, 25)?;
+            let drawn = grid
+                .iter()
+                .flatten()
+                .any(|tile| tile.cell == render::Cell::CenterLine);
+            assert_eq!(drawn, center_line);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn mouse_pulls_player_b_paddle_toward_the_pointer() -> Result<()> {
+        let config = GameConfig::default();
+        // Pointer on field row 20 centers the 7-row paddle there, but the paddle only
+        // moves by its speed per tick
+        let input = PlayerInput::from_mouse(40, FIELD_TOP + 20, &config);
+        assert_eq!(input, PlayerInput::Toward(17));
+        let state = tick_from("bx = 10", input)?;
+        assert_eq!(state.bx, 12);
+
+        // Within reach it lands exactly on the target
+        let state = tick_from("bx = 16", input)?;
+        assert_eq!(state.bx, 17);
+
+        // Outside the field the mouse is ignored
+        assert_eq!(
+            PlayerInput::from_mouse(90, FIELD_TOP + 20, &config),
+            PlayerInput::None
+        );
+        assert_eq!(PlayerInput::from_mouse(40, 0, &config), PlayerInput::None);
+        Ok(())
+    }
+
+    #[test]
+    fn game_ticks_and_renders_through_its_api() -> Result<()> {
+        let mut game = Game::new(GameConfig::default())?;
+        let start = game.state()?;
+        game.tick(Inputs {
+            b: PlayerInput::Up,
+            ..Inputs::default()
+        })?;
+        let state = game.state()?;
+        assert_eq!(state.tick, start.tick + 1);
+        assert_eq!(state.bx, start.bx - 2);
+
+        let lines = game.render_lines()?;
+        assert_eq!(lines.len(), 25);
+        assert!(lines.iter().all(|line| line.chars().count() == 80));
+        assert!(lines[0].chars().all(|c| c == '▄'));
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_render_lists_the_non_empty_cells_of_the_grid() -> Result<()> {
+        use render::{Cell, Tile};
+
+        let game = Game::new(GameConfig {
+            balls: 3,
+            paddle_width: 2,
+            powerups: true,
+            ..GameConfig::default()
+        })?;
+        let conn = game.connection();
+        // A ball on the center line and on a paddle, a pickup and the countdown
+        conn.execute_batch(
+            "INSERT INTO powerups VALUES (30, 5, 'shrink', 100);
+             UPDATE state SET ball_x = 40, ball_y = 4, ax = 3, serve_timer = 10,
+                 extra_balls = [{'id': 1, 'ball_x': 2, 'ball_y': 5, 'vx': -1, 'vy': 0, 'hits': 0}];",
+        )?;
+        let Rendered::Cells(field) = game.render(RenderMode::Grid)? else {
+            anyhow::bail!("grid render returned a full screen");
+        };
+        let expected: Vec<_> = field
+            .iter()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.iter()
+                    .enumerate()
+                    .filter(|(_, tile)| tile.cell != Cell::Empty)
+                    .map(move |(x, &tile)| (y as i32, x as i32, tile))
+            })
+            .collect();
+        let sparse = render::render_sparse(conn)?;
+        assert_eq!(sparse, expected);
+        assert!(sparse.contains(&(4, 40, Tile::from(Cell::Ball))));
+        assert!(sparse.contains(&(
+            5,
+            30,
+            Tile {
+                cell: Cell::PowerUp,
+                glyph: '-'
+            }
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_drawing_matches_the_grid_queries() -> Result<()> {
+        for hires in [false, true] {
+            let game = Game::new(GameConfig {
+                balls: 2,
+                paddle_width: 2,
+                powerups: true,
+                hires,
+                ..GameConfig::default()
+            })?;
+            // In hi-res the ball and the pickup share a character cell
+            game.connection().execute_batch(
+                "INSERT INTO powerups VALUES (30, 5, 'grow', 100);
+                 UPDATE state SET ax = 1, ball_x = 30, ball_y = 4, serve_timer = 20;",
+            )?;
+            let (Rendered::Cells(cells), Rendered::Cells(grid)) = (
+                game.render(RenderMode::Cells)?,
+                game.render(RenderMode::Grid)?,
+            ) else {
+                anyhow::bail!("cell render returned a full screen");
+            };
+            assert_eq!(cells, grid, "hires {hires}");
+            if !hires {
+                let Rendered::Full(screen) = game.render(RenderMode::Full)? else {
+                    anyhow::bail!("full render returned cells");
+                };
+                let lines: Vec<String> = grid
+                    .iter()
+                    .map(|line| line.iter().map(|tile| tile.glyph).collect())
+                    .collect();
+                assert_eq!(screen, lines.join("\r\n"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,289 +1,2847 @@
-use anyhow::Result;
-use core::f64;
+use anyhow::{Context, Result, bail};
+use clap::{Parser, ValueEnum};
 use crossterm::style::{Stylize, style};
 use crossterm::{QueueableCommand, cursor, event, style, terminal};
-use duckdb::Connection;
-use duckdb::arrow::array::ArrowNativeTypeOp;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-const SETUP_SQL: &str = r#"
--- Game constants: field dimensions and paddle properties
-CREATE TEMP TABLE params AS
-SELECT
-    80 AS W,              -- Width of the playing field (characters)
-    25 AS H,              -- Height of the playing field (characters)
-    7  AS PADDLE_H,       -- Height of each paddle (characters)
-    2  AS PADDLE_SPEED;   -- How fast paddles can move per frame
-
--- Game state: positions, velocities, and scores
--- This single row gets updated every frame with new positions
-CREATE TEMP TABLE state(
-    tick    INTEGER,      -- Frame counter (increases each update)
-    ax      INTEGER,      -- Player A paddle Y position (left side)
-    bx      INTEGER,      -- Player B paddle Y position (right side)
-    ball_x  INTEGER,      -- Ball X position (0 to W-1)
-    ball_y  INTEGER,      -- Ball Y position (0 to H-1)
-    vx      INTEGER,      -- Ball velocity in X direction (±1)
-    vy      INTEGER,      -- Ball velocity in Y direction (-2, -1, 0, 1, 2)
-    score_a INTEGER,      -- Player A score
-    score_b INTEGER       -- Player B score
-);
-
--- Initialize game with random starting position and angle
-INSERT INTO state
-SELECT
-    0,                                                       -- tick = 0 (start)
-    (H-PADDLE_H)/2,                                          -- Player A paddle centered
-    (H-PADDLE_H)/2,                                          -- Player B paddle centered
-    W/2,                                                     -- Ball at horizontal center
-    CAST(H/2 + (random() * 6 - 3) AS INTEGER),               -- Ball Y: center ± 3 pixels
-    CASE WHEN random() < 0.5 THEN 1 ELSE -1 END,             -- Ball direction: random left/right
-    CAST((random() * 5 - 2) AS INTEGER),                     -- Ball angle: -2 to +2 (5 angles)
-    0,                                                       -- Score A = 0
-    0                                                        -- Score B = 0
-FROM params;
-"#;
-
-const TICK_SQL: &str = r#"
--- Use CTEs (Common Table Expressions) to break down the game logic into clear steps
--- Each WITH clause is like a mini-table that feeds into the next step
-WITH
-    -- Load game parameters and current state for easy reference
-    p AS (SELECT * FROM params),
-    s AS (SELECT * FROM state),
-
--- STEP 1: AI DECISION - Calculate where each paddle should move
--- The AI mimics human players: track defensively, then make strategic shots when close
-ai AS (
-    SELECT
-        -- PLAYER A (left side) - Decide where to move the paddle
-        CASE
-        -- When ball is CLOSE (≤5 pixels away) and approaching: attempt trick shots!
-        -- Position paddle to hit ball at specific zones for different angles
-        WHEN s.vx < 0 AND s.ball_x <= 5 THEN
-            CASE
-                WHEN random() < 0.25 THEN greatest(s.ball_y - 0, 1)  -- Hit top: steep up (vy=-2)
-                WHEN random() < 0.50 THEN greatest(s.ball_y - 1, 1)  -- Hit upper: diagonal up (vy=-1)
-                WHEN random() < 0.55 THEN greatest(s.ball_y - 3, 1)  -- Hit center: straight (vy=0) RARE!
-                WHEN random() < 0.75 THEN greatest(s.ball_y - 5, 1)  -- Hit lower: diagonal down (vy=+1)
-                ELSE greatest(s.ball_y - 6, 1)                       -- Hit bottom: steep down (vy=+2)
-            END
-        -- When ball is FAR: track defensively (85% accuracy for more scoring opportunities)
-        WHEN random() < 0.85 THEN
-            CASE
-                WHEN s.ball_y < s.ax + 2 THEN greatest(s.ax - p.PADDLE_SPEED, 1)
-                WHEN s.ball_y > s.ax + p.PADDLE_H - 3 THEN least(s.ax + p.PADDLE_SPEED, p.H - p.PADDLE_H - 1)
-                ELSE s.ax
-            END
-        -- 15% of the time: don't move (more imperfection for shorter games)
-        ELSE s.ax
-        END AS ax2,
-        -- PLAYER B (right side) - Same logic but mirrored
-        -- Can be controlled by human player
-        CASE
-        WHEN s.vx > 0 AND s.ball_x >= p.W - 6 THEN
-            CASE
-                WHEN random() < 0.25 THEN greatest(s.ball_y - 0, 1)
-                WHEN random() < 0.50 THEN greatest(s.ball_y - 1, 1)
-                WHEN random() < 0.55 THEN greatest(s.ball_y - 3, 1)
-                WHEN random() < 0.75 THEN greatest(s.ball_y - 5, 1)
-                ELSE greatest(s.ball_y - 6, 1)
-            END
-        WHEN random() < 0.85 THEN
-            CASE
-                WHEN s.ball_y < s.bx + 2 THEN greatest(s.bx - p.PADDLE_SPEED, 1)
-                WHEN s.ball_y > s.bx + p.PADDLE_H - 3 THEN least(s.bx + p.PADDLE_SPEED, p.H - p.PADDLE_H - 1)
-                ELSE s.bx
-            END
-        ELSE s.bx
-        END AS bx2
-    FROM p, s
-),
-
--- STEP 2: BALL MOVEMENT - Move ball by its velocity
-step AS (
-    SELECT
-        s.ball_x + s.vx AS nx,
-        s.ball_y + s.vy AS ny,
-        s.vx,
-        s.vy
-    FROM s
-),
-
--- STEP 3: WALL COLLISION - Bounce ball off top/bottom walls
-wall AS (
-    SELECT
-        nx,
-        CASE WHEN ny <= 1 THEN 1 WHEN ny >= p.H-2 THEN p.H-2 ELSE ny END AS ny1,
-        vx AS vx1,
-        CASE WHEN ny <= 1 OR ny >= p.H-2 THEN -vy ELSE vy END AS vy1  -- Flip Y velocity
-    FROM step, p
-),
-
--- STEP 4: PADDLE COLLISION - Detect hits and calculate bounce angles
--- This is the magic! Ball angle depends on WHERE it hits the paddle (classic Pong physics)
-paddle AS (
-    SELECT
-        w.nx, w.ny1,
-        -- Reverse horizontal direction if paddle hit
-        CASE
-            WHEN w.nx <= 1     AND w.vx1 < 0 AND w.ny1 BETWEEN ai.ax2 AND ai.ax2 + p.PADDLE_H - 1 THEN 1
-            WHEN w.nx >= p.W-2 AND w.vx1 > 0 AND w.ny1 BETWEEN ai.bx2 AND ai.bx2 + p.PADDLE_H - 1 THEN -1
-            ELSE w.vx1
-        END AS vx2,
-        -- Calculate new vertical velocity based on hit zone (5 zones on paddle)
-        -- Top edge = steep up (-2), Center = straight (0), Bottom edge = steep down (+2)
-        CASE
-            WHEN w.nx <= 1 AND w.vx1 < 0 AND w.ny1 BETWEEN ai.ax2 AND ai.ax2 + p.PADDLE_H - 1 THEN
-                CASE
-                    WHEN w.ny1 - ai.ax2 =  0 THEN -2     -- Position 0: top edge
-                    WHEN w.ny1 - ai.ax2 <= 2 THEN -1     -- Positions 1-2: upper
-                    WHEN w.ny1 - ai.ax2 <= 4 THEN 0      -- Positions 3-4: center
-                    WHEN w.ny1 - ai.ax2 <= 5 THEN 1      -- Position 5: lower
-                    ELSE 2                               -- Position 6: bottom edge
-                END
-            WHEN w.nx >= p.W-2 AND w.vx1 > 0 AND w.ny1 BETWEEN ai.bx2 AND ai.bx2 + p.PADDLE_H - 1 THEN
-                CASE
-                    WHEN w.ny1 - ai.bx2 =  0 THEN -2
-                    WHEN w.ny1 - ai.bx2 <= 2 THEN -1
-                    WHEN w.ny1 - ai.bx2 <= 4 THEN 0
-                    WHEN w.ny1 - ai.bx2 <= 5 THEN 1
-                    ELSE 2
-                END
-            ELSE w.vy1
-        END AS vy2,
-        ai.ax2 AS ax2, ai.bx2 AS bx2
-    FROM wall w, ai, p
-),
-
--- STEP 5: SCORING - Detect if ball went past a paddle
-sc AS (
-    SELECT
-        CASE
-            WHEN paddle.nx < 1 THEN 'B'              -- Ball past left: Player B scores
-            WHEN paddle.nx > p.W-2 THEN 'A'          -- Ball past right: Player A scores
-            ELSE NULL                                -- NULL = still in play
-        END AS point_to,
-        paddle.*, p.W, p.H
-    FROM paddle, p
-),
-
--- STEP 6: UPDATE STATE - Combine all changes and increment scores
-next_state AS (
-    SELECT
-        s.tick + 1 AS tick,                           -- Increment frame counter
-        sc.ax2 AS ax, sc.bx2 AS bx,                   -- New paddle positions
-        -- Ball position: reset to center if scored, otherwise use new position
-        CASE
-            WHEN sc.point_to IS NULL THEN sc.nx
-            WHEN sc.point_to='A' THEN sc.W/2 + 1
-            ELSE sc.W/2 - 1
-        END AS ball_x,
-        CASE
-            WHEN sc.point_to IS NULL THEN sc.ny1
-            ELSE CAST(sc.H/2 + (random() * 6 - 3) AS INTEGER)
-        END AS ball_y,
-        -- Ball velocity: keep current if in play, otherwise random serve
-        CASE
-            WHEN sc.point_to IS NULL THEN sc.vx2
-            WHEN sc.point_to='A' THEN -1
-            ELSE 1
-        END AS vx,
-        CASE
-            WHEN sc.point_to IS NULL THEN sc.vy2
-            ELSE CAST((random() * 5 - 2) AS INTEGER)
-        END AS vy,
-        -- Increment score if someone scored
-        s.score_a + COALESCE((sc.point_to='A')::INT, 0) AS score_a,
-        s.score_b + COALESCE((sc.point_to='B')::INT, 0) AS score_b
-    FROM sc, state s
-)
-
--- Finally, write the new state back to the state table
-UPDATE state
-SET tick = n.tick, ax = n.ax, bx = n.bx,
-    ball_x = n.ball_x, ball_y = n.ball_y,
-    vx = n.vx, vy = n.vy,
-    score_a = n.score_a, score_b = n.score_b
-FROM next_state n;
-"#;
-
-const RENDER_SQL: &str = r#"
--- Generate the entire game screen as ASCII art, one character at a time
--- This creates an 80x25 grid and decides what character to put in each position
-SELECT y,
-    string_agg(
-        CASE
-        WHEN y IN (0,p.H-1) THEN '▀'                                         -- Top/bottom borders
-        WHEN x=1 AND y BETWEEN s.ax AND s.ax + p.PADDLE_H - 1 THEN '█'       -- Player A paddle (left)
-        WHEN x=p.W-2 AND y BETWEEN s.bx AND s.bx + p.PADDLE_H - 1 THEN '█'   -- Player B paddle (right)
-        WHEN x=s.ball_x AND y=s.ball_y THEN '█'                              -- Ball
-        WHEN x=p.W/2 AND (y % 3)=1 THEN '█'                                  -- Center line (dotted)
-        ELSE ' '                                                             -- Empty space
-        END, ''
-    ) AS line
-FROM params p, state s, range(0,p.H) AS t_y(y), range(0,p.W) AS t_x(x)
-GROUP BY y
-ORDER BY y;
-"#;
+use duckdb_pong::cast::{CastHeader, CastWriter};
+use duckdb_pong::config::{self, GameConfig};
+use duckdb_pong::heatmap;
+use duckdb_pong::history::{self, History, MatchResult, Opponent};
+use duckdb_pong::keys::{Action, Key};
+use duckdb_pong::net::{self, ClientMessage, ConnectionLost, HostMessage, NetRole};
+use duckdb_pong::record::{self, Recorder, RewindBuffer};
+use duckdb_pong::render::{self, BorderStyle, FIELD_TOP, FrameBuffer, RenderMode, Rendered, Trail};
+use duckdb_pong::script::Script;
+use duckdb_pong::sound::{Sound, SoundEvent, SoundMode};
+use duckdb_pong::stats::{MatchStats, ServeReturns};
+use duckdb_pong::status::StatusServer;
+use duckdb_pong::telemetry::TelemetryLog;
+use duckdb_pong::timing::{self, FrameCap, FramePacer, FrameTimings, RollingAverage};
+use duckdb_pong::{
+    AiStyle, Deuce, Difficulty, FirstServe, Game, IdlePaddle, Inputs, PlayMode, PlayerInput,
+    SIM_DT, SIM_HZ, ServeAngle, ServeRule, Side, State, TimeoutAction, Win, check_initial_state,
+};
 
-fn main() -> Result<()> {
-    let fps = 120;
-    let frame_dt = Duration::from_secs_f64(1.0 / fps as f64);
+/// Upper bound on catch-up ticks per rendered frame, so a stalled terminal can't snowball.
+const MAX_TICKS_PER_FRAME: u32 = 5;
 
-    let conn = Connection::open_in_memory()?;
-    conn.execute(SETUP_SQL, [])?;
+/// Frames the FPS counter and `--debug-timing` average over.
+const FPS_SAMPLES: usize = 30;
 
-    terminal::enable_raw_mode()?;
-    let mut stdout = io::BufWriter::new(io::stdout());
-    stdout
-        .queue(terminal::Clear(terminal::ClearType::All))?
-        .queue(cursor::Hide)?
-        .flush()?;
+/// How long to idle between redraws while the game is paused.
+const PAUSE_SLEEP: Duration = Duration::from_millis(50);
+
+/// With `--power-save`, the longest a paused game waits for input before redrawing.
+const POWER_SAVE_WAIT: Duration = Duration::from_secs(1);
+
+/// With `--momentum` a key press keeps pushing the paddle this long. Terminals don't
+/// report key releases, so this bridges the gaps between key repeats while a key is held.
+const MOMENTUM_KEY_HOLD: Duration = Duration::from_millis(100);
+
+/// Slow motion runs the simulation this many times slower.
+const SLOWMO_FACTOR: u32 = 4;
+
+/// Ticks per drawn frame while fast-forwarding, which runs without sleeping: enough
+/// to skip a rally in a moment, few enough that the ball is still seen moving.
+const FAST_FORWARD_TICKS: u32 = 20;
+
+/// Ticks the instant replay key shows again: the last five seconds.
+const REWIND_TICKS: usize = 300;
+
+/// Ticks `--replay-on-score` shows again after a point: the last two seconds.
+const SCORE_REPLAY_TICKS: usize = 120;
+
+/// The instant replay runs this many times slower than the match did.
+const INSTANT_REPLAY_SLOWDOWN: u32 = 2;
+
+/// How long into an instant replay its key is ignored, so the key repeat of the press
+/// that started it doesn't end it right away.
+const INSTANT_REPLAY_GUARD: Duration = Duration::from_millis(500);
+
+/// How long `--attract` shows the winner before starting the next match.
+const ATTRACT_RESTART_DELAY: Duration = Duration::from_secs(3);
+
+/// How long the "SET TO A" banner stays up before the next set is served.
+const SET_BANNER_TIME: Duration = Duration::from_millis(1500);
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Pong with all game logic running in DuckDB SQL")]
+struct Args {
+    /// Read game settings from this TOML file; flags given here override it
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Width of the playing field in characters [default: 80]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(config::MIN_WIDTH as i64..=config::MAX_WIDTH as i64))]
+    width: Option<u16>,
+
+    /// Height of the playing field in characters [default: 25]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(config::MIN_HEIGHT as i64..=config::MAX_HEIGHT as i64))]
+    height: Option<u16>,
+
+    /// Points a player needs to win a set, with a two-point lead [default: 11]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    target_score: Option<u32>,
+
+    /// Play a best-of-N match: the first to win more than half of the sets wins
+    /// [default: 1]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    sets: Option<u32>,
+
+    /// Number of balls in play at once (multi-ball mode when more than one) [default: 1]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=config::MAX_BALLS as i64))]
+    balls: Option<u32>,
+
+    /// Columns each paddle is thick [default: 1]
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u16).range(1..=config::MAX_PADDLE_WIDTH as i64))]
+    paddle_width: Option<u16>,
+
+    /// Rows a paddle moves per tick [default: 2]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    paddle_speed: Option<u32>,
+
+    /// Columns the ball moves per tick; bounce angles scale with it [default: 1]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=config::MAX_BALL_SPEED as i64))]
+    ball_speed: Option<u32>,
+
+    /// Fastest the ball gets during a long rally [default: 4]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=config::MAX_BALL_SPEED as i64))]
+    max_ball_speed: Option<u32>,
+
+    /// Paddle hits in a rally before the ball speeds up by one [default: 4]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    speedup_hits: Option<u32>,
+
+    /// Let moving paddles spin the ball: a paddle that moved up or down over the last
+    /// two ticks bends the bounce one step further that way (vy stays within -2..2,
+    /// times the ball speed)
+    #[arg(long)]
+    spin: bool,
+
+    /// Give paddles weight: holding a direction speeds a paddle up by 0.25 rows per
+    /// tick up to --paddle-speed, letting go makes it coast to a stop just as fast, and
+    /// running into a wall stops it dead
+    #[arg(long)]
+    momentum: bool,
+
+    /// Now and then put a pickup on the field that changes a paddle's size for 8
+    /// seconds when a ball crosses it: `+` grows the paddle of whoever hit that ball
+    /// last by 2 rows, `-` shrinks the other one by 2. Replays show paddles at their
+    /// normal size
+    #[arg(long, conflicts_with_all = ["host", "connect"])]
+    powerups: bool,
+
+    /// Which paddle you steer with the keys and mouse; the AI plays the other one.
+    /// Player A stays on the left either way [default: right]
+    #[arg(long, value_enum, conflicts_with_all = ["host", "connect"])]
+    human_side: Option<Side>,
+
+    /// Who plays: solo against the AI, or local-vs for two players at one keyboard,
+    /// W/S on the left paddle and the arrows on the right (keys left_up, left_down, up
+    /// and down) [default: solo]
+    #[arg(long, value_enum, conflicts_with_all = ["host", "connect", "attract"])]
+    mode: Option<PlayMode>,
+
+    /// What a paddle in local-vs does while its keys aren't pressed: hold where it is,
+    /// or leave it to the AI until they are [default: hold]
+    #[arg(long, value_enum)]
+    idle_paddle: Option<IdlePaddle>,
+
+    /// Practice alone: the left paddle is replaced by a wall the ball bounces off, and
+    /// every ball you miss costs a life until none are left
+    #[arg(long, conflicts_with_all = ["host", "connect", "replay", "attract"])]
+    training: bool,
+
+    /// Balls you may miss in --training before the session ends [default: 3]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    lives: Option<u32>,
+
+    /// Hold your serves at your paddle until you launch them with the serve key
+    /// (Enter). You serve first, and then whenever --serve sends the ball away from
+    /// your side; it leaves at the angle --serve-angle drew for it
+    #[arg(long, conflicts_with = "attract")]
+    wait_for_serve: bool,
+
+    /// Toroidal field: a ball leaving past the top or bottom edge comes back in at
+    /// the other one at the same speed instead of bouncing. The sides score as usual
+    #[arg(long)]
+    wrap: bool,
+
+    /// Draw the ball as a 2x2 block that is easier to follow. It bounces off paddles
+    /// and walls as a block too, and only scores once all of it is past a paddle
+    #[arg(long)]
+    big_ball: bool,
+
+    /// Hit zones on each paddle, each sending the ball off at its own angle: an odd
+    /// number from 3 to 9, spread over the paddle's height. More zones give finer
+    /// control and steeper edges [default: 5]
+    #[arg(long, value_name = "N", value_parser = parse_zones)]
+    zones: Option<u32>,
+
+    /// After every point, show the last two seconds of the rally again in slow motion
+    /// before the serve, like the instant replay key does; that key also skips it
+    #[arg(long, conflicts_with_all = ["host", "connect", "replay"])]
+    replay_on_score: bool,
+
+    /// Halve the ball's vertical speed to make up for terminal cells being about twice
+    /// as tall as wide, so a 45° ball looks like one. Has no effect with --hires,
+    /// whose half-block pixels are already about square
+    #[arg(long)]
+    aspect_correct: bool,
+
+    /// Ticks a rally may go on without a point before --on-rally-timeout steps in; 0
+    /// lets it go on for as long as it takes [default: 0]
+    #[arg(long, value_name = "TICKS")]
+    rally_timeout: Option<u32>,
+
+    /// What a rally that hits --rally-timeout gets: a let serves again without a
+    /// point, speedup makes every ball one column per tick faster [default: let]
+    #[arg(long, value_enum)]
+    on_rally_timeout: Option<TimeoutAction>,
+
+    /// How the AI plays: reactive follows the ball and goes for trick shots, predictive
+    /// works out where the ball will land, bounces included, and waits there
+    /// [default: reactive]
+    #[arg(long, value_enum)]
+    ai: Option<AiStyle>,
+
+    /// Most rows the predictive AI misjudges where a ball lands by, so it can be
+    /// beaten; 0 makes it miss only balls it can't reach in time [default: 4]
+    #[arg(long, value_name = "ROWS")]
+    ai_error: Option<u16>,
+
+    /// Let the score sway the AI: behind, it goes for steep trick shots more often,
+    /// 5% more per point up to 3 points; ahead, it plays safer diagonals instead
+    #[arg(long)]
+    adaptive_ai: bool,
+
+    /// Sharpen the AI as the match goes on: every 20 seconds it tracks the ball 3%
+    /// more often, up to 99%, and reacts a row sooner, until at most the middle row of
+    /// its paddle is a dead zone
+    #[arg(long)]
+    ramp: bool,
+
+    /// Take the randomness out of the AI for repeatable exhibition matches: it always
+    /// tracks the ball, and aims its trick shots at the top edge, upper part, center,
+    /// lower part and bottom edge of the paddle in turn, by tick. Serves stay random
+    #[arg(long)]
+    deterministic_ai: bool,
+
+    /// Milliseconds both AIs take to react: each only decides where to go this often
+    /// and sticks to that decision in between [default: 0]
+    #[arg(long, value_name = "MS")]
+    ai_reaction_ms: Option<u32>,
+
+    /// Reaction time of Player A's AI alone, overriding --ai-reaction-ms
+    #[arg(long, value_name = "MS")]
+    ai_reaction_ms_a: Option<u32>,
+
+    /// Reaction time of Player B's AI alone, overriding --ai-reaction-ms
+    #[arg(long, value_name = "MS")]
+    ai_reaction_ms_b: Option<u32>,
+
+    /// Milliseconds to hold the balls with a countdown after each point, 0 to serve
+    /// right away [default: 1000]
+    #[arg(long, value_name = "MS")]
+    serve_delay: Option<u32>,
+
+    /// Milliseconds of the "READY? 3, 2, 1, GO!" intro before the match, 0 to skip
+    /// it; any key skips it too. There is none with --attract, --max-frames or --host
+    /// [default: 2500]
+    #[arg(long, value_name = "MS")]
+    intro_delay: Option<u32>,
+
+    /// Start the match right away instead of at the start menu, where the mode and
+    /// difficulty the flags set can still be changed. There is no menu when the flags
+    /// already say what to do, e.g. with --replay, --host or --max-frames
+    #[arg(long)]
+    no_menu: bool,
+
+    /// Frame rate cap, 0 for uncapped [default: 120]
+    #[arg(long)]
+    fps: Option<u32>,
+
+    /// While paused, or waiting for the terminal to be resized, sleep until a key is
+    /// pressed instead of redrawing 20 times a second, for next to no CPU use
+    #[arg(long)]
+    power_save: bool,
+
+    /// Who the opening serve of a match heads for; with --wait-for-serve it is always
+    /// the player's [default: random]
+    #[arg(long, value_enum)]
+    first_serve: Option<FirstServe>,
+
+    /// Who the ball is served to after a point [default: winner]
+    #[arg(long, value_enum)]
+    serve: Option<ServeRule>,
+
+    /// How steep serves start: mostly flat, any angle alike, or mostly steep
+    /// [default: random]
+    #[arg(long, value_enum)]
+    serve_angle: Option<ServeAngle>,
+
+    /// How well the AI plays [default: normal]
+    #[arg(long, value_enum)]
+    difficulty: Option<Difficulty>,
+
+    /// Draw everything in the terminal's default color
+    #[arg(long)]
+    no_color: bool,
+
+    /// Dim every other row of the field like the scanlines of an old CRT screen
+    #[arg(long)]
+    crt: bool,
+
+    /// Draw with 7-bit ASCII only, for terminals and SSH sessions that garble block
+    /// characters: `-` borders, `|` paddles, an `O` ball and a `:` center line
+    #[arg(long)]
+    ascii: bool,
+
+    /// Ring the terminal bell on paddle hits and points, at most one hit every
+    /// 150 ms [default: off]
+    #[arg(long, value_enum)]
+    sound: Option<SoundMode>,
+
+    /// Draw the top and bottom edges with half blocks, box-drawing lines or plain
+    /// ASCII for terminals with poor Unicode [default: blocks]
+    #[arg(long, value_enum)]
+    border: Option<BorderStyle>,
+
+    /// Leave out the dotted line down the middle of the field
+    #[arg(long)]
+    no_center_line: bool,
+
+    /// Leave a fading trail of the ball's last N positions behind it [default: 0]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(0..=config::MAX_TRAIL as i64))]
+    trail: Option<u32>,
+
+    /// Double the vertical resolution by drawing two pixel rows per terminal row with
+    /// half-block characters
+    #[arg(long)]
+    hires: bool,
+
+    /// Seed for DuckDB's random() (between -1 and 1) to make games reproducible
+    #[arg(long, value_parser = parse_seed)]
+    seed: Option<f64>,
+
+    /// Play a new game stored in this DuckDB file, checkpointed on exit
+    #[arg(long, value_name = "PATH", conflicts_with = "load")]
+    save: Option<PathBuf>,
+
+    /// Resume the game stored in this DuckDB file and keep saving to it
+    #[arg(long, value_name = "PATH")]
+    load: Option<PathBuf>,
+
+    /// Record every tick's state to this CSV file
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Log every tick as a JSON line to this file: the ball, the paddles, where the
+    /// AI is moving them, and hits and points
+    #[arg(long, value_name = "PATH")]
+    log: Option<PathBuf>,
+
+    /// Count how often the ball is on each cell, over every match of the run, and
+    /// write the counts to this CSV file on exit; `-` draws them in shades instead
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "replay", "connect", "bench", "screenshot", "export_cast", "stats",
+            "print_config", "self_test",
+        ],
+    )]
+    heatmap: Option<PathBuf>,
+
+    /// Play back a match recorded with --record (pass the same --width and --height)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["save", "load", "record"])]
+    replay: Option<PathBuf>,
+
+    /// CSV file that the results of finished matches are added to, and the record
+    /// against the AI before each match is read from
+    /// [default: ~/.duckdb-pong-history.csv]
+    #[arg(long, value_name = "PATH")]
+    history: Option<PathBuf>,
+
+    /// Print your all-time record from --history and exit without playing
+    #[arg(long)]
+    stats: bool,
+
+    /// Print the settings this run would play with as JSON, the config file and flags
+    /// merged over the defaults (or a --load game's own), and exit without playing.
+    /// Together with --seed that is everything needed to play the same match again
+    #[arg(long)]
+    print_config: bool,
+
+    /// Playback speed multiplier for --replay
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = parse_speed)]
+    replay_speed: f64,
+
+    /// Host a two-player game on this port: you play B, the player who connects plays A
+    #[arg(long, value_name = "PORT", conflicts_with_all = ["connect", "replay", "bench"])]
+    host: Option<u16>,
+
+    /// Join a game hosted with --host at ADDR:PORT as Player A
+    #[arg(
+        long,
+        value_name = "ADDR:PORT",
+        conflicts_with_all = ["save", "load", "record", "replay", "bench"]
+    )]
+    connect: Option<String>,
+
+    /// Demo mode for an idle screen: both paddles play themselves and a new match
+    /// starts a few seconds after each one ends, until the quit key
+    #[arg(long, conflicts_with_all = ["host", "connect", "replay", "bench"])]
+    attract: bool,
+
+    /// Play a scripted rally for demos from this CSV file: the paddle moves and
+    /// positions it lists for each tick, with fixed numbers instead of random ones,
+    /// so it plays out the same every time. See the `script` module for the columns
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["host", "connect", "replay", "bench", "attract"]
+    )]
+    script: Option<PathBuf>,
+
+    /// Serve the live game state as JSON on a Unix socket at this path, one object per
+    /// connection (try `nc -U PATH`)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["connect", "replay", "bench"])]
+    status_socket: Option<PathBuf>,
+
+    /// Bind keys to an action (up, down, pause, quit, restart, slowmo, fps_up,
+    /// fps_down, left_up, left_down, serve, fast_forward or instant_replay), replacing its
+    /// default keys, e.g. `--bind up=k,up`. Keys are named like `w`, `space`, `esc`,
+    /// `f1`; repeat the flag for more actions
+    #[arg(long, value_name = "ACTION=KEYS", value_parser = parse_binding)]
+    bind: Vec<(Action, Vec<Key>)>,
+
+    /// Render query to draw with; compare their cost with --debug-timing
+    #[arg(long, value_enum, default_value_t = RenderMode::Cells)]
+    render: RenderMode,
+
+    /// Show how long ticking, rendering, terminal output and sleeping take per frame
+    #[arg(long)]
+    debug_timing: bool,
+
+    /// Show the ball's position and velocity, in columns and rows per tick, on the
+    /// status line of games, replays and network clients alike, to check the angles
+    /// the physics sends it off at
+    #[arg(long)]
+    debug_vectors: bool,
+
+    /// Print every frame as plain text, frames separated by form feeds, without
+    /// colors, cursor movement or key input; the AI plays both sides. On by itself
+    /// when stdout is not a terminal, e.g. piped into a log
+    #[arg(long, conflicts_with_all = ["host", "connect", "replay", "attract"])]
+    plain: bool,
+
+    /// Don't wait for the terminal to be big enough for the field, at the start or
+    /// after a resize, for terminals that report their size wrong
+    #[arg(long)]
+    no_size_check: bool,
+
+    /// Exit after drawing this many frames and print the final score, e.g. for smoke
+    /// tests with a fixed --seed
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_frames: Option<u64>,
+
+    /// Write frame --max-frames as plain text to this file and exit, without a
+    /// terminal. The match is played like with --plain, or with --replay the recorded
+    /// frame is taken
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "max_frames",
+        conflicts_with_all = ["host", "connect", "attract", "plain", "bench"]
+    )]
+    screenshot: Option<PathBuf>,
+
+    /// Convert the --replay recording into an asciinema cast at this path and exit,
+    /// without a terminal. Frames are timed by --replay-speed
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "replay",
+        conflicts_with_all = ["screenshot", "bench", "max_frames"]
+    )]
+    export_cast: Option<PathBuf>,
+
+    /// Title in the header of the --export-cast file
+    #[arg(long, value_name = "TEXT", requires = "export_cast")]
+    cast_title: Option<String>,
+
+    /// Shorten pauses in the --export-cast file to at most this many seconds when
+    /// it is played back, e.g. the wait after a point
+    #[arg(long, value_name = "SECONDS", requires = "export_cast", value_parser = parse_speed)]
+    cast_idle_limit: Option<f64>,
+
+    /// Run this many ticks without rendering and report timings (no terminal needed)
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..))]
+    bench: Option<u32>,
+
+    /// Set the game up with a range of seeds and check each starts sane: paddles in
+    /// the field, the ball in the center column at the ball speed, no score (no
+    /// terminal needed)
+    #[arg(long, conflicts_with_all = ["seed", "load", "bench"])]
+    self_test: bool,
+}
+
+fn parse_zones(value: &str) -> Result<u32, String> {
+    let zones: u32 = value.parse().map_err(|e| format!("{e}"))?;
+    if (3..=config::MAX_ZONES).contains(&zones) && zones % 2 == 1 {
+        Ok(zones)
+    } else {
+        Err(format!(
+            "zones must be odd and between 3 and {}",
+            config::MAX_ZONES
+        ))
+    }
+}
+
+fn parse_seed(value: &str) -> Result<f64, String> {
+    let seed: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    if (-1.0..=1.0).contains(&seed) {
+        Ok(seed)
+    } else {
+        Err("seed must be between -1 and 1".to_string())
+    }
+}
+
+fn parse_binding(value: &str) -> Result<(Action, Vec<Key>), String> {
+    let (action, keys) = value
+        .split_once('=')
+        .ok_or_else(|| "expected ACTION=KEYS, e.g. up=k,up".to_string())?;
+    let action = Action::from_str(action, true)?;
+    let keys = keys.split(',').map(str::parse).collect::<Result<_, _>>()?;
+    Ok((action, keys))
+}
+
+fn parse_speed(value: &str) -> Result<f64, String> {
+    let speed: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    if speed > 0.0 && speed.is_finite() {
+        Ok(speed)
+    } else {
+        Err("speed must be a positive number".to_string())
+    }
+}
+
+impl Args {
+    /// The database file backing this game, if it is saved at all.
+    fn db_path(&self) -> Option<&Path> {
+        self.save.as_deref().or(self.load.as_deref())
+    }
+
+    fn history_path(&self) -> PathBuf {
+        self.history.clone().unwrap_or_else(|| {
+            std::env::home_dir()
+                .unwrap_or_default()
+                .join(".duckdb-pong-history.csv")
+        })
+    }
+
+    /// Whether this run is a match that belongs in the history: not a demo or a
+    /// scripted run. A client's match is its host's to record.
+    fn keeps_history(&self) -> bool {
+        !self.attract
+            && !matches!(self.net_role(), Some(NetRole::Client(_)))
+            && self.max_frames.is_none()
+            && self.script.is_none()
+    }
+
+    /// Whether to open at the start menu: for a new local game in a terminal, unless
+    /// the flags play something else or print and exit.
+    fn shows_menu(&self) -> bool {
+        let scripted = self.max_frames.is_some()
+            || self.bench.is_some()
+            || self.screenshot.is_some()
+            || self.export_cast.is_some()
+            || self.stats
+            || self.plain
+            || self.script.is_some();
+        let other_game = self.load.is_some()
+            || self.replay.is_some()
+            || self.net_role().is_some()
+            || self.attract;
+        !self.no_menu && !scripted && !other_game && io::stdout().is_terminal()
+    }
+
+    fn net_role(&self) -> Option<NetRole> {
+        match (self.host, &self.connect) {
+            (Some(port), _) => Some(NetRole::Host(port)),
+            (None, Some(addr)) => Some(NetRole::Client(addr.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// The settings to play with: defaults, then the `--config` file, then any flags
+    /// given on the command line.
+    fn game_config(&self) -> Result<GameConfig> {
+        let mut config = match &self.config {
+            Some(path) => GameConfig::load(path)?,
+            None => GameConfig::default(),
+        };
+        config.width = self.width.unwrap_or(config.width);
+        config.height = self.height.unwrap_or(config.height);
+        config.target_score = self.target_score.unwrap_or(config.target_score);
+        config.sets = self.sets.unwrap_or(config.sets);
+        config.balls = self.balls.unwrap_or(config.balls);
+        config.paddle_width = self.paddle_width.unwrap_or(config.paddle_width);
+        config.paddle_speed = self.paddle_speed.unwrap_or(config.paddle_speed);
+        config.ball_speed = self.ball_speed.unwrap_or(config.ball_speed);
+        config.max_ball_speed = self.max_ball_speed.unwrap_or(config.max_ball_speed);
+        config.speedup_hits = self.speedup_hits.unwrap_or(config.speedup_hits);
+        config.serve_delay = self.serve_delay.unwrap_or(config.serve_delay);
+        config.intro_delay = self.intro_delay.unwrap_or(config.intro_delay);
+        if let Some(ms) = self.ai_reaction_ms {
+            (config.ai_reaction_a, config.ai_reaction_b) = (ms, ms);
+        }
+        config.ai_reaction_a = self.ai_reaction_ms_a.unwrap_or(config.ai_reaction_a);
+        config.ai = self.ai.unwrap_or(config.ai);
+        config.ai_error = self.ai_error.unwrap_or(config.ai_error);
+        config.ai_reaction_b = self.ai_reaction_ms_b.unwrap_or(config.ai_reaction_b);
+        config.human_side = self.human_side.unwrap_or(config.human_side);
+        // A script steers Player A as a second player and Player B as the local one,
+        // so the local player has to be on the right
+        if self.script.is_some() {
+            config.human_side = Side::Right;
+        }
+        config.mode = self.mode.unwrap_or(config.mode);
+        config.idle_paddle = self.idle_paddle.unwrap_or(config.idle_paddle);
+        config.lives = self.lives.unwrap_or(config.lives);
+        config.rally_timeout = self.rally_timeout.unwrap_or(config.rally_timeout);
+        config.on_rally_timeout = self.on_rally_timeout.unwrap_or(config.on_rally_timeout);
+        config.fps = self.fps.unwrap_or(config.fps);
+        config.trail = self.trail.unwrap_or(config.trail);
+        config.serve = self.serve.unwrap_or(config.serve);
+        config.serve_angle = self.serve_angle.unwrap_or(config.serve_angle);
+        config.first_serve = self.first_serve.unwrap_or(config.first_serve);
+        config.zones = self.zones.unwrap_or(config.zones);
+        config.difficulty = self.difficulty.unwrap_or(config.difficulty);
+        if self.spin {
+            config.spin = true;
+        }
+        if self.momentum {
+            config.momentum = true;
+        }
+        if self.powerups {
+            config.powerups = true;
+        }
+        if self.training {
+            config.training = true;
+        }
+        if self.adaptive_ai {
+            config.adaptive_ai = true;
+        }
+        if self.ramp {
+            config.ramp = true;
+        }
+        if self.deterministic_ai {
+            config.deterministic_ai = true;
+        }
+        if self.wait_for_serve {
+            config.wait_for_serve = true;
+        }
+        if self.wrap {
+            config.wrap = true;
+        }
+        if self.big_ball {
+            config.big_ball = true;
+        }
+        if self.aspect_correct {
+            config.aspect_correct = true;
+        }
+        if self.no_color {
+            config.color = false;
+        }
+        if self.crt {
+            config.crt = true;
+        }
+        if self.ascii {
+            config.ascii = true;
+        }
+        config.sound = self.sound.unwrap_or(config.sound);
+        config.border = self.border.unwrap_or(config.border);
+        if self.no_center_line {
+            config.center_line = false;
+        }
+        if self.hires {
+            config.hires = true;
+        }
+        for (action, keys) in &self.bind {
+            config.keys.bind(*action, keys.clone());
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// The terminal set up for the game: raw mode, hidden cursor, mouse capture and focus
+/// events. All of it is undone when the guard is dropped, which also happens when the
+/// game returns early with an error or panics, so the shell is never left unusable.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        // From here on, anything that fails still gets raw mode switched off again
+        let guard = TerminalGuard;
+        io::stdout()
+            .queue(terminal::Clear(terminal::ClearType::All))?
+            .queue(cursor::Hide)?
+            // A status line wider than the terminal is cut off instead of wrapping
+            // onto a new row, which would scroll the field up on the last one
+            .queue(terminal::DisableLineWrap)?
+            .queue(event::EnableMouseCapture)?
+            .queue(event::EnableFocusChange)?
+            .flush()?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        // Nothing to report errors to from here, so restore as much as possible
+        let mut stdout = io::stdout();
+        let _ = stdout
+            .queue(event::DisableFocusChange)
+            .and_then(|out| out.queue(event::DisableMouseCapture))
+            .and_then(|out| out.queue(terminal::EnableLineWrap))
+            .and_then(|out| out.queue(cursor::Show))
+            .and_then(|out| out.flush());
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Whether `error` comes from writing to a terminal or pipe that was closed on the
+/// other end, rather than from the game or any other I/O.
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// Score header for the row above the field, padded to the field width. A's score
+/// ends just left of the center line and B's starts just right of it, so both stay
+/// put as they grow more digits.
+fn score_header(width: u16, score_a: i32, score_b: i32) -> String {
+    let center = usize::from(width / 2);
+    let a = score_a.to_string();
+    let left = center.saturating_sub(2 + a.len());
+    let header = format!("{:left$}{a}    {score_b}", "");
+    format!("{header:width$}", width = usize::from(width))
+}
+
+/// The ball's position and velocity for `--debug-vectors`, e.g. `BALL 40,12 V +1,-2`.
+fn ball_vector(state: &State) -> String {
+    format!(
+        "BALL {},{} V {:+},{:+}",
+        state.ball_x, state.ball_y, state.vx, state.vy
+    )
+}
+
+/// The frame as `--plain` and `--screenshot` write it: the score header and the
+/// field's text lines, without trailing blanks.
+fn frame_text(game: &Game) -> Result<Vec<String>> {
+    let state = game.state()?;
+    let header = score_header(game.config().width, state.score_a, state.score_b);
+    let mut lines = vec![header.trim_end().to_string()];
+    for line in game.render_lines()? {
+        lines.push(line.trim_end().to_string());
+    }
+    Ok(lines)
+}
+
+/// `--screenshot`: play `--max-frames` ticks as fast as possible, one per frame as in
+/// `--plain`, or show that frame of the `--replay` recording, and write it to `path`.
+fn run_screenshot(game: &mut Game, args: &Args, path: &Path) -> Result<()> {
+    let frames = args.max_frames.unwrap_or(1);
+    if let Some(replay) = &args.replay {
+        let recorded = record::load_replay(game.connection(), replay)?;
+        let Some(last) = recorded.len().checked_sub(1) else {
+            bail!("recording {} has no frames", replay.display());
+        };
+        let index = usize::try_from(frames - 1).unwrap_or(usize::MAX).min(last);
+        record::show(game.connection(), &recorded[index])?;
+    } else {
+        for frame in 1..=frames {
+            game.tick(Inputs::default())?;
+            if frame == frames {
+                break;
+            }
+            match game.winner()? {
+                Some(Win::Set(_)) => {
+                    game.next_set()?;
+                }
+                Some(Win::Match(_)) => break,
+                None => {}
+            }
+        }
+    }
+    let mut text = frame_text(game)?.join("\n");
+    text.push('\n');
+    std::fs::write(path, text).with_context(|| format!("writing screenshot {}", path.display()))?;
+    Ok(())
+}
+
+/// `--export-cast`: show each frame of the `replay` recording as `--plain` prints
+/// it and write them to an asciinema cast at `path`, one [`SIM_DT`] apart at
+/// `--replay-speed`.
+fn run_export_cast(game: &Game, args: &Args, replay: &Path, path: &Path) -> Result<()> {
+    let frames = record::load_replay(game.connection(), replay)?;
+    if frames.is_empty() {
+        bail!("recording {} has no frames", replay.display());
+    }
+    let config = game.config();
+    let header = CastHeader {
+        width: config.width,
+        // The score header above the field
+        height: config.height + 1,
+        title: args.cast_title.clone(),
+        idle_time_limit: args.cast_idle_limit,
+    };
+    let mut cast = CastWriter::create(path, &header)?;
+    let frame_dt = SIM_DT.div_f64(args.replay_speed);
+    for (i, state) in frames.iter().enumerate() {
+        record::show(game.connection(), state)?;
+        cast.frame(frame_dt * i as u32, &frame_text(game)?)?;
+    }
+    cast.finish()
+}
+
+/// Open the game database and start a match, or resume the one stored in `--load`.
+fn open_game(args: &Args, config: GameConfig) -> Result<Game> {
+    // A script plays out the same every time, from the first serve on
+    let seed = args.seed.or(args.script.is_some().then_some(0.0));
+    Game::open(config, args.db_path(), args.load.is_some(), seed)
+}
+
+/// Write the `--heatmap` the run added up, if one was asked for: to its CSV file, or
+/// drawn in shades to `out` for `-`.
+fn write_heatmap(game: &Game, args: &Args, out: &mut impl Write) -> Result<()> {
+    match args.heatmap.as_deref() {
+        Some(path) if path == Path::new("-") => {
+            for line in heatmap::shade(game.connection())? {
+                writeln!(out, "{line}")?;
+            }
+        }
+        Some(path) => heatmap::write_csv(game.connection(), path)?,
+        None => {}
+    }
+    Ok(())
+}
 
+/// Seeds `--self-test` sets the game up with, spread evenly over -1 to 1.
+const SELF_TEST_SEEDS: u32 = 20;
+
+/// `--self-test`: set up `config` once per seed and check every initial state with
+/// [`check_initial_state`]. Stops at the first one that isn't sane.
+fn run_self_test(config: &GameConfig) -> Result<()> {
+    for i in 0..SELF_TEST_SEEDS {
+        let seed = f64::from(i) / f64::from(SELF_TEST_SEEDS) * 2.0 - 1.0;
+        let game = Game::open(config.clone(), None, false, Some(seed))?;
+        check_initial_state(game.connection())
+            .with_context(|| format!("self-test failed with --seed {seed}"))?;
+    }
+    println!(
+        "self-test passed: {SELF_TEST_SEEDS} seeds on a {}x{} field",
+        config.width, config.height
+    );
+    Ok(())
+}
+
+/// Run the tick query back to back with no rendering or sleeping and print how fast
+/// the SQL simulation alone is.
+fn run_bench(game: &mut Game, frames: u32) -> Result<()> {
+    let start = Instant::now();
+    for _ in 0..frames {
+        game.tick(Inputs::default())?;
+    }
+    let total = start.elapsed().as_secs_f64();
+    println!("{frames} ticks in {total:.3} s");
+    println!("{:.1} µs per tick", total * 1e6 / f64::from(frames));
+    println!("{:.0} ticks per second", f64::from(frames) / total);
+    Ok(())
+}
+
+/// `--plain`: one tick per frame at [`SIM_HZ`], each frame printed as the score header
+/// and the field's text lines with a form feed before it, then the match summary.
+/// Nobody is at the keys, so the AI plays both paddles, and the match winner ends it,
+/// or in training the last life lost.
+fn run_plain(game: &mut Game, args: &Args) -> Result<()> {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let mut recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+    let mut log = args.log.as_deref().map(TelemetryLog::create).transpose()?;
+    let mut script = args
+        .script
+        .as_deref()
+        .map(|path| Script::load(game.connection(), path))
+        .transpose()?;
+    let mut last_state = game.state()?;
+    let mut stats = MatchStats::new();
+    let mut cap = FrameCap::new(args.max_frames);
+    let mut pacer = FramePacer::new(SIM_HZ as u32);
+    let mut first = true;
     loop {
-        if event::poll(Duration::ZERO)? {
+        // Nobody would launch a waiting serve, so it goes right away
+        game.serve()?;
+        match script.as_mut() {
+            Some(script) => match script.step(game.connection(), last_state.tick)? {
+                Some(inputs) => game.tick_with_rolls(inputs, Script::ROLLS)?,
+                None => break,
+            },
+            None => game.tick(Inputs::default())?,
+        }
+        if args.heatmap.is_some() {
+            heatmap::record(game.connection())?;
+        }
+        let state = game.state()?;
+        stats.record(&state);
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(&state)?;
+        }
+        if let Some(log) = log.as_mut() {
+            let event = SoundEvent::between(&last_state, &state, game.config().width.into());
+            log.log(&state, game.ai_targets()?, event)?;
+        }
+        last_state = state;
+
+        if !first {
+            write!(out, "\x0c")?;
+        }
+        first = false;
+        for line in frame_text(game)? {
+            writeln!(out, "{line}")?;
+        }
+        out.flush()?;
+        if cap.frame_drawn() {
+            break;
+        }
+        if game.lives()? == Some(0) {
+            writeln!(out, "OUT OF LIVES")?;
+            break;
+        }
+
+        match game.winner()? {
+            Some(Win::Set(winner)) => {
+                let (set_a, set_b) = game.next_set()?;
+                writeln!(out, "SET TO {winner}, sets {set_a} - {set_b}")?;
+            }
+            Some(Win::Match(winner)) => {
+                writeln!(out, "PLAYER {winner} WINS")?;
+                break;
+            }
+            None => {}
+        }
+        pacer.wait();
+    }
+    writeln!(out)?;
+    write!(out, "{}", stats.summary())?;
+    let state = game.state()?;
+    writeln!(
+        out,
+        "\nFinal score: A {} - {} B",
+        state.score_a, state.score_b
+    )?;
+    write_heatmap(game, args, &mut out)?;
+    out.flush()?;
+    if let Some(log) = log {
+        log.finish()?;
+    }
+    if args.db_path().is_some() {
+        game.connection().execute_batch("CHECKPOINT")?;
+    }
+    Ok(())
+}
+
+/// Draw `lines` centered over the middle of the field.
+fn draw_banner(out: &mut impl Write, config: &GameConfig, lines: &[String]) -> Result<()> {
+    let top = FIELD_TOP + config.height / 2 - 1;
+    for (i, line) in lines.iter().enumerate() {
+        let col = config.width.saturating_sub(line.chars().count() as u16) / 2;
+        out.queue(cursor::MoveTo(col, top + i as u16))?
+            .queue(style::PrintStyledContent(line.as_str().reverse()))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Before anything is drawn, hold on a message until the terminal is big enough for
+/// the field, rechecking on every resize. Returns `false` if the player quit instead.
+fn wait_for_size(config: &GameConfig, out: &mut impl Write) -> Result<bool> {
+    let (mut cols, mut rows) = terminal::size()?;
+    let (min_cols, min_rows) = config.required_size();
+    while !config.fits(cols, rows) {
+        out.queue(terminal::Clear(terminal::ClearType::All))?
+            .queue(cursor::MoveTo(0, 0))?
+            .queue(style::Print(format!(
+                "Terminal is {cols}x{rows} — resize to at least {min_cols}x{min_rows}, or press {} to exit",
+                config.keys.label(Action::Quit)
+            )))?
+            .flush()?;
+        match event::read()? {
+            event::Event::Resize(new_cols, new_rows) => (cols, rows) = (new_cols, new_rows),
+            event::Event::Key(key_event)
+                if config.keys.action(key_event.code) == Some(Action::Quit) =>
+            {
+                return Ok(false);
+            }
+            _ => {}
+        }
+    }
+    out.queue(terminal::Clear(terminal::ClearType::All))?;
+    Ok(true)
+}
+
+/// The steps of the intro, each shown for an equal share of `--intro-delay`.
+const INTRO_STEPS: [&str; 5] = ["READY?", "3", "2", "1", "GO!"];
+
+/// Count down over the field before the first serve, with the record from `--history`
+/// under "READY?" once there is one. Any key skips the rest; returns `false` if that
+/// key was quit. A history that can't be read only leaves the record out, with a line
+/// in `warnings` saying why.
+fn play_intro(
+    game: &Game,
+    args: &Args,
+    hosting: bool,
+    warnings: &mut Vec<String>,
+    out: &mut impl Write,
+) -> Result<bool> {
+    let config = game.config();
+    // Demos and scripted runs start right away, and a remote player couldn't skip it
+    if config.intro_delay == 0 || args.attract || args.max_frames.is_some() || hosting {
+        return Ok(true);
+    }
+    let step_time = Duration::from_millis(u64::from(config.intro_delay)) / INTRO_STEPS.len() as u32;
+    let state = game.state()?;
+    let history = history::load(game.connection(), &args.history_path()).unwrap_or_else(|error| {
+        warnings.push(format!("the match history was not read: {error:#}"));
+        History::default()
+    });
+    let mut frame = FrameBuffer::new(config.color, config.crt, config.colors, config.glyphs());
+    for (i, step) in INTRO_STEPS.into_iter().enumerate() {
+        // A shorter banner doesn't cover the last one, so redraw the field under it
+        frame.invalidate();
+        frame.draw(&game.render(args.render)?, out)?;
+        out.queue(cursor::MoveTo(0, 0))?
+            .queue(style::Print(score_header(
+                config.width,
+                state.score_a,
+                state.score_b,
+            )))?;
+        let mut lines = vec![format!(" {step} ")];
+        if i == 0 && history.matches > 0 && !config.training {
+            lines.push(format!(" {} ", history.record()));
+        }
+        draw_banner(out, config, &lines)?;
+
+        let deadline = Instant::now() + step_time;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !event::poll(remaining)? {
+                break;
+            }
             if let event::Event::Key(key_event) = event::read()? {
-                if key_event.code == event::KeyCode::Esc {
-                    break;
+                return Ok(config.keys.action(key_event.code) != Some(Action::Quit));
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Draw `title`, e.g. who won, over the last frame and wait for the player's choice.
+/// Returns `true` to start a new match (restart key) and `false` to quit.
+fn game_over(out: &mut impl Write, config: &GameConfig, title: &str) -> Result<bool> {
+    let keys = &config.keys;
+    draw_banner(
+        out,
+        config,
+        &[
+            format!(" {title} "),
+            format!(
+                " {}: play again   {}: quit ",
+                keys.label(Action::Restart),
+                keys.label(Action::Quit)
+            ),
+        ],
+    )?;
+
+    loop {
+        if let event::Event::Key(key_event) = event::read()? {
+            match keys.action(key_event.code) {
+                Some(Action::Restart) => return Ok(true),
+                Some(Action::Quit) => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// An entry of the start menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuItem {
+    Start,
+    LocalVs,
+    Difficulty,
+    Quit,
+}
+
+/// The start menu's entries, top to bottom.
+const MENU_ITEMS: [MenuItem; 4] = [
+    MenuItem::Start,
+    MenuItem::LocalVs,
+    MenuItem::Difficulty,
+    MenuItem::Quit,
+];
+
+/// How the start menu was left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuChoice {
+    Play,
+    Quit,
+}
+
+/// The start menu's selection and what keys do to it and to the settings, apart
+/// from drawing it.
+#[derive(Debug, Clone, PartialEq)]
+struct Menu {
+    selected: usize,
+    /// Why the last choice couldn't be played, shown under the entries.
+    problem: Option<String>,
+}
+
+impl Menu {
+    /// A menu on the entry matching `config`'s mode.
+    fn new(config: &GameConfig) -> Self {
+        let current = match config.mode {
+            PlayMode::Solo => MenuItem::Start,
+            PlayMode::LocalVs => MenuItem::LocalVs,
+        };
+        Menu {
+            selected: MENU_ITEMS
+                .iter()
+                .position(|&item| item == current)
+                .unwrap_or(0),
+            problem: None,
+        }
+    }
+
+    /// The menu as text, the selected entry marked.
+    fn lines(&self, config: &GameConfig) -> Vec<String> {
+        let difficulty = config
+            .difficulty
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string());
+        let mut lines = vec!["DUCKDB PONG".to_string(), String::new()];
+        for (i, item) in MENU_ITEMS.into_iter().enumerate() {
+            let label = match item {
+                MenuItem::Start => "Start (vs AI)".to_string(),
+                MenuItem::LocalVs => "Local 2P".to_string(),
+                MenuItem::Difficulty => format!("Difficulty: < {difficulty} >"),
+                MenuItem::Quit => "Quit".to_string(),
+            };
+            let marker = if i == self.selected { '>' } else { ' ' };
+            lines.push(format!("{marker} {label}"));
+        }
+        lines.push(String::new());
+        lines.push("Up/Down: select  Left/Right: change  Enter: choose".to_string());
+        if let Some(problem) = &self.problem {
+            lines.push(problem.clone());
+        }
+        lines
+    }
+
+    /// Act on the key `code`, changing `config` as chosen. Returns the choice once
+    /// the menu is done.
+    fn press(&mut self, code: event::KeyCode, config: &mut GameConfig) -> Option<MenuChoice> {
+        let keys = &config.keys;
+        let item = MENU_ITEMS[self.selected];
+        let step = |difficulty: Difficulty, by: usize| {
+            let all = Difficulty::value_variants();
+            let i = all.iter().position(|&d| d == difficulty).unwrap_or(0);
+            all[(i + by) % all.len()]
+        };
+        match code {
+            event::KeyCode::Enter => match item {
+                MenuItem::Start => {
+                    config.mode = PlayMode::Solo;
+                    return Some(MenuChoice::Play);
+                }
+                MenuItem::LocalVs => {
+                    let local = GameConfig {
+                        mode: PlayMode::LocalVs,
+                        ..config.clone()
+                    };
+                    match local.validate() {
+                        Ok(()) => {
+                            *config = local;
+                            return Some(MenuChoice::Play);
+                        }
+                        Err(e) => self.problem = Some(format!("{e:#}")),
+                    }
                 }
+                MenuItem::Difficulty => config.difficulty = step(config.difficulty, 1),
+                MenuItem::Quit => return Some(MenuChoice::Quit),
+            },
+            event::KeyCode::Up => self.move_by(MENU_ITEMS.len() - 1),
+            event::KeyCode::Down => self.move_by(1),
+            event::KeyCode::Left | event::KeyCode::Right if item == MenuItem::Difficulty => {
+                let all = Difficulty::value_variants().len();
+                let by = if code == event::KeyCode::Left {
+                    all - 1
+                } else {
+                    1
+                };
+                config.difficulty = step(config.difficulty, by);
             }
+            code => match keys.action(code) {
+                Some(Action::Up) => self.move_by(MENU_ITEMS.len() - 1),
+                Some(Action::Down) => self.move_by(1),
+                Some(Action::Quit) => return Some(MenuChoice::Quit),
+                _ => {}
+            },
         }
+        None
+    }
+
+    /// Select the entry `by` further down, wrapping around.
+    fn move_by(&mut self, by: usize) {
+        self.selected = (self.selected + by) % MENU_ITEMS.len();
+        self.problem = None;
+    }
+}
 
+/// The start menu: pick a mode and difficulty with the keys, starting from what
+/// `config` says, and choose to play with them or quit.
+fn run_menu(config: &mut GameConfig, out: &mut impl Write) -> Result<MenuChoice> {
+    let mut menu = Menu::new(config);
+    loop {
+        let lines = menu.lines(config);
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines: Vec<_> = lines
+            .iter()
+            .map(|line| format!(" {line:width$} "))
+            .collect();
+        out.queue(terminal::Clear(terminal::ClearType::All))?;
+        draw_banner(out, config, &lines)?;
+        out.flush()?;
+        if let event::Event::Key(key_event) = event::read()?
+            && let Some(choice) = menu.press(key_event.code, config)
+        {
+            return Ok(choice);
+        }
+    }
+}
+
+/// `--attract`'s game over: show the winner for [`ATTRACT_RESTART_DELAY`] without
+/// asking anything. Returns `false` if quit was pressed meanwhile.
+fn attract_game_over(out: &mut impl Write, config: &GameConfig, winner: char) -> Result<bool> {
+    let lines = [
+        format!(" PLAYER {winner} WINS "),
+        " next match starting... ".to_string(),
+    ];
+    hold_banner(out, config, &lines, ATTRACT_RESTART_DELAY)
+}
+
+/// Show `lines` over the field for `duration`. Returns `false` if quit was pressed
+/// meanwhile.
+fn hold_banner(
+    out: &mut impl Write,
+    config: &GameConfig,
+    lines: &[String],
+    duration: Duration,
+) -> Result<bool> {
+    draw_banner(out, config, lines)?;
+
+    let deadline = Instant::now() + duration;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !event::poll(remaining)? {
+            return Ok(true);
+        }
+        if let event::Event::Key(key_event) = event::read()?
+            && config.keys.action(key_event.code) == Some(Action::Quit)
+        {
+            return Ok(false);
+        }
+    }
+}
+
+/// Show `states` again, [`INSTANT_REPLAY_SLOWDOWN`] times slower than they were
+/// played, without running the tick query, then put the live `state` row back as it
+/// was. Its key goes back to the match early; returns false if the player quit
+/// during it instead.
+fn instant_replay<'a>(
+    game: &Game,
+    args: &Args,
+    states: impl IntoIterator<Item = &'a State>,
+    frame: &mut FrameBuffer,
+    out: &mut impl Write,
+) -> Result<bool> {
+    let (conn, config) = (game.connection(), game.config());
+    let keys = &config.keys;
+    let frame_dt = SIM_DT * INSTANT_REPLAY_SLOWDOWN;
+    let start = Instant::now();
+    let mut quit = false;
+    record::hold_live(conn)?;
+    'replay: for state in states {
         let frame_start = Instant::now();
-        conn.execute(TICK_SQL, [])?;
-        let mut stmt = conn.prepare(RENDER_SQL)?;
-        let mut rows = stmt.query([])?;
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                event::Event::Key(key_event) => match keys.action(key_event.code) {
+                    Some(Action::Quit) => {
+                        quit = true;
+                        break 'replay;
+                    }
+                    Some(Action::InstantReplay) if start.elapsed() >= INSTANT_REPLAY_GUARD => {
+                        break 'replay;
+                    }
+                    _ => {}
+                },
+                event::Event::Resize(..) => {
+                    out.queue(terminal::Clear(terminal::ClearType::All))?;
+                    frame.invalidate();
+                }
+                _ => {}
+            }
+        }
 
-        stdout
-            .queue(cursor::MoveTo(0, 0))?
-            .queue(terminal::Clear(terminal::ClearType::FromCursorDown))?;
-        while let Some(row) = rows.next()? {
-            let line = row.get::<&str, String>("line")?;
-            stdout
-                .queue(cursor::MoveToNextLine(1))?
-                .queue(style::Print(line))?;
-        }
-
-        let frame_time = frame_start.elapsed();
-        let sleep_for = frame_dt.checked_sub(frame_time).unwrap_or(Duration::ZERO);
-        let actual_fps = (1.0
-            .div_checked((frame_time + sleep_for).as_secs_f64())
-            .unwrap_or(0.0)) as i32;
-
-        stdout
-            .queue(cursor::MoveToNextLine(1))?
-            .queue(style::Print("Press ESC to exit, FPS: "))?
+        record::show(conn, state)?;
+        frame.draw(&game.render(args.render)?, out)?;
+        out.queue(cursor::MoveTo(0, 0))?
+            .queue(style::Print(score_header(
+                config.width,
+                state.score_a,
+                state.score_b,
+            )))?
+            .queue(cursor::MoveTo(0, config.status_row()))?
             .queue(style::PrintStyledContent(
-                style(actual_fps).with(style::Color::Yellow),
+                style("INSTANT REPLAY").with(style::Color::Cyan),
             ))?
+            .queue(style::Print(format!(
+                " - press {} to go back to the match, {} to exit",
+                keys.label(Action::InstantReplay),
+                keys.label(Action::Quit)
+            )))?
+            .queue(terminal::Clear(terminal::ClearType::UntilNewLine))?
             .flush()?;
-        sleep(sleep_for);
+        sleep(frame_dt.saturating_sub(frame_start.elapsed()));
+    }
+    record::resume_live(conn)?;
+    Ok(!quit)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut config = args.game_config()?;
+    if config.hires && args.render == RenderMode::Full {
+        bail!("--render full has no hi-res version, use --render cells with --hires");
+    }
+    if config.trail > 0 && args.render == RenderMode::Full {
+        bail!("--render full draws no ball trail, use --render cells with --trail");
+    }
+
+    if args.print_config {
+        // A loaded game plays on with the settings it was saved with
+        let config = match args.load {
+            Some(_) => open_game(&args, config)?.config().clone(),
+            None => config,
+        };
+        println!("{}", config.to_json()?);
+        return Ok(());
+    }
+    if args.self_test {
+        return run_self_test(&config);
+    }
+    if args.shows_menu() {
+        let terminal_guard = TerminalGuard::enter()?;
+        let mut stdout = io::BufWriter::new(io::stdout());
+        let choice = run_menu(&mut config, &mut stdout);
+        stdout.flush()?;
+        drop(terminal_guard);
+        if choice? == MenuChoice::Quit {
+            return Ok(());
+        }
+    }
+    let mut game = open_game(&args, config)?;
+    if args.heatmap.is_some() {
+        heatmap::create(game.connection())?;
+    }
+    if let Some(frames) = args.bench {
+        return run_bench(&mut game, frames);
+    }
+    if let Some(path) = &args.screenshot {
+        return run_screenshot(&mut game, &args, path);
+    }
+    if let (Some(path), Some(replay)) = (&args.export_cast, &args.replay) {
+        return run_export_cast(&game, &args, replay, path);
+    }
+    if args.stats {
+        let history = history::load(game.connection(), &args.history_path())?;
+        print!("{}", history.summary());
+        return Ok(());
+    }
+    // --plain always plays plain, and clap keeps it from the runs that can't; without
+    // it plain output only stands in for a missing terminal where it can
+    let terminal = io::stdout().is_terminal();
+    if !args.plain
+        && !terminal
+        && (args.net_role().is_some() || args.replay.is_some() || args.attract)
+    {
+        bail!("stdout is not a terminal: only local games play without one, as with --plain");
+    }
+    if args.plain || !terminal {
+        return match run_plain(&mut game, &args) {
+            // Whatever the output was piped into stopped reading, e.g. `head`
+            Err(error) if is_broken_pipe(&error) => Ok(()),
+            other => other,
+        };
     }
-    stdout.queue(cursor::Show)?.flush()?;
-    terminal::disable_raw_mode()?;
 
+    let mut host_link = None;
+    let mut client_link = None;
+    match args.net_role() {
+        Some(NetRole::Host(port)) => {
+            // Frames only carry the primary ball
+            if game.config().balls > 1 {
+                bail!("network games are played with a single ball");
+            }
+            // Nor do they carry paddle sizes or pickups
+            if game.config().powerups {
+                bail!("network games are played without power-ups");
+            }
+            host_link = Some(net::host(port, game.config().settings())?);
+        }
+        Some(NetRole::Client(addr)) => {
+            // Draw the host's game: same field, state overwritten by every frame
+            let (link, settings) = net::connect(&addr)?;
+            game.adopt(&settings)?;
+            client_link = Some(link);
+        }
+        None => {}
+    }
+
+    // Declared before the buffered writer, so on a panic whatever is still buffered
+    // is written out before the terminal is restored
+    let terminal_guard = TerminalGuard::enter()?;
+    let mut stdout = io::BufWriter::new(io::stdout());
+
+    let mut stats = MatchStats::new();
+    let mut warnings = Vec::new();
+    let mut replayed = None;
+    let fits = if args.no_size_check {
+        Ok(true)
+    } else {
+        wait_for_size(game.config(), &mut stdout)
+    };
+    let result = match fits {
+        Ok(true) => match (client_link, args.replay.as_deref()) {
+            (Some(link), _) => run_client(
+                &game,
+                link,
+                args.max_frames,
+                args.debug_vectors,
+                &mut stdout,
+            ),
+            (None, Some(path)) => run_replay(
+                &game,
+                path,
+                args.replay_speed,
+                args.max_frames,
+                args.power_save,
+                args.debug_vectors,
+                &mut stdout,
+            )
+            .map(|last| replayed = last),
+            (None, None) => {
+                match play_intro(
+                    &game,
+                    &args,
+                    host_link.is_some(),
+                    &mut warnings,
+                    &mut stdout,
+                ) {
+                    Ok(true) => run_game(
+                        &mut game,
+                        &args,
+                        host_link,
+                        &mut stats,
+                        &mut warnings,
+                        &mut stdout,
+                    ),
+                    other => other.map(|_| ()),
+                }
+            }
+        },
+        other => other.map(|_| ()),
+    };
+    // Hand the terminal back before reporting any error from the loop
+    let result = result.and(stdout.flush().map_err(anyhow::Error::from));
+    drop(terminal_guard);
+    for warning in &warnings {
+        eprintln!("warning: {warning}");
+    }
+    if let Err(error) = &result
+        && is_broken_pipe(error)
+    {
+        // The terminal went away, e.g. its pane was closed: nobody is left to show the
+        // summary to, but a saved game still gets written out
+        if args.db_path().is_some() {
+            game.connection().execute_batch("CHECKPOINT")?;
+        }
+        bail!("the terminal closed while the game was drawing to it");
+    }
+    if stats.ticks > 0 {
+        // Below the last frame, which stays on screen
+        println!();
+        stats.print_summary();
+    }
+    result?;
+    if args.max_frames.is_some() {
+        // A replay ends on the score of the last frame it drew
+        let state = match replayed {
+            Some(state) => state,
+            None => game.state()?,
+        };
+        println!("\nFinal score: A {} - {} B", state.score_a, state.score_b);
+    }
+    write_heatmap(&game, &args, &mut io::stdout())?;
+
+    if args.db_path().is_some() {
+        game.connection().execute_batch("CHECKPOINT")?;
+    }
+    Ok(())
+}
+
+/// Idle between redraws while nothing moves: for [`PAUSE_SLEEP`], or with
+/// `power_save` until input arrives, at most [`POWER_SAVE_WAIT`]. The input is left
+/// for the next frame to read, so no key press is lost either way.
+fn idle_wait(power_save: bool) -> Result<()> {
+    if power_save {
+        event::poll(POWER_SAVE_WAIT)?;
+    } else {
+        sleep(PAUSE_SLEEP);
+    }
     Ok(())
 }
+
+/// Play back a recording one row per frame at [`SIM_HZ`](duckdb_pong::SIM_HZ) times
+/// `--replay-speed`, without running the tick query. While paused, Left and Right step
+/// through the frames one at a time. Returns the last frame drawn, `None` for an empty
+/// recording.
+fn run_replay(
+    game: &Game,
+    path: &Path,
+    speed: f64,
+    max_frames: Option<u64>,
+    power_save: bool,
+    debug_vectors: bool,
+    out: &mut impl Write,
+) -> Result<Option<State>> {
+    let (conn, config) = (game.connection(), game.config());
+    let keys = &config.keys;
+    let frames = record::load_replay(conn, path)?;
+    if frames.is_empty() {
+        return Ok(None);
+    }
+    let frame_dt = SIM_DT.div_f64(speed);
+
+    let mut frame = FrameBuffer::new(config.color, config.crt, config.colors, config.glyphs());
+    let mut cap = FrameCap::new(max_frames);
+    let mut paused = false;
+    let mut current: usize = 0;
+    loop {
+        let frame_start = Instant::now();
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                event::Event::Key(key_event) => match key_event.code {
+                    code if keys.action(code) == Some(Action::Quit) => {
+                        return Ok(Some(frames[current]));
+                    }
+                    code if keys.action(code) == Some(Action::Pause) => paused = !paused,
+                    event::KeyCode::Left if paused => current = current.saturating_sub(1),
+                    event::KeyCode::Right if paused => {
+                        current = (current + 1).min(frames.len() - 1)
+                    }
+                    _ => {}
+                },
+                event::Event::Resize(..) => {
+                    out.queue(terminal::Clear(terminal::ClearType::All))?;
+                    frame.invalidate();
+                }
+                _ => {}
+            }
+        }
+
+        let state = &frames[current];
+        record::show(conn, state)?;
+        let field = render::query_field(conn, config.hires, config.width, config.height)?;
+        frame.render_diff(&field, out)?;
+        out.queue(cursor::MoveTo(0, 0))?
+            .queue(style::Print(score_header(
+                config.width,
+                state.score_a,
+                state.score_b,
+            )))?
+            .queue(cursor::MoveTo(0, config.status_row()))?;
+        let progress = format!(" frame {}/{}", current + 1, frames.len());
+        if paused {
+            out.queue(style::PrintStyledContent(
+                style("PAUSED").with(style::Color::Yellow),
+            ))?
+            .queue(style::Print(format!(
+                "{progress} - LEFT/RIGHT to step, {} to resume, {} to exit",
+                keys.label(Action::Pause),
+                keys.label(Action::Quit)
+            )))?;
+        } else {
+            out.queue(style::PrintStyledContent(
+                style("REPLAY").with(style::Color::Yellow),
+            ))?
+            .queue(style::Print(format!(
+                "{progress} - {} to pause, {} to exit",
+                keys.label(Action::Pause),
+                keys.label(Action::Quit)
+            )))?;
+        }
+        if debug_vectors {
+            out.queue(style::Print(format!(" | {}", ball_vector(state))))?;
+        }
+        out.queue(terminal::Clear(terminal::ClearType::UntilNewLine))?
+            .flush()?;
+        if cap.frame_drawn() {
+            return Ok(Some(*state));
+        }
+
+        if paused {
+            idle_wait(power_save)?;
+            continue;
+        }
+        // Hold the last frame on screen until the viewer leaves or steps back
+        if current + 1 == frames.len() {
+            paused = true;
+        } else {
+            current += 1;
+        }
+        sleep(frame_dt.saturating_sub(frame_start.elapsed()));
+    }
+}
+
+/// The client side of a network game: draw every frame the host sends and forward
+/// the player's keys as Player A's input. The tick query never runs here.
+fn run_client(
+    game: &Game,
+    mut link: net::Link<HostMessage>,
+    max_frames: Option<u64>,
+    debug_vectors: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    let (conn, config) = (game.connection(), game.config());
+    let mut frame = FrameBuffer::new(config.color, config.crt, config.colors, config.glyphs());
+    let mut cap = FrameCap::new(max_frames);
+    let mut connection_lost = false;
+    let mut shown = None;
+    loop {
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                event::Event::Key(key_event) => match key_event.code {
+                    code if config.keys.action(code) == Some(Action::Quit) => return Ok(()),
+                    code => {
+                        let direction = PlayerInput::from_key(code, &config.keys).direction();
+                        if let Some(direction) = direction
+                            && !connection_lost
+                            && link.send(&ClientMessage::Input { direction }).is_err()
+                        {
+                            connection_lost = true;
+                        }
+                    }
+                },
+                event::Event::Resize(..) => {
+                    out.queue(terminal::Clear(terminal::ClearType::All))?;
+                    frame.invalidate();
+                }
+                _ => {}
+            }
+        }
+
+        // Only the newest frame is worth drawing
+        let mut latest = None;
+        while !connection_lost {
+            match link.try_recv() {
+                Ok(Some(HostMessage::Frame { state })) => latest = Some(state),
+                Ok(Some(HostMessage::Hello { .. })) => {}
+                Ok(None) => break,
+                Err(ConnectionLost) => connection_lost = true,
+            }
+        }
+        if let Some(state) = latest {
+            shown = Some(state);
+            record::show(conn, &state)?;
+            let field = render::query_field(conn, config.hires, config.width, config.height)?;
+            frame.render_diff(&field, out)?;
+            out.queue(cursor::MoveTo(0, 0))?
+                .queue(style::Print(score_header(
+                    config.width,
+                    state.score_a,
+                    state.score_b,
+                )))?;
+        }
+
+        out.queue(cursor::MoveTo(0, config.status_row()))?;
+        if connection_lost {
+            out.queue(style::PrintStyledContent(
+                style("CONNECTION LOST").with(style::Color::Red),
+            ))?
+            .queue(style::Print(format!(
+                " - press {} to exit",
+                config.keys.label(Action::Quit)
+            )))?;
+        } else {
+            out.queue(style::Print(format!(
+                "Online as Player A - {} and {} to move, {} to exit",
+                config.keys.label(Action::Up),
+                config.keys.label(Action::Down),
+                config.keys.label(Action::Quit)
+            )))?;
+        }
+        if let Some(state) = shown.filter(|_| debug_vectors) {
+            out.queue(style::Print(format!(" | {}", ball_vector(&state))))?;
+        }
+        out.queue(terminal::Clear(terminal::ClearType::UntilNewLine))?
+            .flush()?;
+        // Only frames from the host count, not redraws of the status line
+        if latest.is_some() && cap.frame_drawn() {
+            return Ok(());
+        }
+        sleep(SIM_DT);
+    }
+}
+
+/// Fast-forwarding to the next point: while on, every frame runs
+/// [`FAST_FORWARD_TICKS`] ticks without waiting for game time, until one of them
+/// scores.
+#[derive(Debug, Default)]
+struct FastForward {
+    on: bool,
+}
+
+impl FastForward {
+    /// The most ticks a frame runs.
+    fn max_ticks(&self) -> u32 {
+        if self.on {
+            FAST_FORWARD_TICKS
+        } else {
+            MAX_TICKS_PER_FRAME
+        }
+    }
+
+    /// Whether another tick is due with `sim_time` of game time not yet simulated.
+    fn tick_due(&self, sim_time: Duration) -> bool {
+        self.on || sim_time >= SIM_DT
+    }
+
+    /// Notes the event of the tick just run. A point switches fast-forwarding off, and
+    /// returns true so the game goes on at normal speed from it.
+    fn stop_at(&mut self, event: Option<SoundEvent>) -> bool {
+        let stop = self.on && event == Some(SoundEvent::Score);
+        if stop {
+            self.on = false;
+        }
+        stop
+    }
+}
+
+/// The interactive game loop: ticks the simulation at [`SIM_HZ`](duckdb_pong::SIM_HZ)
+/// and redraws at the frame rate until the player quits. Every tick is counted into
+/// `stats`.
+fn run_game(
+    game: &mut Game,
+    args: &Args,
+    mut remote: Option<net::Link<ClientMessage>>,
+    stats: &mut MatchStats,
+    warnings: &mut Vec<String>,
+    out: &mut impl Write,
+) -> Result<()> {
+    let config = &game.config().clone();
+    let keys = &config.keys;
+
+    let mut recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+    let mut log = args.log.as_deref().map(TelemetryLog::create).transpose()?;
+    let mut script = args
+        .script
+        .as_deref()
+        .map(|path| Script::load(game.connection(), path))
+        .transpose()?;
+    // A demo or a script plays itself, launching its own serves
+    let watching = args.attract || script.is_some();
+    // Every match is kept, but only the ones against the AI count towards the record
+    let mut history_path = args.keeps_history().then(|| args.history_path());
+    let opponent = match (&remote, config.mode) {
+        (Some(_), _) => Opponent::Network,
+        (None, PlayMode::LocalVs) => Opponent::Local,
+        (None, PlayMode::Solo) => Opponent::Ai,
+    };
+    let mut match_start = Instant::now();
+    let status = args
+        .status_socket
+        .as_deref()
+        .map(StatusServer::start)
+        .transpose()?;
+    let mut frame = FrameBuffer::new(config.color, config.crt, config.colors, config.glyphs());
+    let mut cap = FrameCap::new(args.max_frames);
+    let mut trail = Trail::new(config.trail as usize);
+    let mut sound = Sound::new(config.sound);
+    let mut last_state = game.state()?;
+    let mut paused = false;
+    // Paused by switching away from the terminal, so switching back resumes; pausing
+    // or resuming by key in between leaves it to the player
+    let mut focus_paused = false;
+    let mut slowmo = false;
+    // Ticking without sleeping until the next point; not with a remote player, whose
+    // input would have no time to arrive
+    let mut fast_forward = FastForward::default();
+    // The last ticks, shown again in slow motion on the instant replay key; again not
+    // with a remote player, who would see the match stall meanwhile
+    let mut rewind = RewindBuffer::new(REWIND_TICKS);
+    // In training, how often the player gets the serve back
+    let mut returns = ServeReturns::new();
+    // In local versus play a second player steers Player A on keys of their own, and
+    // a paddle without a key pressed holds or goes back to the AI
+    let local_vs = config.mode == PlayMode::LocalVs;
+    let idle = if local_vs {
+        PlayerInput::idle(config.idle_paddle)
+    } else {
+        PlayerInput::None
+    };
+    // Input is kept until a simulation tick consumes it, since not every frame ticks
+    let (mut input, mut input_a) = (idle, idle);
+    let (mut last_key, mut last_key_a) = (Instant::now(), Instant::now());
+    let mut sim_time = Duration::ZERO;
+    let mut last_frame = Instant::now();
+    let mut too_small = false;
+    let mut pacer = FramePacer::new(config.fps);
+    let mut frame_times = RollingAverage::new(FPS_SAMPLES);
+    let mut timings = FrameTimings::new(FPS_SAMPLES);
+    // The remote player's last key press, held like local input until a tick uses it
+    let mut remote_input = 0;
+    let mut connection_lost = false;
+    'frames: loop {
+        // Handle every event that came in since the last frame, so quick taps aren't
+        // lost. Movement keeps the latest intent; a toggle flips once per frame however
+        // often its key shows up, so a key repeat can't switch it off again right away.
+        let (mut toggle_pause, mut toggle_slowmo, mut serve) = (false, false, watching);
+        let (mut toggle_fast_forward, mut replay) = (false, false);
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                event::Event::Key(key_event) => match key_event.code {
+                    code if keys.action(code) == Some(Action::Quit) => break 'frames,
+                    code if keys.action(code) == Some(Action::FastForward) && remote.is_none() => {
+                        toggle_fast_forward = true
+                    }
+                    // Quitting and skipping ahead are all one playing itself listens to
+                    _ if watching => {}
+                    code if keys.action(code) == Some(Action::InstantReplay)
+                        && remote.is_none() =>
+                    {
+                        replay = true
+                    }
+                    code if keys.action(code) == Some(Action::Pause) => toggle_pause = true,
+                    code if keys.action(code) == Some(Action::Slowmo) => toggle_slowmo = true,
+                    code if keys.triggers(Action::Serve, code) => serve = true,
+                    // Steps on every press and key repeat, unlike the toggles
+                    code if keys.action(code) == Some(Action::FpsUp) => {
+                        pacer.set_fps(timing::step_fps(pacer.fps(), true))
+                    }
+                    code if keys.action(code) == Some(Action::FpsDown) => {
+                        pacer.set_fps(timing::step_fps(pacer.fps(), false))
+                    }
+                    // The second player's keys come first, so keys the two share move
+                    // the left paddle
+                    code if local_vs && keys.left_action(code).is_some() => {
+                        input_a = PlayerInput::from_left_key(code, keys);
+                        last_key_a = Instant::now();
+                    }
+                    code => match PlayerInput::from_key(code, keys) {
+                        PlayerInput::None => {}
+                        pressed => {
+                            input = pressed;
+                            last_key = Instant::now();
+                        }
+                    },
+                },
+                event::Event::Mouse(mouse) if !watching => {
+                    if let event::MouseEventKind::Moved | event::MouseEventKind::Drag(_) =
+                        mouse.kind
+                    {
+                        match PlayerInput::from_mouse(mouse.column, mouse.row, config) {
+                            PlayerInput::None => {}
+                            target => input = target,
+                        }
+                    }
+                }
+                event::Event::Resize(cols, rows) => {
+                    // Leftovers from the old layout would never be overwritten otherwise
+                    out.queue(terminal::Clear(terminal::ClearType::All))?;
+                    frame.invalidate();
+                    too_small = !args.no_size_check && !config.fits(cols, rows);
+                }
+                event::Event::FocusLost if !watching && !paused => {
+                    paused = true;
+                    focus_paused = true;
+                }
+                event::Event::FocusGained => {
+                    if focus_paused && !connection_lost {
+                        paused = false;
+                    }
+                    focus_paused = false;
+                    // The terminal may have been redrawn or resized while away
+                    out.queue(terminal::Clear(terminal::ClearType::All))?;
+                    frame.invalidate();
+                }
+                _ => {}
+            }
+        }
+        if toggle_pause && !connection_lost {
+            paused = !paused;
+            focus_paused = false;
+        }
+        if toggle_slowmo {
+            slowmo = !slowmo;
+        }
+        if toggle_fast_forward {
+            fast_forward.on = !fast_forward.on;
+        }
+        if serve && !paused {
+            game.serve()?;
+        }
+        if replay && !paused && !rewind.is_empty() {
+            if !instant_replay(game, args, rewind.states(), &mut frame, out)? {
+                break 'frames;
+            }
+            // The live match was held still, so it picks up from where it was
+            sim_time = Duration::ZERO;
+            last_frame = Instant::now();
+            pacer.reset();
+        }
+
+        if let Some(link) = remote.as_ref().filter(|_| !connection_lost) {
+            loop {
+                match link.try_recv() {
+                    Ok(Some(ClientMessage::Input { direction })) => {
+                        remote_input = direction.signum()
+                    }
+                    Ok(None) => break,
+                    Err(ConnectionLost) => {
+                        connection_lost = true;
+                        paused = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Hold the game until the terminal is big enough again
+        if too_small {
+            let (min_cols, min_rows) = config.required_size();
+            out.queue(cursor::MoveTo(0, 0))?
+                .queue(style::Print(format!(
+                    "Terminal too small — resize to at least {min_cols}x{min_rows}"
+                )))?
+                .queue(terminal::Clear(terminal::ClearType::UntilNewLine))?
+                .flush()?;
+            last_frame = Instant::now();
+            idle_wait(args.power_save)?;
+            pacer.reset();
+            continue;
+        }
+
+        let frame_start = Instant::now();
+        let mut point_scored = false;
+        if !paused {
+            // Slow motion only slows how fast game time passes, so switching it off
+            // carries on from where the game is instead of catching up
+            let elapsed = frame_start - last_frame;
+            sim_time += if slowmo {
+                elapsed / SLOWMO_FACTOR
+            } else {
+                elapsed
+            };
+            let mut ticks = 0;
+            let max_ticks = fast_forward.max_ticks();
+            while fast_forward.tick_due(sim_time) && ticks < max_ticks {
+                let a = if local_vs {
+                    input_a.direction()
+                } else {
+                    remote.is_some().then_some(remote_input)
+                };
+                match script.as_mut() {
+                    Some(script) => match script.step(game.connection(), last_state.tick)? {
+                        Some(inputs) => game.tick_with_rolls(inputs, Script::ROLLS)?,
+                        None => break 'frames,
+                    },
+                    None => game.tick(Inputs { a, b: input })?,
+                }
+                if args.heatmap.is_some() {
+                    heatmap::record(game.connection())?;
+                }
+                remote_input = 0;
+                let state = game.state()?;
+                stats.record(&state);
+                if let Some(lives) = game.lives()? {
+                    returns.record(&state, lives);
+                }
+                let event = SoundEvent::between(&last_state, &state, config.width.into());
+                if fast_forward.stop_at(event) {
+                    sim_time = Duration::ZERO;
+                }
+                // Skipped hits would only ring as a buzz
+                if let Some(event) = event.filter(|_| !fast_forward.on) {
+                    sound.play(event, Instant::now(), out)?;
+                }
+                if args.replay_on_score && remote.is_none() && event == Some(SoundEvent::Score) {
+                    point_scored = true;
+                }
+                if let Some(log) = log.as_mut() {
+                    log.log(&state, game.ai_targets()?, event)?;
+                }
+                last_state = state;
+                rewind.push(state);
+                trail.push(state.ball_x, state.ball_y);
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record(&state)?;
+                }
+                // The paddle keeps following the mouse until it reaches it
+                let key_held =
+                    |last: Instant| config.momentum && last.elapsed() < MOMENTUM_KEY_HOLD;
+                if !matches!(input, PlayerInput::Toward(_)) && !key_held(last_key) {
+                    input = idle;
+                }
+                if !key_held(last_key_a) {
+                    input_a = idle;
+                }
+                sim_time = sim_time.saturating_sub(SIM_DT);
+                ticks += 1;
+                // The replay ends on the point, not on the serve after it
+                if point_scored {
+                    break;
+                }
+            }
+            // Too far behind to catch up: drop the backlog instead of fast-forwarding
+            if sim_time >= SIM_DT {
+                sim_time = Duration::ZERO;
+            }
+        }
+        frame_times.push(frame_start - last_frame);
+        last_frame = frame_start;
+        // Show the point again before the serve countdown goes on, match point included
+        if point_scored {
+            if !instant_replay(game, args, rewind.last(SCORE_REPLAY_TICKS), &mut frame, out)? {
+                break 'frames;
+            }
+            sim_time = Duration::ZERO;
+            last_frame = Instant::now();
+            pacer.reset();
+        }
+
+        let render_start = Instant::now();
+        timings.tick.push(render_start - frame_start);
+        let mut field = game.render(args.render)?;
+        if let Rendered::Cells(lines) = &mut field {
+            trail.paint(lines, config.pixels_per_row());
+        }
+        let state = game.state()?;
+        if let Some(status) = &status {
+            status.update(state);
+        }
+
+        let io_start = Instant::now();
+        timings.render.push(io_start - render_start);
+        frame.draw(&field, out)?;
+        if args.attract {
+            // Drawn over the field every frame, in the top right corner clear of paddle B
+            let demo = " DEMO ";
+            out.queue(cursor::MoveTo(
+                config.width - demo.len() as u16 - 2 - config.paddle_width,
+                FIELD_TOP + 1,
+            ))?
+            .queue(style::PrintStyledContent(demo.reverse()))?;
+        }
+        out.queue(cursor::MoveTo(0, 0))?
+            .queue(style::Print(score_header(
+                config.width,
+                state.score_a,
+                state.score_b,
+            )))?;
+
+        out.queue(cursor::MoveTo(0, config.status_row()))?;
+        if connection_lost {
+            out.queue(style::PrintStyledContent(
+                style("CONNECTION LOST").with(style::Color::Red),
+            ))?
+            .queue(style::Print(format!(
+                " - press {} to exit",
+                config.keys.label(Action::Quit)
+            )))?;
+        } else if paused {
+            out.queue(style::PrintStyledContent(
+                style("PAUSED").with(style::Color::Yellow),
+            ))?
+            .queue(style::Print(format!(
+                " - press {} to resume, {} to exit",
+                keys.label(Action::Pause),
+                keys.label(Action::Quit)
+            )))?;
+        } else if args.attract {
+            out.queue(style::PrintStyledContent(
+                style("DEMO").with(style::Color::Yellow),
+            ))?
+            .queue(style::Print(format!(
+                " - press {} to exit",
+                config.keys.label(Action::Quit)
+            )))?;
+        } else {
+            out.queue(style::Print(format!(
+                "Press {} to exit, {} to pause, FPS: ",
+                keys.label(Action::Quit),
+                keys.label(Action::Pause)
+            )))?
+            .queue(style::PrintStyledContent(
+                style(frame_times.per_second().round()).with(style::Color::Yellow),
+            ))?;
+            // The cap the FPS keys move, next to the rate actually reached
+            if pacer.fps() > 0 {
+                out.queue(style::Print(format!(
+                    "/{} ({}/{})",
+                    pacer.fps(),
+                    keys.label(Action::FpsDown),
+                    keys.label(Action::FpsUp)
+                )))?;
+            }
+        }
+        if slowmo {
+            out.queue(style::Print(" | "))?
+                .queue(style::PrintStyledContent(
+                    style("SLOW").with(style::Color::Cyan),
+                ))?;
+        }
+        if fast_forward.on {
+            out.queue(style::Print(" | "))?
+                .queue(style::PrintStyledContent(
+                    style(format!(
+                        "FAST FORWARD ({} to stop)",
+                        keys.label(Action::FastForward)
+                    ))
+                    .with(style::Color::Cyan),
+                ))?;
+        }
+        if !paused && game.awaiting_serve()? {
+            out.queue(style::Print(" | "))?
+                .queue(style::PrintStyledContent(
+                    style(format!("{} TO SERVE", keys.label(Action::Serve)))
+                        .with(style::Color::Green),
+                ))?;
+        }
+        if let Some(lives) = game.lives()? {
+            out.queue(style::Print(" | "))?
+                .queue(style::PrintStyledContent(
+                    style(format!("LIVES {lives}")).with(style::Color::Red),
+                ))?;
+            if let Some(rate) = returns.rate() {
+                out.queue(style::Print(format!(" | RETURN RATE {rate}%")))?;
+            }
+        }
+        if !args.attract {
+            let rally = format!("RALLY {}", stats.rally_hits);
+            out.queue(style::Print(" | "))?;
+            if stats.new_best_rally() {
+                // Flashes twice a second while the rally is beating the record
+                let best = style(format!("{rally} NEW BEST")).with(style::Color::Yellow);
+                let flash =
+                    (frame_start.duration_since(match_start).as_millis() / 250).is_multiple_of(2);
+                out.queue(style::PrintStyledContent(if flash {
+                    best.reverse()
+                } else {
+                    best
+                }))?;
+            } else {
+                out.queue(style::Print(format!(
+                    "{rally} (BEST {})",
+                    stats.best_rally_hits
+                )))?;
+            }
+        }
+        if let Some(deuce) = Deuce::from_scores(state.score_a, state.score_b, config.target_score) {
+            out.queue(style::Print(" | "))?
+                .queue(style::PrintStyledContent(
+                    style(deuce).with(style::Color::Magenta),
+                ))?;
+        }
+        if args.debug_vectors {
+            out.queue(style::Print(format!(" | {}", ball_vector(&state))))?;
+        }
+        if args.debug_timing {
+            out.queue(style::Print(format!(" | {}", timings.summary())))?;
+        }
+        out.queue(terminal::Clear(terminal::ClearType::UntilNewLine))?
+            .flush()?;
+        timings.io.push(io_start.elapsed());
+
+        if let Some(link) = remote.as_mut().filter(|_| !connection_lost)
+            && link.send(&HostMessage::Frame { state }).is_err()
+        {
+            connection_lost = true;
+            paused = true;
+        }
+        if cap.frame_drawn() {
+            break;
+        }
+
+        let finished = if game.lives()? == Some(0) {
+            let again = game_over(out, config, "OUT OF LIVES")?;
+            if again {
+                game.restart()?;
+                rewind = RewindBuffer::new(REWIND_TICKS);
+            }
+            Some(again)
+        } else if let Some(win) = game.winner()? {
+            Some(match win {
+                Win::Set(winner) => {
+                    let (set_a, set_b) = game.next_set()?;
+                    let lines = [
+                        format!(" SET TO {winner} "),
+                        format!(" sets {set_a} - {set_b} "),
+                    ];
+                    hold_banner(out, config, &lines, SET_BANNER_TIME)?
+                }
+                Win::Match(winner) => {
+                    if let Some(path) = &history_path {
+                        // The history always has the player as B, whichever side they took
+                        let mirrored = config.human_side == Side::Left;
+                        let result = MatchResult {
+                            winner: match (mirrored, winner) {
+                                (true, 'A') => 'B',
+                                (true, _) => 'A',
+                                (false, winner) => winner,
+                            },
+                            score_a: if mirrored {
+                                state.score_b
+                            } else {
+                                state.score_a
+                            },
+                            score_b: if mirrored {
+                                state.score_a
+                            } else {
+                                state.score_b
+                            },
+                            duration: match_start.elapsed(),
+                            opponent,
+                        };
+                        // Not worth ending the game over: say so once it is over, and
+                        // don't try again
+                        if let Err(error) = history::append(game.connection(), path, &result) {
+                            warnings.push(format!("the match history was not saved: {error:#}"));
+                            history_path = None;
+                        }
+                    }
+                    let again = if args.attract {
+                        attract_game_over(out, config, winner)?
+                    } else {
+                        game_over(out, config, &format!("PLAYER {winner} WINS"))?
+                    };
+                    if again {
+                        game.restart()?;
+                        match_start = Instant::now();
+                        rewind = RewindBuffer::new(REWIND_TICKS);
+                    }
+                    again
+                }
+            })
+        } else {
+            None
+        };
+        if let Some(go_on) = finished {
+            if !go_on {
+                break;
+            }
+            frame.invalidate();
+            sim_time = Duration::ZERO;
+            last_frame = Instant::now();
+            pacer.reset();
+            continue;
+        }
+        let sleep_start = Instant::now();
+        if paused {
+            // While paused there is no frame budget to keep, just avoid spinning the CPU
+            idle_wait(args.power_save)?;
+            pacer.reset();
+        } else if fast_forward.on {
+            // Straight on to the next batch of ticks
+            pacer.reset();
+        } else {
+            pacer.wait();
+        }
+        timings.sleep.push(sleep_start.elapsed());
+    }
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(log) = log {
+        log.finish()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn saved_game_loads_with_same_scores_and_positions() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("duckdb-pong-{}.db", std::process::id()));
+        let cli = |flag: &str| {
+            Args::parse_from([
+                OsStr::new("duckdb-pong"),
+                OsStr::new("--width"),
+                OsStr::new("60"),
+                OsStr::new(flag),
+                path.as_os_str(),
+            ])
+        };
+
+        let saved = {
+            let args = cli("--save");
+            let mut game = open_game(&args, args.game_config()?)?;
+            for _ in 0..50 {
+                game.tick(Inputs::default())?;
+            }
+            let conn = game.connection();
+            conn.execute("UPDATE state SET score_a = 3, score_b = 7", [])?;
+            conn.execute_batch("CHECKPOINT")?;
+            game.state()?
+        };
+
+        // Loading ignores the default width in favor of the saved one
+        let args = Args::parse_from([
+            OsStr::new("duckdb-pong"),
+            OsStr::new("--load"),
+            path.as_os_str(),
+        ]);
+        let game = open_game(&args, args.game_config()?)?;
+        assert_eq!(game.state()?, saved);
+        assert_eq!(game.config().width, 60);
+
+        drop(game);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("db.wal"));
+        Ok(())
+    }
+
+    #[test]
+    fn screenshots_are_plain_text_and_repeat_with_the_same_seed() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let shot = |name: &str, flags: &[&str]| -> Result<String> {
+            let path = dir.join(format!("duckdb-pong-{}-{name}.txt", std::process::id()));
+            let args = Args::parse_from(
+                ["duckdb-pong", "--screenshot", path.to_str().unwrap()]
+                    .iter()
+                    .chain(flags),
+            );
+            let mut game = open_game(&args, args.game_config()?)?;
+            run_screenshot(&mut game, &args, &path)?;
+            let text = std::fs::read_to_string(&path)?;
+            let _ = std::fs::remove_file(&path);
+            Ok(text)
+        };
+
+        let flags = ["--seed", "0.3", "--max-frames", "30"];
+        let text = shot("a", &flags)?;
+        assert_eq!(text, shot("b", &flags)?);
+        assert!(!text.contains('\x1b'));
+        // The score header and 25 field lines
+        assert_eq!(text.lines().count(), 26);
+
+        // A recording of the same match shows the same frame; 30 ticks is too soon for
+        // a point, whose serve countdown a recording doesn't keep
+        let recording = dir.join(format!("duckdb-pong-{}-shot.csv", std::process::id()));
+        let args = Args::parse_from(["duckdb-pong", "--seed", "0.3"]);
+        let mut game = open_game(&args, args.game_config()?)?;
+        let mut recorder = Recorder::create(&recording)?;
+        for _ in 0..30 {
+            game.tick(Inputs::default())?;
+            recorder.record(&game.state()?)?;
+        }
+        recorder.finish()?;
+        let replayed = shot(
+            "c",
+            &[
+                "--replay",
+                recording.to_str().unwrap(),
+                "--max-frames",
+                "30",
+            ],
+        )?;
+        let _ = std::fs::remove_file(&recording);
+        assert_eq!(replayed, text);
+        Ok(())
+    }
+
+    #[test]
+    fn recordings_export_to_asciinema_casts() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let recording = dir.join(format!("duckdb-pong-{}-cast.csv", std::process::id()));
+        let cast = recording.with_extension("cast");
+        let args = Args::parse_from(["duckdb-pong", "--seed", "0.3"]);
+        let mut game = open_game(&args, args.game_config()?)?;
+        let mut recorder = Recorder::create(&recording)?;
+        for _ in 0..30 {
+            game.tick(Inputs::default())?;
+            recorder.record(&game.state()?)?;
+        }
+        recorder.finish()?;
+        let last_frame = frame_text(&game)?;
+
+        let args = Args::parse_from([
+            "duckdb-pong",
+            "--replay",
+            recording.to_str().unwrap(),
+            "--export-cast",
+            cast.to_str().unwrap(),
+            "--cast-title",
+            "Rally",
+            "--cast-idle-limit",
+            "2",
+            "--replay-speed",
+            "2",
+        ]);
+        let game = open_game(&args, args.game_config()?)?;
+        run_export_cast(&game, &args, &recording, &cast)?;
+        let text = std::fs::read_to_string(&cast)?;
+        let _ = std::fs::remove_file(&recording);
+        let _ = std::fs::remove_file(&cast);
+
+        let mut lines = text.lines();
+        let header: serde_json::Value = serde_json::from_str(lines.next().unwrap())?;
+        assert_eq!(
+            header,
+            serde_json::json!({
+                "version": 2, "width": 80, "height": 26, "title": "Rally", "idle_time_limit": 2.0
+            })
+        );
+        let events = lines
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<(f64, String, String)>, _>>()?;
+        assert_eq!(events.len(), 30);
+        assert_eq!(events[0].0, 0.0);
+        assert!(events[0].2.starts_with("\x1b[?25l\x1b[2J\x1b[H"));
+        // Twice the replay speed halves the time between frames
+        let (time, kind, output) = &events[29];
+        assert!((time - 29.0 / 120.0).abs() < 1e-6, "{time}");
+        assert_eq!(kind, "o");
+        assert_eq!(output.matches("\x1b[K").count(), last_frame.len());
+        for line in &last_frame {
+            assert!(output.contains(line.as_str()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn debug_vectors_show_the_ball_with_signed_speeds() {
+        let state = State {
+            tick: 7,
+            ax: 9,
+            bx: 9,
+            ball_x: 40,
+            ball_y: 12,
+            vx: 1,
+            vy: -2,
+            score_a: 0,
+            score_b: 0,
+        };
+        assert_eq!(ball_vector(&state), "BALL 40,12 V +1,-2");
+        let flat = State { vy: 0, ..state };
+        assert_eq!(ball_vector(&flat), "BALL 40,12 V +1,+0");
+    }
+
+    #[test]
+    fn debug_vectors_read_the_step_the_next_tick_takes() -> Result<()> {
+        let mut game = Game::open(GameConfig::default(), None, false, Some(0.5))?;
+        game.connection().execute(
+            "UPDATE state SET tick = 0, serve_timer = 0, awaiting_serve = false,
+                ball_x = 40, ball_y = 12, vx = 1, vy = -2",
+            [],
+        )?;
+        let inputs = Inputs {
+            a: Some(0),
+            b: PlayerInput::Hold,
+        };
+        let before = game.state()?;
+        assert_eq!(ball_vector(&before), "BALL 40,12 V +1,-2");
+        game.tick(inputs)?;
+        let after = game.state()?;
+        assert_eq!(
+            (after.ball_x - before.ball_x, after.ball_y - before.ball_y),
+            (before.vx, before.vy)
+        );
+        assert_eq!(ball_vector(&after), "BALL 41,10 V +1,-2");
+        Ok(())
+    }
+
+    #[test]
+    fn cast_frames_that_repeat_the_last_one_are_left_out() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("duckdb-pong-{}-still.cast", std::process::id()));
+        let mut cast = CastWriter::create(&path, &CastHeader::default())?;
+        let frame = |ball: &str| vec!["0    0".to_string(), ball.to_string()];
+        assert!(cast.frame(Duration::ZERO, &frame(" o"))?);
+        assert!(!cast.frame(Duration::from_secs(1), &frame(" o"))?);
+        assert!(cast.frame(Duration::from_secs(2), &frame("o"))?);
+        cast.finish()?;
+        let text = std::fs::read_to_string(&path)?;
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(text.lines().count(), 3);
+        assert!(
+            text.ends_with("[2.0,\"o\",\"\\u001b[H0    0\\u001b[K\\r\\no\\u001b[K\"]\n"),
+            "{text}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn start_menu_starts_from_the_flags_and_changes_the_settings() -> Result<()> {
+        use event::KeyCode;
+
+        let args = Args::parse_from(["duckdb-pong", "--mode", "local-vs", "--difficulty", "hard"]);
+        let mut config = args.game_config()?;
+        let mut menu = Menu::new(&config);
+        assert_eq!(MENU_ITEMS[menu.selected], MenuItem::LocalVs);
+        assert!(menu.lines(&config).contains(&"> Local 2P".to_string()));
+
+        // Left and Right step through the difficulties, wrapping around
+        assert_eq!(menu.press(KeyCode::Down, &mut config), None);
+        assert_eq!(menu.press(KeyCode::Right, &mut config), None);
+        assert_eq!(config.difficulty, Difficulty::Easy);
+        menu.press(KeyCode::Left, &mut config);
+        menu.press(KeyCode::Left, &mut config);
+        assert_eq!(config.difficulty, Difficulty::Normal);
+        assert!(
+            menu.lines(&config)
+                .contains(&"> Difficulty: < normal >".to_string())
+        );
+
+        menu.press(KeyCode::Up, &mut config);
+        menu.press(KeyCode::Up, &mut config);
+        assert_eq!(MENU_ITEMS[menu.selected], MenuItem::Start);
+        assert_eq!(
+            menu.press(KeyCode::Enter, &mut config),
+            Some(MenuChoice::Play)
+        );
+        assert_eq!(config.mode, PlayMode::Solo);
+        // Up wraps from the top entry to the bottom one
+        menu.press(KeyCode::Up, &mut config);
+        assert_eq!(
+            menu.press(KeyCode::Enter, &mut config),
+            Some(MenuChoice::Quit)
+        );
+        assert_eq!(
+            menu.press(KeyCode::Esc, &mut config),
+            Some(MenuChoice::Quit)
+        );
+
+        // Training has no second player, so Local 2P says why and stays in the menu
+        let args = Args::parse_from(["duckdb-pong", "--training"]);
+        let mut config = args.game_config()?;
+        let mut menu = Menu::new(&config);
+        menu.press(KeyCode::Down, &mut config);
+        assert_eq!(menu.press(KeyCode::Enter, &mut config), None);
+        assert_eq!(config.mode, PlayMode::Solo);
+        assert!(
+            menu.problem
+                .as_deref()
+                .is_some_and(|p| p.contains("can't train"))
+        );
+        Ok(())
+    }
+
+    /// Parse `flags` after `--config` pointing at a file holding `toml`.
+    fn config_from(toml: &str, flags: &[&str]) -> Result<GameConfig> {
+        // Tests run in parallel, so every call gets a file of its own
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "duckdb-pong-{}-{}.toml",
+            std::process::id(),
+            CALLS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, toml)?;
+        let args = Args::parse_from(
+            ["duckdb-pong", "--config", path.to_str().unwrap()]
+                .iter()
+                .chain(flags),
+        );
+        let config = args.game_config();
+        let _ = std::fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn command_line_flags_override_the_config_file() -> Result<()> {
+        let toml = "width = 100\nheight = 30\ndifficulty = \"hard\"\nfps = 60\n";
+        let config = config_from(toml, &["--width", "90", "--no-color"])?;
+        assert_eq!(config.width, 90);
+        assert_eq!(config.height, 30);
+        assert_eq!(config.difficulty, Difficulty::Hard);
+        assert_eq!(config.fps, 60);
+        assert!(!config.color);
+        assert_eq!(config.target_score, GameConfig::default().target_score);
+        Ok(())
+    }
+
+    #[test]
+    fn printed_config_has_the_merged_settings_and_reads_back() -> Result<()> {
+        let toml = "height = 30\nsound = \"bell\"\n[keys]\nquit = [\"q\", \"esc\"]\n[colors]\nball = \"#ffd700\"\n";
+        let config = config_from(toml, &["--width", "90", "--wrap", "--bind", "up=k,F2"])?;
+        let json = config.to_json()?;
+        let printed: serde_json::Value = serde_json::from_str(&json)?;
+        assert_eq!(printed["width"], 90);
+        assert_eq!(printed["height"], 30);
+        assert_eq!(printed["paddle_height"], 8);
+        assert_eq!(printed["wrap"], true);
+        assert_eq!(printed["sound"], "bell");
+        assert_eq!(printed["difficulty"], "normal");
+        assert_eq!(printed["keys"]["quit"], serde_json::json!(["q", "esc"]));
+        assert_eq!(printed["keys"]["up"], serde_json::json!(["k", "f2"]));
+        assert_eq!(printed["colors"]["ball"], "#ffd700");
+
+        let read_back: GameConfig = serde_json::from_str(&json)?;
+        assert_eq!(
+            read_back,
+            GameConfig {
+                paddle_height: Some(8),
+                ..config
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn only_closed_pipes_count_as_broken() {
+        let closed = io::Error::from(io::ErrorKind::BrokenPipe);
+        assert!(is_broken_pipe(&anyhow::Error::from(closed)));
+        let closed = io::Error::from(io::ErrorKind::BrokenPipe);
+        assert!(is_broken_pipe(
+            &anyhow::Error::from(closed).context("drawing the field")
+        ));
+        let other = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(!is_broken_pipe(&anyhow::Error::from(other)));
+        assert!(!is_broken_pipe(&anyhow::anyhow!("broken pipe")));
+    }
+
+    #[test]
+    fn huge_delays_turn_into_ticks_without_overflowing() -> Result<()> {
+        let max = u32::MAX.to_string();
+        let config = config_from("", &["--serve-delay", &max, "--ai-reaction-ms", &max])?;
+        let settings = config.settings();
+        let ticks = u32::MAX / 1000 * 60;
+        assert!(settings.serve_ticks >= ticks && settings.ai_reaction_a >= ticks);
+
+        let mut adopted = GameConfig::default();
+        adopted.adopt(&duckdb_pong::sql::Settings {
+            serve_ticks: u32::MAX,
+            ..settings
+        });
+        assert_eq!(adopted.serve_delay, u32::MAX);
+        Ok(())
+    }
+
+    #[test]
+    fn hires_settings_double_tall_fields_without_overflowing() {
+        let config = GameConfig {
+            height: 40000,
+            paddle_height: Some(20000),
+            hires: true,
+            ..GameConfig::default()
+        };
+        let settings = config.settings();
+        assert_eq!((settings.height, settings.paddle_h), (80000, 40000));
+    }
+
+    #[test]
+    fn derived_paddle_height_follows_even_the_tallest_fields() -> Result<()> {
+        let config = config_from("height = 1000\n", &[])?;
+        assert_eq!(config.paddle_height(), 280);
+        assert_eq!(config.required_size(), (80, 1002));
+        assert_eq!(config.status_row(), 1001);
+        let config = config_from("height = 10\n", &[])?;
+        assert_eq!(config.paddle_height(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn fields_beyond_the_largest_are_refused() {
+        let err = config_from("width = 1001\nheight = 65535\n", &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("width = 1001"), "{err}");
+        assert!(err.contains("height = 65535"), "{err}");
+        assert!(Args::try_parse_from(["duckdb-pong", "--height", "1001"]).is_err());
+        assert!(Args::try_parse_from(["duckdb-pong", "--width", "1000"]).is_ok());
+    }
+
+    #[test]
+    fn invalid_config_names_every_offending_field() {
+        let toml = "height = 12\npaddle_height = 11\nballs = 20\n";
+        let err = config_from(toml, &[]).unwrap_err().to_string();
+        assert!(err.contains("paddle_height = 11"), "{err}");
+        assert!(err.contains("balls = 20"), "{err}");
+        assert!(!err.contains("width"), "{err}");
+    }
+
+    #[test]
+    fn keys_come_from_the_config_file_and_bind_flags() -> Result<()> {
+        let toml = "[keys]\nquit = [\"q\", \"esc\"]\npause = [\"p\"]\n";
+        let config = config_from(toml, &["--bind", "up=k,F2", "--bind", "down=j"])?;
+        let keys = &config.keys;
+        assert_eq!(keys.action(event::KeyCode::Char('Q')), Some(Action::Quit));
+        assert_eq!(keys.action(event::KeyCode::Esc), Some(Action::Quit));
+        assert_eq!(keys.action(event::KeyCode::F(2)), Some(Action::Up));
+        assert_eq!(keys.action(event::KeyCode::Char('w')), None);
+        assert_eq!(keys.label(Action::Up), "K/F2");
+        assert_eq!(keys.label(Action::Restart), "ENTER");
+        assert_eq!(keys.action(event::KeyCode::Char('M')), Some(Action::Slowmo));
+        assert_eq!(keys.action(event::KeyCode::Char('+')), Some(Action::FpsUp));
+        assert_eq!(keys.label(Action::FpsDown), "-");
+        assert_eq!(
+            keys.action(event::KeyCode::Char('f')),
+            Some(Action::FastForward)
+        );
+        assert_eq!(keys.label(Action::InstantReplay), "R");
+        Ok(())
+    }
+
+    #[test]
+    fn fast_forward_ticks_through_the_rally_and_stops_on_the_point() -> Result<()> {
+        let mut game = Game::open(GameConfig::default(), None, false, Some(0.5))?;
+        // Both paddles hold still at the top while the ball runs low at Player A
+        game.connection().execute(
+            "UPDATE state SET ax = 0, bx = 0, ball_x = 40, ball_y = 20, vx = -1, vy = 0",
+            [],
+        )?;
+        let inputs = Inputs {
+            a: Some(0),
+            b: PlayerInput::Hold,
+        };
+        let width = game.config().width.into();
+        let mut last = game.state()?;
+        let points = last.score_a + last.score_b;
+
+        let mut fast_forward = FastForward { on: true };
+        let mut frames = 0;
+        while fast_forward.on {
+            frames += 1;
+            assert!(
+                frames < 100,
+                "no point after {frames} fast-forwarded frames"
+            );
+            // No game time passes between the frames, yet they keep ticking
+            let mut ticks = 0;
+            while fast_forward.tick_due(Duration::ZERO) && ticks < fast_forward.max_ticks() {
+                game.tick(inputs)?;
+                let state = game.state()?;
+                let event = SoundEvent::between(&last, &state, width);
+                last = state;
+                ticks += 1;
+                if fast_forward.stop_at(event) {
+                    assert_eq!(state.score_a + state.score_b, points + 1);
+                }
+            }
+            assert!(ticks <= FAST_FORWARD_TICKS);
+            if fast_forward.on {
+                assert_eq!(ticks, FAST_FORWARD_TICKS);
+            }
+        }
+        assert!(frames > 1, "the rally should take more than one frame");
+
+        // The frame ended on the scoring tick, and normal speed waits for game time
+        assert_eq!(game.state()?, last);
+        assert_eq!(last.score_a + last.score_b, points + 1);
+        assert!(!fast_forward.tick_due(Duration::ZERO));
+        assert!(fast_forward.tick_due(SIM_DT));
+        assert_eq!(fast_forward.max_ticks(), MAX_TICKS_PER_FRAME);
+        // Scoring again at normal speed changes nothing
+        assert!(!fast_forward.stop_at(Some(SoundEvent::Score)));
+        Ok(())
+    }
+
+    #[test]
+    fn colors_are_hex_strings_checked_when_the_config_loads() -> Result<()> {
+        use duckdb_pong::palette::Rgb;
+        use style::Color;
+
+        let toml = "[colors]\nball = \"#ffd700\"\npaddle_a = \"#0cc\"\n";
+        let config = config_from(toml, &["--crt", "--spin", "--hires", "--sound", "bell"])?;
+        let colors = config.colors;
+        assert_eq!(colors.ball, Some(Rgb(0xff, 0xd7, 0x00)));
+        assert_eq!(colors.paddle_a, Some(Rgb(0x00, 0xcc, 0xcc)));
+        assert_eq!(colors.background, None);
+        // Without truecolor the nearest ANSI colors stand in
+        assert_eq!(Rgb(0xff, 0xd7, 0x00).to_color(false), Color::Yellow);
+        assert_eq!(Rgb(0x00, 0xcc, 0xcc).to_color(false), Color::DarkCyan);
+        assert_eq!(Rgb(1, 2, 3).to_color(true), Color::Rgb { r: 1, g: 2, b: 3 });
+
+        for bad in ["\"ffd700\"", "\"#ffd70\"", "\"#gggggg\""] {
+            let toml = format!("[colors]\nborder = {bad}\n");
+            let err =
+                config_from(&toml, &["--crt", "--spin", "--hires", "--sound", "bell"]).unwrap_err();
+            assert!(
+                format!("{err:#}").contains("use #rrggbb or #rgb"),
+                "{err:#}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn heatmap_is_refused_for_runs_that_never_play_a_match() {
+        for flag in [
+            "--screenshot=shot.txt",
+            "--stats",
+            "--print-config",
+            "--self-test",
+        ] {
+            let parsed = Args::try_parse_from(["duckdb-pong", "--heatmap", "-", flag]);
+            assert!(parsed.is_err(), "{flag}");
+        }
+        assert!(Args::try_parse_from(["duckdb-pong", "--heatmap", "-", "--plain"]).is_ok());
+    }
+
+    #[test]
+    fn unknown_or_clashing_keys_are_rejected() {
+        let err = config_from("[keys]\nup = [\"jump\"]\n", &["--fps", "60"]).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unknown key \"jump\""),
+            "{err:#}"
+        );
+
+        let err = Args::try_parse_from(["duckdb-pong", "--bind", "up=f13"]).unwrap_err();
+        assert!(err.to_string().contains("f1 to f12"), "{err}");
+
+        let err = config_from("[keys]\npause = [\"esc\"]\n", &["--spin"])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("key ESC is bound to both pause and quit"),
+            "{err}"
+        );
+    }
+}
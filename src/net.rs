@@ -0,0 +1,192 @@
+//! Two-player games over TCP. The host runs the simulation and streams every frame's
+//! state to the client; the client only draws what it receives and sends its paddle
+//! input back.
+//!
+//! The protocol is one JSON object per line. The host opens with a `hello` carrying
+//! the game settings, then sends a `frame` after every redraw:
+//!
+//! ```text
+//! {"type":"hello","settings":{"width":80,"height":25,...}}
+//! {"type":"frame","state":{"tick":1,"ax":9,"bx":9,...}}
+//! ```
+//!
+//! The client answers key presses with `{"type":"input","direction":-1}`.
+
+use anyhow::{Context, Result, bail};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::{Ipv4Addr, Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::{State, sql};
+
+/// Which end of a network game this process is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetRole {
+    /// Wait for a player on this port, play Player B and run the simulation.
+    Host(u16),
+    /// Join the game at this `addr:port` as Player A.
+    Client(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HostMessage {
+    Hello { settings: sql::Settings },
+    Frame { state: State },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// -1 up, +1 down, like [`crate::PlayerInput`]'s direction.
+    Input { direction: i32 },
+}
+
+/// One end of the connection. Incoming lines are parsed on a reader thread so the
+/// game loop can poll for them without blocking.
+pub struct Link<In> {
+    writer: BufWriter<TcpStream>,
+    incoming: Receiver<In>,
+}
+
+/// The other side hung up, or sent something that isn't part of the protocol.
+#[derive(Debug)]
+pub struct ConnectionLost;
+
+impl<In: DeserializeOwned + Send + 'static> Link<In> {
+    fn new(stream: TcpStream) -> Result<Self> {
+        // Frames are tiny and latency matters more than throughput
+        stream.set_nodelay(true)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (tx, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(message) = line
+                    .map_err(drop)
+                    .and_then(|line| serde_json::from_str::<In>(&line).map_err(drop))
+                else {
+                    break;
+                };
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+            // Dropping the sender is how the game loop learns the connection is gone
+        });
+        Ok(Link {
+            writer: BufWriter::new(stream),
+            incoming,
+        })
+    }
+
+    pub fn send(&mut self, message: &impl Serialize) -> Result<(), ConnectionLost> {
+        serde_json::to_writer(&mut self.writer, message).map_err(|_| ConnectionLost)?;
+        self.writer
+            .write_all(b"\n")
+            .and_then(|()| self.writer.flush())
+            .map_err(|_| ConnectionLost)
+    }
+
+    /// The next message if one has arrived.
+    pub fn try_recv(&self) -> Result<Option<In>, ConnectionLost> {
+        match self.incoming.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(ConnectionLost),
+        }
+    }
+
+    /// Wait for the next message.
+    pub fn recv(&self) -> Result<In, ConnectionLost> {
+        self.incoming.recv().map_err(|_| ConnectionLost)
+    }
+}
+
+impl<In> Drop for Link<In> {
+    /// The reader thread holds a clone of the socket, so close it explicitly for the
+    /// other side to notice.
+    fn drop(&mut self) {
+        let _ = self.writer.get_ref().shutdown(Shutdown::Both);
+    }
+}
+
+/// Wait for a client on `port` and greet it with the game settings.
+pub fn host(port: u16, settings: sql::Settings) -> Result<Link<ClientMessage>> {
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+        .with_context(|| format!("listening on port {port}"))?;
+    println!("Waiting for a player to connect on port {port}...");
+    accept(&listener, settings)
+}
+
+/// Take the next client from `listener` and greet it with the game settings.
+pub fn accept(listener: &TcpListener, settings: sql::Settings) -> Result<Link<ClientMessage>> {
+    let (stream, peer) = listener.accept()?;
+    println!("{peer} joined");
+    let mut link = Link::new(stream)?;
+    if link.send(&HostMessage::Hello { settings }).is_err() {
+        bail!("{peer} disconnected before the game started");
+    }
+    Ok(link)
+}
+
+/// Join the host at `addr` and return the link with the game settings it sent.
+pub fn connect(addr: &str) -> Result<(Link<HostMessage>, sql::Settings)> {
+    let stream = TcpStream::connect(addr).with_context(|| format!("connecting to {addr}"))?;
+    let link = Link::new(stream)?;
+    match link.recv() {
+        Ok(HostMessage::Hello { settings }) => Ok((link, settings)),
+        Ok(HostMessage::Frame { .. }) => bail!("{addr} sent a frame before saying hello"),
+        Err(ConnectionLost) => bail!("{addr} closed the connection"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_client_gets_settings_and_frames_and_sends_input() -> Result<()> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?.to_string();
+        let settings = sql::Settings {
+            width: 60,
+            ..sql::Settings::default()
+        };
+        let host = std::thread::spawn(move || accept(&listener, settings));
+
+        let (mut client, received) = connect(&addr)?;
+        let mut host = host.join().unwrap()?;
+        assert_eq!(received, settings);
+
+        let state = State {
+            tick: 7,
+            ax: 3,
+            bx: 4,
+            ball_x: 30,
+            ball_y: 12,
+            vx: -1,
+            vy: 2,
+            score_a: 1,
+            score_b: 5,
+        };
+        host.send(&HostMessage::Frame { state }).unwrap();
+        match client.recv().unwrap() {
+            HostMessage::Frame { state: got } => assert_eq!(got, state),
+            other => panic!("expected a frame, got {other:?}"),
+        }
+
+        client
+            .send(&ClientMessage::Input { direction: -1 })
+            .unwrap();
+        let ClientMessage::Input { direction } = host.recv().unwrap();
+        assert_eq!(direction, -1);
+
+        // Hanging up shows as a lost connection on the other side
+        drop(client);
+        assert!(host.recv().is_err());
+        Ok(())
+    }
+}
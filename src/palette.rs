@@ -0,0 +1,158 @@
+//! Custom colors from the `[colors]` table of the config file, as hex strings:
+//!
+//! ```toml
+//! [colors]
+//! ball = "#ffffff"
+//! paddle_a = "#00c0c0"
+//! paddle_b = "#c000c0"
+//! border = "#606060"
+//! center_line = "#404040"
+//! background = "#101010"
+//! ```
+//!
+//! Colors left out keep the built-in ANSI ones, and the background stays the
+//! terminal's own. Terminals that don't announce truecolor in `COLORTERM` get the
+//! nearest of the 16 ANSI colors instead.
+
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::render::Cell;
+
+/// A 24-bit color, parsed from `#rrggbb` or the short `#rgb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+/// The 16 ANSI colors with the values xterm gives them, for [`Rgb::nearest_ansi`].
+const ANSI: [(Color, Rgb); 16] = [
+    (Color::Black, Rgb(0, 0, 0)),
+    (Color::DarkRed, Rgb(205, 0, 0)),
+    (Color::DarkGreen, Rgb(0, 205, 0)),
+    (Color::DarkYellow, Rgb(205, 205, 0)),
+    (Color::DarkBlue, Rgb(0, 0, 238)),
+    (Color::DarkMagenta, Rgb(205, 0, 205)),
+    (Color::DarkCyan, Rgb(0, 205, 205)),
+    (Color::Grey, Rgb(229, 229, 229)),
+    (Color::DarkGrey, Rgb(127, 127, 127)),
+    (Color::Red, Rgb(255, 0, 0)),
+    (Color::Green, Rgb(0, 255, 0)),
+    (Color::Yellow, Rgb(255, 255, 0)),
+    (Color::Blue, Rgb(92, 92, 255)),
+    (Color::Magenta, Rgb(255, 0, 255)),
+    (Color::Cyan, Rgb(0, 255, 255)),
+    (Color::White, Rgb(255, 255, 255)),
+];
+
+impl Rgb {
+    /// The color as the terminal gets it: exact with `truecolor`, otherwise the
+    /// nearest ANSI color.
+    pub fn to_color(self, truecolor: bool) -> Color {
+        if truecolor {
+            Color::Rgb {
+                r: self.0,
+                g: self.1,
+                b: self.2,
+            }
+        } else {
+            self.nearest_ansi()
+        }
+    }
+
+    /// The ANSI color closest to this one, by distance in RGB space.
+    pub fn nearest_ansi(self) -> Color {
+        let distance = |other: Rgb| {
+            let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+            d(self.0, other.0) + d(self.1, other.1) + d(self.2, other.2)
+        };
+        ANSI.into_iter()
+            .min_by_key(|&(_, rgb)| distance(rgb))
+            .map_or(Color::White, |(color, _)| color)
+    }
+
+    /// The color as `0xRRGGBB`, e.g. for a window's pixel buffer.
+    pub fn packed(self) -> u32 {
+        u32::from(self.0) << 16 | u32::from(self.1) << 8 | u32::from(self.2)
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(hex: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid color {hex:?}: use #rrggbb or #rgb, e.g. \"#00c0c0\"");
+        let digits = hex.strip_prefix('#').ok_or_else(invalid)?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |i: usize, len: usize| {
+            u8::from_str_radix(&digits[i * len..(i + 1) * len], 16).map_err(|_| invalid())
+        };
+        match digits.len() {
+            6 => Ok(Rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+            // Each digit doubled, as in CSS: #0cc is #00cccc
+            3 => Ok(Rgb(
+                channel(0, 1)? * 17,
+                channel(1, 1)? * 17,
+                channel(2, 1)? * 17,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, String> {
+        hex.parse()
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(color: Rgb) -> String {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Colors replacing the built-in ones; `None` keeps the built-in color. The default
+/// is the built-in scheme.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Palette {
+    pub ball: Option<Rgb>,
+    pub paddle_a: Option<Rgb>,
+    pub paddle_b: Option<Rgb>,
+    /// The top and bottom edges, and the training wall.
+    pub border: Option<Rgb>,
+    pub center_line: Option<Rgb>,
+    /// Behind the whole field, empty cells included.
+    pub background: Option<Rgb>,
+}
+
+impl Palette {
+    /// The custom color for `cell`, if there is one.
+    pub fn get(&self, cell: Cell) -> Option<Rgb> {
+        match cell {
+            Cell::Ball => self.ball,
+            Cell::PaddleA => self.paddle_a,
+            Cell::PaddleB => self.paddle_b,
+            Cell::Border | Cell::Wall => self.border,
+            Cell::CenterLine => self.center_line,
+            _ => None,
+        }
+    }
+}
+
+/// Whether the terminal says it shows 24-bit colors, by `COLORTERM`.
+pub fn truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|value| matches!(value.as_str(), "truecolor" | "24bit"))
+}
@@ -0,0 +1,224 @@
+//! Recording matches to CSV, one `state` row per tick, and loading them back for
+//! playback, or keeping the last few ticks in memory for an instant replay.
+//! Recordings are plain CSV, so they can also be analyzed in DuckDB:
+//!
+//! ```sql
+//! SELECT * FROM read_csv_auto('match.csv');
+//! ```
+
+use anyhow::{Context, Result};
+use duckdb::{Connection, params};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::{STATE_COLUMNS, State};
+
+/// Appends every tick's state to a CSV file. Rows go through a buffered writer so
+/// the disk is only touched every few hundred ticks, not every frame.
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("creating recording {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "{}", STATE_COLUMNS.replace(' ', ""))?;
+        Ok(Recorder { out })
+    }
+
+    pub fn record(&mut self, state: &State) -> Result<()> {
+        let State {
+            tick,
+            ax,
+            bx,
+            ball_x,
+            ball_y,
+            vx,
+            vy,
+            score_a,
+            score_b,
+        } = state;
+        writeln!(
+            self.out,
+            "{tick},{ax},{bx},{ball_x},{ball_y},{vx},{vy},{score_a},{score_b}"
+        )?;
+        Ok(())
+    }
+
+    /// Write out whatever is still buffered.
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Load a recording into the temp table `replay` and return its rows in file order.
+pub fn load_replay(conn: &Connection, path: &Path) -> Result<Vec<State>> {
+    let context = || format!("loading replay {}", path.display());
+    let quoted = path.to_string_lossy().replace('\'', "''");
+    conn.execute_batch(&format!(
+        "CREATE OR REPLACE TEMP TABLE replay AS SELECT * FROM read_csv_auto('{quoted}')"
+    ))
+    .with_context(context)?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {STATE_COLUMNS} FROM replay"))
+        .with_context(context)?;
+    let frames = stmt
+        .query_map([], State::from_row)
+        .with_context(context)?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(context)?;
+    Ok(frames)
+}
+
+/// Overwrite the `state` row with a recorded frame so the render query draws it.
+/// Recordings only hold the primary ball, so any extra balls are cleared.
+pub fn show(conn: &Connection, state: &State) -> Result<()> {
+    conn.execute(
+        "UPDATE state SET tick = ?, ax = ?, bx = ?, ball_x = ?, ball_y = ?, vx = ?, vy = ?,
+                          score_a = ?, score_b = ?, extra_balls = NULL",
+        params![
+            state.tick,
+            state.ax,
+            state.bx,
+            state.ball_x,
+            state.ball_y,
+            state.vx,
+            state.vy,
+            state.score_a,
+            state.score_b
+        ],
+    )
+    .context("showing a recorded frame")?;
+    Ok(())
+}
+
+/// The states of the last `capacity` ticks, oldest first, for an instant replay.
+pub struct RewindBuffer {
+    states: VecDeque<State>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            states: VecDeque::with_capacity(capacity + 1),
+            capacity,
+        }
+    }
+
+    /// Keep `state`, forgetting the oldest one once the buffer is full.
+    pub fn push(&mut self, state: State) {
+        self.states.push_back(state);
+        if self.states.len() > self.capacity {
+            self.states.pop_front();
+        }
+    }
+
+    pub fn states(&self) -> &VecDeque<State> {
+        &self.states
+    }
+
+    /// The newest `n` states, or all of them while there are fewer, oldest first.
+    pub fn last(&self, n: usize) -> impl Iterator<Item = &State> {
+        self.states.range(self.states.len().saturating_sub(n)..)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+/// Put the whole `state` row aside in the temp table `live_state`, before [`show`]
+/// draws other frames over it.
+pub fn hold_live(conn: &Connection) -> Result<()> {
+    conn.execute_batch("CREATE OR REPLACE TEMP TABLE live_state AS SELECT * FROM state")
+        .context("holding the live state")?;
+    Ok(())
+}
+
+/// Bring back the `state` row [`hold_live`] put aside, extra balls, serve timers
+/// and all, so the match goes on exactly where it was.
+pub fn resume_live(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DELETE FROM state;
+        INSERT INTO state SELECT * FROM live_state;
+        DROP TABLE live_state;",
+    )
+    .context("resuming the live state")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Difficulty, PlayerInput, read_state, sql, tick};
+
+    #[test]
+    fn recorded_match_loads_back_frame_by_frame() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("duckdb-pong-{}.csv", std::process::id()));
+        let conn = Connection::open_in_memory()?;
+        conn.execute(&sql::build_setup(&sql::Settings::default(), false), [])?;
+
+        let mut recorder = Recorder::create(&path)?;
+        let mut recorded = Vec::new();
+        for _ in 0..20 {
+            tick(&conn, PlayerInput::None, Difficulty::Normal)?;
+            let state = read_state(&conn)?;
+            recorder.record(&state)?;
+            recorded.push(state);
+        }
+        recorder.finish()?;
+
+        let frames = load_replay(&conn, &path)?;
+        assert_eq!(frames, recorded);
+        show(&conn, &frames[5])?;
+        assert_eq!(read_state(&conn)?, recorded[5]);
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[test]
+    fn instant_replay_keeps_the_last_ticks_and_resumes_where_the_match_was() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        let settings = sql::Settings {
+            balls: 2,
+            ..sql::Settings::default()
+        };
+        conn.execute(&sql::build_setup(&settings, false), [])?;
+        let mut rewind = RewindBuffer::new(10);
+        let mut played = Vec::new();
+        for _ in 0..25 {
+            tick(&conn, PlayerInput::None, Difficulty::Normal)?;
+            let state = read_state(&conn)?;
+            rewind.push(state);
+            played.push(state);
+        }
+        assert!(rewind.states().iter().eq(&played[15..]));
+        // A replay after a point only takes the newest of them
+        assert!(rewind.last(4).eq(&played[21..]));
+        assert!(rewind.last(50).eq(&played[15..]));
+
+        let full_row = |conn: &Connection| -> Result<String> {
+            Ok(
+                conn.query_row("SELECT CAST(state AS VARCHAR) FROM state", [], |row| {
+                    row.get(0)
+                })?,
+            )
+        };
+        let live = full_row(&conn)?;
+        hold_live(&conn)?;
+        for state in rewind.states() {
+            show(&conn, state)?;
+            assert_eq!(read_state(&conn)?, *state);
+        }
+        resume_live(&conn)?;
+        assert_eq!(full_row(&conn)?, live);
+        Ok(())
+    }
+}
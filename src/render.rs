@@ -0,0 +1,599 @@
+//! Turning the cells listed by [`sql::RENDER_SPARSE`](crate::sql::RENDER_SPARSE) (or
+//! the whole grid from [`sql::RENDER`](crate::sql::RENDER) and
+//! [`sql::RENDER_HIRES`](crate::sql::RENDER_HIRES)) into styled terminal output, or
+//! printing the ready-made screen from [`sql::RENDER_FULL`](crate::sql::RENDER_FULL).
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use crossterm::style::{self, Attribute, Color, Stylize};
+use crossterm::{QueueableCommand, cursor};
+use duckdb::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+
+use crate::config::MAX_BALL_SPEED;
+use crate::palette::{self, Palette};
+use crate::sql;
+
+/// Terminal row of the first field line; row 0 above it holds the score header.
+pub const FIELD_TOP: u16 = 1;
+
+/// Which render query draws the field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    /// Only the cells that aren't empty, drawn in color and only where the field changed
+    Cells,
+    /// The same picture from one row per cell of the whole grid, which costs more the
+    /// bigger the field is
+    Grid,
+    /// The whole field as a single string, reprinted every frame without colors
+    Full,
+}
+
+/// How the top and bottom edges of the field are drawn. Every style takes the same
+/// single row at each edge, so the field inside stays the same size. The left and
+/// right sides stay open, they are the goals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderStyle {
+    /// Half blocks hugging the field: `▄` along the top, `▀` along the bottom
+    #[default]
+    Blocks,
+    /// Box-drawing lines with corners: `┌─┐` and `└─┘`
+    Box,
+    /// `+` corners and `-` lines, for terminals with poor Unicode
+    Ascii,
+}
+
+impl BorderStyle {
+    /// The style as the `BORDER` parameter of [`sql::SETUP`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            BorderStyle::Blocks => "blocks",
+            BorderStyle::Box => "box",
+            BorderStyle::Ascii => "ascii",
+        }
+    }
+}
+
+/// Which characters draw the field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GlyphSet {
+    /// Block characters, and half blocks in hi-res mode
+    #[default]
+    Unicode,
+    /// 7-bit characters only, for terminals and SSH setups that garble blocks: `-`
+    /// borders, `|` paddles, an `O` ball and a `:` center line
+    Ascii,
+}
+
+impl GlyphSet {
+    /// The glyph set as the `$1` parameter of [`sql::RENDER_FULL`] spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            GlyphSet::Unicode => "unicode",
+            GlyphSet::Ascii => "ascii",
+        }
+    }
+
+    /// The character `tile` is drawn with. Glyphs that are ASCII already, like the
+    /// countdown digits and pickups, stay; in [`GlyphSet::Ascii`] the others become
+    /// their cell's stand-in, half blocks included, so nothing but empty cells turns
+    /// blank.
+    pub fn glyph(self, tile: Tile) -> char {
+        if self == GlyphSet::Unicode || tile.glyph.is_ascii() {
+            return tile.glyph;
+        }
+        match tile.cell {
+            Cell::Border => '-',
+            Cell::Wall | Cell::PaddleA | Cell::PaddleB => '|',
+            Cell::Ball => 'O',
+            Cell::CenterLine => ':',
+            Cell::Trail(_) => '.',
+            Cell::Countdown => '*',
+            Cell::PowerUp => '+',
+            Cell::Empty => ' ',
+        }
+    }
+}
+
+/// A field fetched with any [`RenderMode`].
+pub enum Rendered {
+    Cells(Vec<Vec<Tile>>),
+    Full(String),
+}
+
+/// What occupies a single character cell of the playing field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Border,
+    /// The wall standing in for Player A in training, whose glyph comes from the query.
+    Wall,
+    PaddleA,
+    PaddleB,
+    Ball,
+    /// The serve countdown digit, whose glyph comes from the query.
+    Countdown,
+    CenterLine,
+    /// A power-up pickup, whose glyph (its kind) comes from the query.
+    PowerUp,
+    /// Where the ball was a few ticks ago, `0` the most recent of [`TRAIL_SHADES`].
+    Trail(u8),
+}
+
+/// Glyphs for the ball's trail, from right behind the ball to the faded tail.
+const TRAIL_SHADES: [char; 3] = ['▓', '▒', '░'];
+
+impl Cell {
+    /// Parse the `kind` column returned by the render query.
+    fn from_kind(kind: &str) -> Result<Self> {
+        Ok(match kind {
+            "empty" => Cell::Empty,
+            "border" => Cell::Border,
+            "wall" => Cell::Wall,
+            "paddle_a" => Cell::PaddleA,
+            "paddle_b" => Cell::PaddleB,
+            "ball" => Cell::Ball,
+            "countdown" => Cell::Countdown,
+            "center" => Cell::CenterLine,
+            "powerup" => Cell::PowerUp,
+            other => bail!("render query returned unknown cell kind {other:?}"),
+        })
+    }
+
+    fn glyph(self) -> char {
+        match self {
+            Cell::Empty | Cell::Countdown | Cell::PowerUp => ' ',
+            Cell::Border => '▀',
+            Cell::Wall | Cell::PaddleA | Cell::PaddleB | Cell::Ball | Cell::CenterLine => '█',
+            Cell::Trail(shade) => TRAIL_SHADES[usize::from(shade)],
+        }
+    }
+
+    fn color(self) -> Option<Color> {
+        match self {
+            Cell::Empty => None,
+            Cell::Border | Cell::Wall | Cell::CenterLine => Some(Color::DarkGrey),
+            Cell::PaddleA => Some(Color::Cyan),
+            Cell::PaddleB => Some(Color::Magenta),
+            Cell::Ball => Some(Color::White),
+            Cell::Countdown => Some(Color::Yellow),
+            Cell::PowerUp => Some(Color::Green),
+            Cell::Trail(0) => Some(Color::Grey),
+            Cell::Trail(_) => Some(Color::DarkGrey),
+        }
+    }
+}
+
+/// A character cell as drawn: what occupies it, which sets the color, and the glyph.
+/// The glyph only differs from the cell's usual one for the serve countdown, power-up
+/// pickups, the border (its [`BorderStyle`]) and in hi-res mode, where half-blocks
+/// show which of its two pixels are filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub cell: Cell,
+    pub glyph: char,
+}
+
+impl From<Cell> for Tile {
+    fn from(cell: Cell) -> Self {
+        Tile {
+            cell,
+            glyph: cell.glyph(),
+        }
+    }
+}
+
+/// Build the rows of the field from the cells [`render_sparse`] lists. With `hires`
+/// the field is simulated at twice the height and drawn with half-blocks, combining
+/// each cell's two pixels the way [`sql::RENDER_HIRES`] does.
+pub fn query_field(
+    conn: &Connection,
+    hires: bool,
+    width: u16,
+    height: u16,
+) -> Result<Vec<Vec<Tile>>> {
+    let empty = Tile::from(Cell::Empty);
+    let mut field = vec![vec![empty; usize::from(width)]; usize::from(height)];
+    if !hires {
+        for (y, x, tile) in render_sparse(conn)? {
+            if let Some(slot) = field
+                .get_mut(y as usize)
+                .and_then(|row| row.get_mut(x as usize))
+            {
+                *slot = tile;
+            }
+        }
+        return Ok(field);
+    }
+
+    // The upper and lower pixel of every character cell
+    let mut halves = vec![vec![(empty, empty); usize::from(width)]; usize::from(height)];
+    for (y, x, tile) in render_sparse(conn)? {
+        let row = y as usize / 2;
+        if let Some(pair) = halves
+            .get_mut(row)
+            .and_then(|line| line.get_mut(x as usize))
+        {
+            if y % 2 == 0 {
+                pair.0 = tile;
+            } else {
+                pair.1 = tile;
+            }
+        }
+    }
+    for (line, pairs) in field.iter_mut().zip(&halves) {
+        for (slot, &(upper, lower)) in line.iter_mut().zip(pairs) {
+            *slot = half_blocks(upper, lower);
+        }
+    }
+    Ok(field)
+}
+
+/// Which of its two pixels decides a hi-res cell's color, most important first.
+const HIRES_PRECEDENCE: [Cell; 8] = [
+    Cell::Ball,
+    Cell::Countdown,
+    Cell::PowerUp,
+    Cell::PaddleA,
+    Cell::PaddleB,
+    Cell::CenterLine,
+    Cell::Border,
+    Cell::Wall,
+];
+
+/// The character cell for an upper and a lower pixel. The countdown digit and pickup
+/// glyphs fill the whole cell unless the ball shares it, and so do the training wall
+/// and border lines and corners that aren't half blocks; anything else is drawn as the
+/// half-block of the pixels that aren't empty.
+fn half_blocks(upper: Tile, lower: Tile) -> Tile {
+    let cell = HIRES_PRECEDENCE
+        .into_iter()
+        .find(|&cell| upper.cell == cell || lower.cell == cell)
+        .unwrap_or(Cell::Empty);
+    let own = if upper.cell == cell { upper } else { lower };
+    let glyph = match (cell, upper.cell, lower.cell) {
+        (Cell::Countdown | Cell::PowerUp | Cell::Wall, _, _) => own.glyph,
+        (Cell::Border, _, _) if !matches!(own.glyph, '▀' | '▄') => own.glyph,
+        (_, Cell::Empty, Cell::Empty) => ' ',
+        (_, _, Cell::Empty) => '▀',
+        (_, Cell::Empty, _) => '▄',
+        _ => '█',
+    };
+    Tile { cell, glyph }
+}
+
+/// Run [`sql::RENDER`] (or [`sql::RENDER_HIRES`]), which classifies every cell of the
+/// grid, and collect its cells into rows of the field; [`RenderMode::Grid`].
+pub fn query_grid(
+    conn: &Connection,
+    hires: bool,
+    width: u16,
+    height: u16,
+) -> Result<Vec<Vec<Tile>>> {
+    let mut field = vec![vec![Tile::from(Cell::Empty); usize::from(width)]; usize::from(height)];
+    let (query, name) = if hires {
+        (sql::RENDER_HIRES, "RENDER_HIRES")
+    } else {
+        (sql::RENDER, "RENDER")
+    };
+    let context = || format!("running the render query ({name})");
+    let mut stmt = conn.prepare(query).with_context(context)?;
+    let cells = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                row.get::<_, usize>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .with_context(context)?;
+    for cell in cells {
+        let (y, x, kind, glyph) = cell.with_context(context)?;
+        if let Some(slot) = field.get_mut(y).and_then(|row| row.get_mut(x)) {
+            let cell = Cell::from_kind(&kind)?;
+            *slot = Tile {
+                cell,
+                glyph: glyph
+                    .and_then(|glyph| glyph.chars().next())
+                    .unwrap_or(cell.glyph()),
+            };
+        }
+    }
+    Ok(field)
+}
+
+/// The field's non-empty cells from [`sql::RENDER_SPARSE`] as `(y, x, tile)`, ordered
+/// by row and then column, for callers that build their own frame from them. Tiles
+/// carry the glyph along with the cell, since the countdown and pickups bring their own.
+pub fn render_sparse(conn: &Connection) -> Result<Vec<(i32, i32, Tile)>> {
+    let context = "running the render query (RENDER_SPARSE)";
+    let mut stmt = conn.prepare(sql::RENDER_SPARSE).context(context)?;
+    let cells = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .context(context)?;
+    let mut tiles = Vec::new();
+    for cell in cells {
+        let (y, x, kind, glyph) = cell.context(context)?;
+        let cell = Cell::from_kind(&kind)?;
+        let glyph = glyph
+            .and_then(|glyph| glyph.chars().next())
+            .unwrap_or(cell.glyph());
+        tiles.push((y, x, Tile { cell, glyph }));
+    }
+    Ok(tiles)
+}
+
+/// Fetch the field with the query `mode` picks. `hires` only applies to
+/// [`RenderMode::Cells`] and [`RenderMode::Grid`], and `glyphs` only to
+/// [`RenderMode::Full`]; the cells get theirs from the [`FrameBuffer`].
+pub fn query(
+    conn: &Connection,
+    mode: RenderMode,
+    hires: bool,
+    glyphs: GlyphSet,
+    width: u16,
+    height: u16,
+) -> Result<Rendered> {
+    Ok(match mode {
+        RenderMode::Cells => Rendered::Cells(query_field(conn, hires, width, height)?),
+        RenderMode::Grid => Rendered::Cells(query_grid(conn, hires, width, height)?),
+        RenderMode::Full => Rendered::Full(
+            conn.query_row(sql::RENDER_FULL, params![glyphs.name()], |row| row.get(0))
+                .context("running the render query (RENDER_FULL)")?,
+        ),
+    })
+}
+
+/// The ball's last few positions, painted behind it in fading shades (`--trail`).
+/// Only the primary ball leaves one.
+pub struct Trail {
+    /// Newest first, in simulated pixels.
+    positions: VecDeque<(i32, i32)>,
+    len: usize,
+}
+
+impl Trail {
+    pub fn new(len: usize) -> Self {
+        Trail {
+            positions: VecDeque::with_capacity(len + 1),
+            len,
+        }
+    }
+
+    /// Note where the ball is after a tick. A jump no ball could make in one tick
+    /// means it was served again, which starts a fresh trail.
+    pub fn push(&mut self, x: i32, y: i32) {
+        if self.len == 0 {
+            return;
+        }
+        match self.positions.front() {
+            Some(&last) if last == (x, y) => return,
+            Some(&(last_x, _)) if (x - last_x).abs() > MAX_BALL_SPEED as i32 => {
+                self.positions.clear()
+            }
+            _ => {}
+        }
+        self.positions.push_front((x, y));
+        // The newest position is the ball itself, the trail is the rest
+        self.positions.truncate(self.len + 1);
+    }
+
+    /// Draw the trail into empty cells of `lines`; the ball, paddles, borders and
+    /// everything else the query drew stay on top. `pixels_per_row` maps hi-res
+    /// positions to terminal rows.
+    pub fn paint(&self, lines: &mut [Vec<Tile>], pixels_per_row: u16) {
+        let shades = TRAIL_SHADES.len();
+        for (age, &(x, y)) in self.positions.iter().enumerate().skip(1) {
+            let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+                continue;
+            };
+            let row = y / usize::from(pixels_per_row);
+            if let Some(tile) = lines.get_mut(row).and_then(|line| line.get_mut(x))
+                && tile.cell == Cell::Empty
+            {
+                // Spread the shades evenly over the trail's length
+                let shade = ((age - 1) * shades / self.len).min(shades - 1);
+                *tile = Tile::from(Cell::Trail(shade as u8));
+            }
+        }
+    }
+}
+
+/// Remembers the last drawn field so only rows that changed get rewritten,
+/// which avoids the flicker of clearing and reprinting the whole screen.
+pub struct FrameBuffer {
+    lines: Vec<Vec<Tile>>,
+    color: bool,
+    /// Dim odd rows as scanlines.
+    crt: bool,
+    palette: Palette,
+    /// Whether the palette's colors are shown exactly or as the nearest ANSI ones.
+    truecolor: bool,
+    glyphs: GlyphSet,
+}
+
+impl FrameBuffer {
+    /// A frame buffer drawing with `glyphs`, in `palette`'s colors where it has any,
+    /// in truecolor if `COLORTERM` says the terminal has it.
+    pub fn new(color: bool, crt: bool, palette: Palette, glyphs: GlyphSet) -> Self {
+        FrameBuffer {
+            lines: Vec::new(),
+            color,
+            crt,
+            palette,
+            truecolor: palette::truecolor(),
+            glyphs,
+        }
+    }
+
+    pub fn render_diff(&mut self, new_lines: &[Vec<Tile>], out: &mut impl Write) -> Result<()> {
+        for (y, line) in new_lines.iter().enumerate() {
+            if self.lines.get(y) != Some(line) {
+                out.queue(cursor::MoveTo(0, FIELD_TOP + y as u16))?;
+                let scanline = self.crt && y % 2 == 1;
+                // Set once for the whole row: the color resets between runs leave
+                // the intensity alone
+                if scanline {
+                    out.queue(style::SetAttribute(Attribute::Dim))?;
+                }
+                self.queue_line(line, out)?;
+                if scanline {
+                    out.queue(style::SetAttribute(Attribute::NormalIntensity))?;
+                }
+            }
+        }
+        self.lines = new_lines.to_vec();
+        Ok(())
+    }
+
+    /// Draw a field from [`query`]. A full screen is always printed whole, and without
+    /// scanlines. Either way every row is put in place with the cursor instead of
+    /// line breaks, so the status row below the field never scrolls the terminal.
+    pub fn draw(&mut self, rendered: &Rendered, out: &mut impl Write) -> Result<()> {
+        match rendered {
+            Rendered::Cells(lines) => self.render_diff(lines, out),
+            Rendered::Full(screen) => {
+                for (y, line) in screen.lines().enumerate() {
+                    out.queue(cursor::MoveTo(0, FIELD_TOP + y as u16))?
+                        .queue(style::Print(line))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Print one row, batching runs of identical cells into a single styled string.
+    fn queue_line(&self, line: &[Tile], out: &mut impl Write) -> Result<()> {
+        let background = self
+            .palette
+            .background
+            .map(|rgb| rgb.to_color(self.truecolor));
+        for run in line.chunk_by(|a, b| a == b) {
+            let text = self.glyphs.glyph(run[0]).to_string().repeat(run.len());
+            let cell = run[0].cell;
+            let color = match self.palette.get(cell) {
+                Some(rgb) => Some(rgb.to_color(self.truecolor)),
+                None => cell.color(),
+            };
+            if !self.color || (color.is_none() && background.is_none()) {
+                out.queue(style::Print(text))?;
+                continue;
+            }
+            let mut styled = style::style(text);
+            if let Some(color) = color {
+                styled = styled.with(color);
+            }
+            if let Some(background) = background {
+                styled = styled.on(background);
+            }
+            out.queue(style::PrintStyledContent(styled))?;
+        }
+        Ok(())
+    }
+
+    /// Forget what is on screen so the next frame is drawn in full, e.g. after an overlay.
+    pub fn invalidate(&mut self) {
+        self.lines.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crt_mode_dims_every_other_row() -> Result<()> {
+        let lines = vec![vec![Tile::from(Cell::Ball); 4]; 4];
+        let mut out = Vec::new();
+        FrameBuffer::new(true, true, Default::default(), GlyphSet::Unicode)
+            .render_diff(&lines, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert_eq!(out.matches("\x1b[2m").count(), 2, "{out:?}");
+        assert_eq!(out.matches("\x1b[22m").count(), 2, "{out:?}");
+
+        let mut plain = Vec::new();
+        FrameBuffer::new(true, false, Default::default(), GlyphSet::Unicode)
+            .render_diff(&lines, &mut plain)?;
+        assert!(!String::from_utf8(plain)?.contains("\x1b[2m"));
+        Ok(())
+    }
+
+    #[test]
+    fn full_screens_are_drawn_row_by_row_without_line_breaks() -> Result<()> {
+        let screen = Rendered::Full("ab\r\ncd".to_string());
+        let mut out = Vec::new();
+        FrameBuffer::new(true, false, Default::default(), GlyphSet::Unicode)
+            .draw(&screen, &mut out)?;
+        // Rows 2 and 3 of the terminal, under the score header
+        assert_eq!(String::from_utf8(out)?, "\x1b[2;1Hab\x1b[3;1Hcd");
+        Ok(())
+    }
+
+    #[test]
+    fn ascii_glyphs_stand_in_for_blocks_and_keep_the_ball_apart() -> Result<()> {
+        let tile = |cell, glyph| Tile { cell, glyph };
+        let line = vec![
+            tile(Cell::Border, '▀'),
+            tile(Cell::PaddleA, '█'),
+            // A hi-res ball in the upper pixel only
+            tile(Cell::Ball, '▀'),
+            tile(Cell::CenterLine, '█'),
+            tile(Cell::Trail(1), '▒'),
+            tile(Cell::Countdown, '3'),
+            tile(Cell::Border, '+'),
+        ];
+        let mut out = Vec::new();
+        FrameBuffer::new(false, false, Default::default(), GlyphSet::Ascii)
+            .render_diff(std::slice::from_ref(&line), &mut out)?;
+        assert!(String::from_utf8(out)?.ends_with("-|O:.3+"));
+
+        let unicode: String = line.iter().map(|&t| GlyphSet::Unicode.glyph(t)).collect();
+        assert_eq!(unicode, "▀█▀█▒3+");
+
+        // Countdowns and pickups drawn in other glyphs still show up in ASCII
+        let fancy = [tile(Cell::Countdown, '③'), tile(Cell::PowerUp, '▲')];
+        assert_eq!(fancy.map(|t| GlyphSet::Ascii.glyph(t)), ['*', '+']);
+        Ok(())
+    }
+
+    #[test]
+    fn trail_fades_behind_the_ball_and_stays_under_the_field() {
+        let mut lines = vec![vec![Tile::from(Cell::Empty); 10]; 3];
+        lines[1][2] = Tile::from(Cell::PaddleA);
+        lines[1][6] = Tile::from(Cell::Ball);
+        let mut trail = Trail::new(3);
+        for x in 1..=6 {
+            trail.push(x, 1);
+        }
+        trail.paint(&mut lines, 1);
+        let row: Vec<Cell> = lines[1].iter().map(|tile| tile.cell).collect();
+        assert_eq!(
+            row[1..=6],
+            [
+                Cell::Empty,
+                Cell::PaddleA,
+                Cell::Trail(2),
+                Cell::Trail(1),
+                Cell::Trail(0),
+                Cell::Ball
+            ]
+        );
+
+        // A serve jumps across the field and leaves the old trail behind
+        trail.push(40, 1);
+        let mut fresh = vec![vec![Tile::from(Cell::Empty); 10]; 3];
+        trail.paint(&mut fresh, 1);
+        assert!(fresh.iter().flatten().all(|tile| tile.cell == Cell::Empty));
+    }
+}
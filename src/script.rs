@@ -0,0 +1,138 @@
+//! Scripted matches for demos (`--script`): a CSV file of ticks, each with the moves
+//! both paddles make from then on and where to put the ball or a paddle. Instead of
+//! the AI the script steers both paddles, and ticks roll the same fixed numbers
+//! instead of random ones, so a script plays out the same rally every time:
+//!
+//! ```text
+//! tick,a,b,ball_x,ball_y,vx,vy
+//! 0,0,0,40,12,-1,1
+//! 20,-1,
+//! 45,0,1
+//! 400,,
+//! ```
+//!
+//! Only `tick` is required. `a` and `b` move Player A's and Player B's paddle from
+//! that tick on: -1 up, 1 down, 0 holding still. `ax`, `bx`, `ball_x`, `ball_y`, `vx`
+//! and `vy` overwrite the state before the tick runs. Empty cells leave things as
+//! they are, and the script ends at its last row.
+
+use anyhow::{Context, Result, ensure};
+use duckdb::{Connection, params};
+use std::path::Path;
+
+use crate::{Inputs, PlayerInput, Rolls};
+
+/// One line of a script.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScriptRow {
+    pub tick: i32,
+    pub a: Option<i32>,
+    pub b: Option<i32>,
+    pub ax: Option<i32>,
+    pub bx: Option<i32>,
+    pub ball_x: Option<i32>,
+    pub ball_y: Option<i32>,
+    pub vx: Option<i32>,
+    pub vy: Option<i32>,
+}
+
+/// A loaded script, and how far the game has got through it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Script {
+    rows: Vec<ScriptRow>,
+    next: usize,
+    /// The paddle moves the rows so far left in place.
+    a: i32,
+    b: i32,
+}
+
+impl Script {
+    /// The numbers every scripted tick rolls instead of random ones.
+    pub const ROLLS: Rolls = Rolls {
+        ai_a: 0.5,
+        ai_b: 0.5,
+        serve_row: 0.5,
+        serve_angle: 0.5,
+        serve_side: 0.5,
+    };
+
+    /// Read the script at `path` through DuckDB, in tick order. Columns the file
+    /// leaves out are empty in every row, and unknown ones are an error.
+    pub fn load(conn: &Connection, path: &Path) -> Result<Self> {
+        let quoted = path.to_string_lossy().replace('\'', "''");
+        conn.execute_batch(&format!(
+            "CREATE OR REPLACE TEMP TABLE script(
+                tick INTEGER, a INTEGER, b INTEGER, ax INTEGER, bx INTEGER,
+                ball_x INTEGER, ball_y INTEGER, vx INTEGER, vy INTEGER
+            );
+            INSERT INTO script BY NAME SELECT * FROM read_csv_auto('{quoted}', header = true, null_padding = true);"
+        ))
+        .with_context(|| format!("loading script {}", path.display()))?;
+        let mut stmt = conn
+            .prepare("SELECT tick, a, b, ax, bx, ball_x, ball_y, vx, vy FROM script ORDER BY tick")
+            .with_context(|| format!("loading script {}", path.display()))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(ScriptRow {
+                    tick: row.get(0)?,
+                    a: row.get(1)?,
+                    b: row.get(2)?,
+                    ax: row.get(3)?,
+                    bx: row.get(4)?,
+                    ball_x: row.get(5)?,
+                    ball_y: row.get(6)?,
+                    vx: row.get(7)?,
+                    vy: row.get(8)?,
+                })
+            })
+            .with_context(|| format!("loading script {}", path.display()))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| {
+                format!("loading script {}: every row needs a tick", path.display())
+            })?;
+        for row in &rows {
+            for (player, dir) in [('a', row.a), ('b', row.b)] {
+                ensure!(
+                    dir.is_none_or(|dir| (-1..=1).contains(&dir)),
+                    "script {}: {player} is {} at tick {} instead of -1, 0 or 1",
+                    path.display(),
+                    dir.unwrap_or_default(),
+                    row.tick
+                );
+            }
+        }
+        Ok(Script {
+            rows,
+            ..Script::default()
+        })
+    }
+
+    /// Apply the rows up to `tick`, the one about to run, and return the inputs for
+    /// it, or `None` once the script is past its last row.
+    pub fn step(&mut self, conn: &Connection, tick: i32) -> Result<Option<Inputs>> {
+        if self.rows.last().is_none_or(|last| tick > last.tick) {
+            return Ok(None);
+        }
+        while let Some(row) = self.rows.get(self.next).filter(|row| row.tick <= tick) {
+            conn.execute(
+                "UPDATE state SET
+                    ax = coalesce(?, ax), bx = coalesce(?, bx),
+                    ball_x = coalesce(?, ball_x), ball_y = coalesce(?, ball_y),
+                    vx = coalesce(?, vx), vy = coalesce(?, vy)",
+                params![row.ax, row.bx, row.ball_x, row.ball_y, row.vx, row.vy],
+            )
+            .with_context(|| format!("applying the script row for tick {}", row.tick))?;
+            self.a = row.a.unwrap_or(self.a);
+            self.b = row.b.unwrap_or(self.b);
+            self.next += 1;
+        }
+        Ok(Some(Inputs {
+            a: Some(self.a),
+            b: match self.b {
+                -1 => PlayerInput::Up,
+                1 => PlayerInput::Down,
+                _ => PlayerInput::Hold,
+            },
+        }))
+    }
+}
@@ -0,0 +1,137 @@
+//! Sounds for paddle hits and points, found by comparing the state before and after
+//! each tick. Only the terminal bell for now; `--sound` leaves room for more.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::State;
+
+/// Columns from either edge of the field within which a ball turning around counts as
+/// a paddle hit: the widest paddle plus the fastest ball.
+const NEAR_PADDLE: i32 =
+    crate::config::MAX_PADDLE_WIDTH as i32 + crate::config::MAX_BALL_SPEED as i32;
+
+/// Shortest time between two hit bells, so a fast rally doesn't ring every frame.
+pub const BELL_GAP: Duration = Duration::from_millis(150);
+
+/// How game events are made audible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SoundMode {
+    /// Silent
+    #[default]
+    Off,
+    /// The terminal bell (BEL): once for a paddle hit, twice for a point
+    Bell,
+}
+
+/// Something worth a sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    Hit,
+    Score,
+}
+
+impl SoundEvent {
+    /// The event from `last` to `state`, on a field `width` columns wide. A point
+    /// beats a hit in the same tick; a new set lowering the scores is neither.
+    pub fn between(last: &State, state: &State, width: i32) -> Option<Self> {
+        if state.score_a > last.score_a || state.score_b > last.score_b {
+            return Some(SoundEvent::Score);
+        }
+        let near_paddle = state.ball_x < NEAR_PADDLE || state.ball_x >= width - NEAR_PADDLE;
+        let turned = state.vx != 0 && state.vx.signum() == -last.vx.signum();
+        (near_paddle && turned).then_some(SoundEvent::Hit)
+    }
+}
+
+/// Plays [`SoundEvent`]s in the chosen [`SoundMode`].
+#[derive(Debug, Clone)]
+pub struct Sound {
+    mode: SoundMode,
+    last_bell: Option<Instant>,
+}
+
+impl Sound {
+    pub fn new(mode: SoundMode) -> Self {
+        Sound {
+            mode,
+            last_bell: None,
+        }
+    }
+
+    /// Play `event` at `now` on the terminal `out`. Points always ring; a hit is
+    /// skipped within [`BELL_GAP`] of the last bell. Returns whether anything rang.
+    pub fn play(
+        &mut self,
+        event: SoundEvent,
+        now: Instant,
+        out: &mut impl Write,
+    ) -> io::Result<bool> {
+        if self.mode == SoundMode::Off {
+            return Ok(false);
+        }
+        let too_soon = self
+            .last_bell
+            .is_some_and(|last| now.saturating_duration_since(last) < BELL_GAP);
+        if event == SoundEvent::Hit && too_soon {
+            return Ok(false);
+        }
+        let bells = match event {
+            SoundEvent::Hit => "\x07",
+            SoundEvent::Score => "\x07\x07",
+        };
+        out.write_all(bells.as_bytes())?;
+        self.last_bell = Some(now);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn bells_ring_for_hits_and_points_but_not_every_frame() -> Result<()> {
+        let state = |ball_x, vx, score_a| State {
+            tick: 0,
+            ax: 9,
+            bx: 9,
+            ball_x,
+            ball_y: 12,
+            vx,
+            vy: 1,
+            score_a,
+            score_b: 0,
+        };
+        let between = |last, now| SoundEvent::between(&last, &now, 80);
+        assert_eq!(
+            between(state(2, -1, 0), state(1, 1, 0)),
+            Some(SoundEvent::Hit)
+        );
+        assert_eq!(
+            between(state(78, 1, 0), state(40, -1, 1)),
+            Some(SoundEvent::Score)
+        );
+        // Turning around mid-field isn't a paddle, and a new set isn't a point
+        assert_eq!(between(state(40, -1, 0), state(41, 1, 0)), None);
+        assert_eq!(between(state(40, 1, 5), state(40, 1, 0)), None);
+
+        let start = Instant::now();
+        let mut out = Vec::new();
+        let mut bell = Sound::new(SoundMode::Bell);
+        assert!(bell.play(SoundEvent::Hit, start, &mut out)?);
+        assert!(!bell.play(SoundEvent::Hit, start + BELL_GAP / 2, &mut out)?);
+        assert!(bell.play(SoundEvent::Score, start + BELL_GAP / 2, &mut out)?);
+        assert!(bell.play(SoundEvent::Hit, start + BELL_GAP * 2, &mut out)?);
+        assert_eq!(out, b"\x07\x07\x07\x07");
+
+        let mut silent = Sound::new(SoundMode::Off);
+        assert!(!silent.play(SoundEvent::Score, start, &mut out)?);
+        assert_eq!(out.len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,1366 @@
+//! The SQL that runs the game. Everything from AI to scoring lives in these queries;
+//! the Rust side only binds input, executes them and prints the result.
+
+use serde::{Deserialize, Serialize};
+
+use crate::render::BorderStyle;
+use crate::{AiStyle, FirstServe, ServeAngle, ServeRule, Side, TimeoutAction};
+
+/// Creates the `params`, `state` and `powerups` tables and the views other queries read
+/// from, and serves the first ball.
+///
+/// Contains `{W}`, `{H}`, `{PADDLE_H}`, `{PADDLE_W}`, `{PADDLE_SPEED}`, `{BALL_SPEED}`,
+/// `{MAX_BALL_SPEED}`, `{SPEEDUP_HITS}`, `{SPIN}`, `{MOMENTUM}`, `{AI_REACTION_A}`,
+/// `{AI_REACTION_B}`, `{SERVE_TICKS}`, `{FIRST_SERVE}`, `{SERVE}`, `{SERVE_ANGLE}`,
+/// `{TARGET_SCORE}`, `{SETS}`, `{BALLS}`, `{POWERUPS}`, `{POWERUP_ROWS}`, `{BORDER}`,
+/// `{CENTER_LINE}`, `{AI}`, `{AI_ERROR}`, `{DETERMINISTIC_AI}`, `{HUMAN_SIDE}`,
+/// `{TRAINING}`, `{LIVES}`, `{RALLY_TIMEOUT}`, `{ON_RALLY_TIMEOUT}`, `{WAIT_FOR_SERVE}`,
+/// `{RAMP}`, `{WRAP}`, `{BIG_BALL}`, `{ASPECT_CORRECT}`, `{ZONES}` and `{TEMP}`
+/// placeholders; use [`build_setup`] to fill them in.
+pub const SETUP: &str = r#"
+-- Game constants: field dimensions and paddle properties
+CREATE OR REPLACE {TEMP} TABLE params AS
+SELECT
+    {W} AS W,             -- Width of the playing field (characters)
+    {H} AS H,             -- Height of the playing field (characters)
+    {PADDLE_H} AS PADDLE_H, -- Height of each paddle (characters)
+    {PADDLE_W} AS PADDLE_W, -- Columns each paddle is thick, growing inward from x=1 and x=W-2
+    {PADDLE_SPEED} AS PADDLE_SPEED, -- How fast paddles can move per frame
+    {BALL_SPEED} AS BALL_SPEED,     -- Horizontal ball speed; also scales the bounce angles
+    greatest({MAX_BALL_SPEED}, {BALL_SPEED}) AS MAX_BALL_SPEED, -- Cap for rally speedups
+    {SPEEDUP_HITS} AS SPEEDUP_HITS, -- Paddle hits per +1 horizontal ball speed
+    {SPIN} AS SPIN,                 -- Moving paddles put spin on the ball
+    {MOMENTUM} AS MOMENTUM,         -- Paddles accelerate and coast instead of moving at a fixed speed
+    0.25 AS PADDLE_ACCEL,           -- Momentum: rows/tick gained per tick while pushed (top speed PADDLE_SPEED)
+    0.25 AS PADDLE_FRICTION,        -- Momentum: rows/tick lost per tick while coasting
+    {AI_REACTION_A} AS AI_REACTION_A, -- Ticks Player A's AI sticks to a decision, 0 to decide every tick
+    {AI_REACTION_B} AS AI_REACTION_B, -- Same for Player B's AI
+    '{AI}' AS AI,                   -- How the AIs play: reactive (track the ball) or predictive
+    {AI_ERROR} AS AI_ERROR,         -- Predictive AI: most pixels it misjudges the landing row by
+    {DETERMINISTIC_AI} AS DETERMINISTIC_AI, -- Reactive AI without rolls: trick zones in turn, always tracking
+    '{HUMAN_SIDE}' AS HUMAN_SIDE,   -- Which paddle the local player steers: left (A) or right (B)
+    {SERVE_TICKS} AS SERVE_TICKS,   -- Ticks the balls are held after a point (the serve countdown)
+    '{SERVE}' AS SERVE,             -- Who a point's serve heads for: winner, loser, alternate or random
+    '{SERVE_ANGLE}' AS SERVE_ANGLE, -- How steep a point's serve starts: flat, random or wide
+    '{FIRST_SERVE}' AS FIRST_SERVE, -- Who the opening serve heads for: a, b or random
+    {TARGET_SCORE} AS TARGET_SCORE, -- Points needed to win a set
+    {SETS} AS SETS,                 -- Best of this many sets wins the match
+    {BALLS} AS BALLS,     -- Balls in play at once (multi-ball mode when > 1)
+    {POWERUPS} AS POWERUPS,         -- Power-up pickups appear on the field, see POWERUPS
+    0.003 AS POWERUP_CHANCE,        -- Chance per tick of a pickup appearing while none is out
+    600 AS POWERUP_TTL,             -- Ticks a pickup stays on the field before it vanishes
+    480 AS POWERUP_TICKS,           -- Ticks a power-up's effect lasts
+    {POWERUP_ROWS} AS POWERUP_ROWS, -- Rows a power-up adds to or takes from a paddle
+    '{BORDER}' AS BORDER,           -- How the top and bottom edges are drawn: blocks, box or ascii
+    {CENTER_LINE} AS CENTER_LINE,   -- Draw the dotted center line
+    {TRAINING} AS TRAINING,         -- Solo practice: a wall instead of Player A, and lives instead of points
+    {LIVES} AS LIVES,               -- Training: misses allowed before the session ends
+    {RALLY_TIMEOUT} AS RALLY_TIMEOUT, -- Ticks a rally may last without a point, 0 for no limit
+    '{ON_RALLY_TIMEOUT}' AS ON_RALLY_TIMEOUT, -- What a rally that long gets: let or speedup
+    {WAIT_FOR_SERVE} AS WAIT_FOR_SERVE, -- The player's serves wait at their paddle until launched
+    {RAMP} AS RAMP,                 -- The AIs get sharper the longer a match runs, see TICK
+    1200 AS RAMP_TICKS,             -- Ramp: ticks per step
+    0.03 AS RAMP_ACCURACY,          -- Ramp: tracking accuracy gained per step
+    0.99 AS RAMP_MAX_ACCURACY,      -- Ramp: accuracy the steps stop adding at
+    {WRAP} AS WRAP,                 -- Top and bottom wrap the ball around instead of bouncing it
+    {BIG_BALL} AS BIG_BALL,         -- Balls are 2x2 blocks, drawn and colliding as such
+    CASE WHEN {BIG_BALL} THEN 2 ELSE 1 END AS BALL_SIZE, -- Columns and rows a ball covers from ball_x, ball_y
+    {ASPECT_CORRECT} AS ASPECT_CORRECT, -- Balls move vertically on even ticks only, see TICK
+    {ZONES} AS ZONES,               -- Hit zones on a paddle, each its own bounce angle (odd)
+    ({ZONES} - 1) // 2 AS MAX_ANGLE; -- Steepest bounce, in rows per tick at ball speed 1
+
+-- The glyph of the border cell at column x of row y (0 for the top edge), in the
+-- BORDER style `style` on a field `w` columns wide. Blocks hug the field from
+-- outside, the other styles put corners at both ends of each edge.
+CREATE OR REPLACE {TEMP} MACRO border_glyph(style, x, y, w) AS
+    CASE
+    WHEN style = 'box' AND x = 0 THEN CASE WHEN y = 0 THEN '┌' ELSE '└' END
+    WHEN style = 'box' AND x = w-1 THEN CASE WHEN y = 0 THEN '┐' ELSE '┘' END
+    WHEN style = 'box' THEN '─'
+    WHEN style = 'ascii' AND x IN (0, w-1) THEN '+'
+    WHEN style = 'ascii' THEN '-'
+    WHEN y = 0 THEN '▄'
+    ELSE '▀'
+    END;
+
+-- The glyph of the training wall along the left edge in the BORDER style `style`.
+CREATE OR REPLACE {TEMP} MACRO wall_glyph(style) AS
+    CASE style WHEN 'box' THEN '│' WHEN 'ascii' THEN '|' ELSE '█' END;
+
+-- The column a ball is served from: the center, or with `dir` -1 or +1 one column
+-- back from where the serve heads, but never beyond the front columns of paddles
+-- `paddle_w` thick, so however narrow the field a `ball_size` ball starts in play.
+CREATE OR REPLACE {TEMP} MACRO serve_column(w, paddle_w, ball_size, dir) AS
+    least(greatest(CAST(w/2 - dir AS INTEGER), paddle_w + 1), w - 1 - paddle_w - ball_size);
+
+-- The row a ball is served from for a `roll` from 0 to 1: one of the 3 rows above the
+-- middle of a field `h` rows tall, the middle one or the 2 below, each equally likely,
+-- but never so low that a `ball_size` ball starts in the bottom wall.
+CREATE OR REPLACE {TEMP} MACRO serve_row(h, ball_size, roll) AS
+    least(h // 2 + CAST(floor(roll * 6) AS INTEGER) - 3, h - 1 - ball_size);
+
+-- The horizontal direction of an opening serve: toward Player A (-1) or B (+1) as
+-- FIRST_SERVE says, or either way with equal chances.
+CREATE OR REPLACE {TEMP} MACRO first_serve_dir(first_serve) AS
+    CASE first_serve
+        WHEN 'a' THEN -1
+        WHEN 'b' THEN 1
+        ELSE CASE WHEN random() < 0.5 THEN 1 ELSE -1 END
+    END;
+
+-- The vertical speed of a serve, -2 to +2 before ball speed, for a `roll` from 0 to 1,
+-- weighted by SERVE_ANGLE (see ServeAngle for the chances). Every serve draws it this
+-- way: the opening one, a new set's and the ones after a point.
+CREATE OR REPLACE {TEMP} MACRO serve_vy(roll, serve_angle) AS
+    CASE serve_angle
+        WHEN 'flat' THEN CASE
+            WHEN roll < 0.05 THEN -2
+            WHEN roll < 0.25 THEN -1
+            WHEN roll < 0.75 THEN 0
+            WHEN roll < 0.95 THEN 1
+            ELSE 2
+        END
+        WHEN 'wide' THEN CASE
+            WHEN roll < 0.35 THEN -2
+            WHEN roll < 0.45 THEN -1
+            WHEN roll < 0.55 THEN 0
+            WHEN roll < 0.65 THEN 1
+            ELSE 2
+        END
+        ELSE CAST(floor(roll * 5) AS INTEGER) - 2
+    END;
+
+-- The vertical speed, before spin and ball speed, that a ball leaves a paddle `h`
+-- rows tall with when it hits `offset` rows below its top, from -MAX_ANGLE (steep up)
+-- to MAX_ANGLE (steep down) over `zones` zones. The top and bottom rows are the
+-- steepest zones and the rows between are spread evenly over the others, so the 5
+-- zones of a 7-row paddle are its rows 0, 1-2, 3-4, 5 and 6.
+CREATE OR REPLACE {TEMP} MACRO zone_vy(offset, h, zones) AS
+    CASE
+        WHEN offset <= 0 THEN 0
+        WHEN offset >= h - 1 THEN zones - 1
+        ELSE 1 + (offset - 1) * (zones - 2) // greatest(h - 2, 1)
+    END - (zones - 1) // 2;
+
+-- The first row, counted from a paddle's second, of inner zone `zone` (1 to zones-2)
+-- as zone_vy spreads them over a paddle `h` rows tall.
+CREATE OR REPLACE {TEMP} MACRO zone_first(zone, h, zones) AS
+    ((zone - 1) * greatest(h - 2, 1) + zones - 3) // (zones - 2);
+
+-- The rows below a paddle's top that hit zone `zone` (0 for the top edge to zones-1
+-- for the bottom one) of a paddle `h` rows tall, undoing zone_vy: the middle row of
+-- the zone, or the nearest row when the paddle is too short to give it one.
+CREATE OR REPLACE {TEMP} MACRO zone_offset(zone, h, zones) AS
+    CASE
+        WHEN zone <= 0 THEN 0
+        WHEN zone >= zones - 1 THEN h - 1
+        ELSE least(1 + zone_first(zone, h, zones)
+            + greatest(zone_first(zone + 1, h, zones) - 1 - zone_first(zone, h, zones), 0) // 2,
+            h - 2)
+    END;
+
+-- The zone, 0 to zones-1, an AI's trick shot aims for with `roll`, where `shift`
+-- moves the odds to the edges, see $11. The diagonals are spread over the zones
+-- between an edge and the center, and with only 3 zones fall back on the edge.
+CREATE OR REPLACE {TEMP} MACRO trick_zone(roll, shift, zones) AS
+    CASE
+        -- Top edge: steep up
+        WHEN roll < 0.25 + shift THEN 0
+        -- Upper zones: diagonal up
+        WHEN roll < 0.50 THEN least(
+            1 + CAST(floor((roll - 0.25 - shift) / (0.25 - shift) * ((zones - 1) // 2 - 1)) AS INTEGER),
+            (zones - 1) // 2 - 1)
+        -- Center: straight, RARE!
+        WHEN roll < 0.55 THEN (zones - 1) // 2
+        -- Lower zones: diagonal down
+        WHEN roll < 0.75 - shift THEN greatest(
+            zones - 2 - CAST(floor((roll - 0.55) / (0.20 - shift) * ((zones - 1) // 2 - 1)) AS INTEGER),
+            (zones - 1) // 2 + 1)
+        -- Bottom edge: steep down
+        ELSE zones - 1
+    END;
+
+-- The playable row (1 to h-2) that row y lands on when the field WRAPs around, so a
+-- ball leaving past the bottom row comes back in at the top one.
+CREATE OR REPLACE {TEMP} MACRO wrap_row(y, h) AS ((y - 1) % (h - 2) + (h - 2)) % (h - 2) + 1;
+
+-- Row y of a ball's path as paddle hits judge it: within rows 1 to `bottom`, which the
+-- walls keep the ball's top row to, or as it is when the field WRAPs around.
+CREATE OR REPLACE {TEMP} MACRO swept_row(y, bottom, wrap) AS
+    CASE WHEN wrap THEN y ELSE least(greatest(y, 1), bottom) END;
+
+-- Whether the rows lo..hi a ball swept meet a paddle on rows top..bottom. A sweep on
+-- a WRAP field may run past an edge, so with the field's playable rows as `period`
+-- the paddle's copies a period above and below it count too.
+CREATE OR REPLACE {TEMP} MACRO sweep_meets(lo, hi, top, bottom, period) AS
+    (lo <= bottom AND hi >= top)
+    OR (period > 0 AND lo <= bottom - period AND hi >= top - period)
+    OR (period > 0 AND lo <= bottom + period AND hi >= top + period);
+
+-- Game state: positions, velocities, and scores
+-- This single row gets updated every frame with new positions
+CREATE OR REPLACE {TEMP} TABLE state(
+    tick    INTEGER,      -- Frame counter (increases each update)
+    ax      INTEGER,      -- Player A paddle Y position (left side)
+    bx      INTEGER,      -- Player B paddle Y position (right side)
+    prev_ax INTEGER,      -- Player A paddle Y position one tick earlier (for spin)
+    prev_bx INTEGER,      -- Player B paddle Y position one tick earlier (for spin)
+    pa_vel  DOUBLE,       -- Player A paddle velocity in rows per tick (momentum mode)
+    pb_vel  DOUBLE,       -- Player B paddle velocity in rows per tick (momentum mode)
+    pa_h    INTEGER,      -- Player A paddle height: PADDLE_H, unless a power-up changed it
+    pb_h    INTEGER,      -- Player B paddle height
+    pa_h_timer INTEGER,   -- Ticks until Player A's paddle goes back to PADDLE_H, 0 if it is
+    pb_h_timer INTEGER,   -- Ticks until Player B's paddle goes back to PADDLE_H
+    ai_target_a INTEGER,  -- Row Player A's AI last decided to move its paddle to
+    ai_target_b INTEGER,  -- Row Player B's AI last decided to move its paddle to
+    ai_tick_a INTEGER,    -- Tick of Player A's AI's last decision
+    ai_tick_b INTEGER,    -- Tick of Player B's AI's last decision
+    ball_x  INTEGER,      -- Ball X position (0 to W-1)
+    ball_y  INTEGER,      -- Ball Y position (0 to H-1)
+    vx      INTEGER,      -- Ball velocity in X direction (±BALL_SPEED)
+    vy      INTEGER,      -- Ball velocity in Y direction (-2..2 times BALL_SPEED)
+    hits    INTEGER,      -- Paddle hits since the ball was served
+    serve_timer INTEGER,  -- Ticks left until the balls move again after a point, 0 in play
+    serve_dir INTEGER,    -- Which way the last serve went: -1 toward A, +1 toward B
+    score_a INTEGER,      -- Player A score
+    score_b INTEGER,      -- Player B score
+    set_a   INTEGER,      -- Sets won by Player A
+    set_b   INTEGER,      -- Sets won by Player B
+    lives   INTEGER,      -- Training: misses left before the session ends
+    rally_ticks INTEGER,  -- Ticks in play since the last point, let or speed-up (RALLY_TIMEOUT)
+    awaiting_serve BOOLEAN, -- WAIT_FOR_SERVE: the player's serve waits for them to launch it
+    -- Balls beyond the first in multi-ball mode, NULL when playing with a single ball
+    extra_balls STRUCT(id INTEGER, ball_x INTEGER, ball_y INTEGER, vx INTEGER, vy INTEGER, hits INTEGER)[]
+);
+
+-- Initialize game with random starting position and angle
+INSERT INTO state
+SELECT
+    0,                                                       -- tick = 0 (start)
+    (H-PADDLE_H)/2,                                          -- Player A paddle centered
+    (H-PADDLE_H)/2,                                          -- Player B paddle centered
+    (H-PADDLE_H)/2,                                          -- Neither paddle moving yet
+    (H-PADDLE_H)/2,
+    0,
+    0,
+    PADDLE_H,                                                -- Paddles at their normal height
+    PADDLE_H,
+    0,
+    0,
+    (H-PADDLE_H)/2,                                          -- The AIs start out holding still
+    (H-PADDLE_H)/2,
+    0,
+    0,
+    serve_column(W, PADDLE_W, BALL_SIZE, 0),                 -- Ball at horizontal center
+    serve_row(H, BALL_SIZE, random()),                       -- Ball Y: center -3 to +2 pixels
+    first_serve_dir(FIRST_SERVE) * BALL_SPEED,               -- Ball direction: left, right or random
+    serve_vy(random(), SERVE_ANGLE) * BALL_SPEED,            -- Ball angle: -2 to +2 (5 angles)
+    0,                                                       -- No hits yet
+    0,                                                       -- First serve goes right away
+    0,                                                       -- Set from the first serve below
+    0,                                                       -- Score A = 0
+    0,                                                       -- Score B = 0
+    0,                                                       -- No sets won yet
+    0,
+    LIVES,                                                   -- Training: every life left
+    0,                                                       -- No rally yet
+    false,                                                   -- Set below with WAIT_FOR_SERVE
+    (SELECT list({                                           -- Extra balls: served like the first
+        'id': i::INTEGER,
+        'ball_x': serve_column(W, PADDLE_W, BALL_SIZE, 0),
+        'ball_y': serve_row(H, BALL_SIZE, random()),
+        'vx': first_serve_dir(FIRST_SERVE) * BALL_SPEED,
+        'vy': serve_vy(random(), SERVE_ANGLE) * BALL_SPEED,
+        'hits': 0
+    } ORDER BY i) FROM range(1, {BALLS}) AS t(i))
+FROM params;
+UPDATE state SET serve_dir = sign(vx);
+-- WAIT_FOR_SERVE: the player serves first, once they launch the ball
+UPDATE state SET
+    vx = abs(vx) * CASE WHEN p.HUMAN_SIDE = 'right' THEN -1 ELSE 1 END,
+    serve_dir = CASE WHEN p.HUMAN_SIDE = 'right' THEN -1 ELSE 1 END,
+    awaiting_serve = true
+FROM params p
+WHERE p.WAIT_FOR_SERVE;
+
+-- Power-up pickups on the field, at most one at a time (see POWERUPS)
+CREATE OR REPLACE {TEMP} TABLE powerups(
+    x    INTEGER,         -- Column of the pickup
+    y    INTEGER,         -- Row of the pickup
+    kind VARCHAR,         -- 'grow' or 'shrink'
+    ttl  INTEGER          -- Ticks left until it vanishes
+);
+
+-- Pickups a ball passed over during the last tick, with the side that hit that ball
+-- last: a ball heading right was last hit by Player A. A ball that was only served
+-- hasn't been hit by anyone yet and passes over pickups without taking them.
+CREATE OR REPLACE {TEMP} VIEW powerup_catches AS
+WITH balls AS (
+    SELECT 0 AS id, ball_x, ball_y, vx, vy, hits FROM state
+    UNION ALL
+    SELECT e.b.id, e.b.ball_x, e.b.ball_y, e.b.vx, e.b.vy, e.b.hits
+    FROM (SELECT unnest(extra_balls) AS b FROM state) e
+)
+SELECT u.x, u.y, u.kind, CASE WHEN b.vx > 0 THEN 'A' ELSE 'B' END AS side
+FROM powerups u, balls b, params p
+WHERE b.hits > 0
+    AND u.x BETWEEN least(b.ball_x - b.vx, b.ball_x)
+        AND greatest(b.ball_x - b.vx, b.ball_x) + p.BALL_SIZE - 1
+    AND u.y BETWEEN least(b.ball_y - b.vy, b.ball_y)
+        AND greatest(b.ball_y - b.vy, b.ball_y) + p.BALL_SIZE - 1
+QUALIFY row_number() OVER (PARTITION BY u.x, u.y ORDER BY b.id) = 1;
+
+-- Every cell covered by a ball: the primary ball plus any multi-ball extras, each
+-- BALL_SIZE columns wide and rows high from its ball_x, ball_y. On a WRAP field a
+-- big ball's lower row may come around at the top.
+CREATE OR REPLACE {TEMP} VIEW ball_cells AS
+WITH balls AS (
+    SELECT ball_x, ball_y FROM state
+    UNION ALL
+    SELECT e.b.ball_x, e.b.ball_y FROM (SELECT unnest(extra_balls) AS b FROM state) e
+)
+SELECT
+    b.ball_x + dx AS ball_x,
+    CASE WHEN p.WRAP THEN wrap_row(b.ball_y + dy, p.H) ELSE b.ball_y + dy END AS ball_y
+FROM balls b, params p, range(p.BALL_SIZE) AS t_x(dx), range(p.BALL_SIZE) AS t_y(dy);
+
+-- The cells RENDER would classify as anything but empty, with their kind and the
+-- glyph of those that bring their own. Instead of classifying every cell of the grid
+-- this lists what each element covers, a few hundred rows instead of W * H, and where
+-- elements overlap keeps the one RENDER would show. RENDER_SPARSE and RENDER_FULL
+-- draw from it.
+CREATE OR REPLACE {TEMP} VIEW field_cells AS
+WITH balls AS (SELECT * FROM ball_cells),
+-- Every element with the cells it covers; rank is its place in RENDER's CASE, the
+-- lowest one wins a cell
+elements AS (
+    SELECT 1 AS rank, y, x, 'border' AS kind, border_glyph(p.BORDER, x, y, p.W) AS glyph
+    FROM params p, range(0,p.W) AS t_x(x), unnest([0, p.H-1]) AS t_y(y)
+    UNION ALL
+    SELECT 1, y, 0, 'wall', wall_glyph(p.BORDER)
+    FROM params p, range(1,p.H-1) AS t_y(y)
+    WHERE p.TRAINING
+    UNION ALL
+    SELECT 2, y, x, 'paddle_a', NULL
+    FROM params p, state s, range(1,p.PADDLE_W+1) AS t_x(x), range(s.ax,s.ax+s.pa_h) AS t_y(y)
+    WHERE NOT p.TRAINING
+    UNION ALL
+    SELECT 3, y, x, 'paddle_b', NULL
+    FROM params p, state s, range(p.W-1-p.PADDLE_W,p.W-1) AS t_x(x), range(s.bx,s.bx+s.pb_h) AS t_y(y)
+    UNION ALL
+    SELECT 4, ball_y, ball_x, 'ball', NULL FROM balls
+    UNION ALL
+    SELECT 5, p.H//2, p.W//2, 'countdown',
+        CAST(ceil(3 * s.serve_timer / p.SERVE_TICKS) AS INTEGER)::VARCHAR
+    FROM params p, state s
+    WHERE s.serve_timer > 0
+    UNION ALL
+    SELECT 6, y, x, 'powerup', CASE kind WHEN 'grow' THEN '+' ELSE '-' END FROM powerups
+    UNION ALL
+    -- RENDER matches x=W/2, a fraction no column has when the width is odd
+    SELECT 7, y, p.W//2, 'center', NULL
+    FROM params p, range(0,p.H) AS t_y(y)
+    WHERE y % 3 = 1 AND p.W % 2 = 0 AND p.CENTER_LINE
+)
+SELECT y::INTEGER AS y, x::INTEGER AS x, kind, glyph
+FROM elements, params p
+-- Anything outside the grid RENDER covers isn't drawn there either
+WHERE y BETWEEN 0 AND p.H-1 AND x BETWEEN 0 AND p.W-1
+QUALIFY row_number() OVER (PARTITION BY y, x ORDER BY rank) = 1;
+"#;
+
+/// Advances the game by one frame: AI, ball movement, collisions and scoring.
+///
+/// Bind parameters:
+/// - `$1`: Local player input direction (-1 up, +1 down, 0 for AI), for Player B, or
+///   for Player A when `HUMAN_SIDE` is `left`
+/// - `$2`: AI tracking accuracy, the chance per tick of following a far-away ball
+/// - `$3`: AI reaction margin, how close to a paddle end the ball may get before the
+///   AI moves (capped at half the paddle height)
+/// - `$4`: Row the local player's paddle should head for (mouse control), or NULL
+/// - `$5`: Player A input direction when a remote player controls it (-1 up, +1 down,
+///   0 to hold still), or NULL to let the AI play
+/// - `$6`, `$7`: Player A's and Player B's AI rolls, uniform in `[0, 1)`. One roll
+///   picks the trick shot zone when the ball is close, or decides whether the AI
+///   tracks it when it is far. With `DETERMINISTIC_AI` they are ignored: the AI always
+///   tracks, and the zone goes by `tick % ZONES`, from 0 the top edge (steep up) over
+///   the center (straight) to `ZONES - 1` the bottom edge (steep down).
+/// - `$8`, `$9`: Serve rolls in `[0, 1)` for the row and angle of a ball put back in
+///   play after a point
+/// - `$10`: Serve roll in `[0, 1)` for its direction when `SERVE` is `random`
+/// - `$11`: Points Player A trails Player B by (negative when ahead), for the adaptive
+///   AI, or 0 to play the same at any score. Clamped to ±3, each point moves 5% of the
+///   trick shots from the diagonal zones to the steep edges for the side behind, and
+///   the other way for the side ahead: from 40% per edge at 3 down to 10% at 3 up.
+///
+/// With `RAMP` both AIs sharpen as the match goes on: every `RAMP_TICKS` ticks (20
+/// seconds) `$2` gains `RAMP_ACCURACY` (0.03), up to `RAMP_MAX_ACCURACY` (0.99), and
+/// `$3` one row, up to half the paddle, which leaves at most a row of dead zone. Easy
+/// thus reaches normal's accuracy after 3 minutes and hard's after 4 minutes 20.
+///
+/// The query itself never calls `random()`, so a tick is fully determined by the
+/// state and these parameters.
+pub const TICK: &str = r#"
+-- Use CTEs (Common Table Expressions) to break down the game logic into clear steps
+-- Each WITH clause is like a mini-table that feeds into the next step
+WITH
+    -- Load game parameters and current state for easy reference
+    p AS (SELECT * FROM params),
+    s AS (SELECT * FROM state),
+    -- Values bound from Rust each tick
+    i AS (
+        SELECT
+            CASE WHEN p.RAMP THEN s.tick // p.RAMP_TICKS ELSE 0 END AS ramp, -- Ramp steps so far
+            -- Chance the AI tracks a far-away ball
+            least($2::DOUBLE + ramp * p.RAMP_ACCURACY, greatest($2::DOUBLE, p.RAMP_MAX_ACCURACY)) AS accuracy,
+            least($3::INTEGER + ramp, p.PADDLE_H // 2) AS margin,  -- Rows from a paddle end before the AI reacts
+            -- The local player's key ($1) and mouse ($4) input goes to the paddle on
+            -- HUMAN_SIDE; the other one is left to the AI, or to a remote or second
+            -- local Player A ($5)
+            -- Human input: -1 up, +1 down, 0 holds still, NULL = AI plays
+            CASE WHEN p.HUMAN_SIDE = 'right' THEN $1::INTEGER END AS b_move,
+            -- Mouse: paddle row to head for, NULL if unused
+            CASE WHEN p.HUMAN_SIDE = 'right' THEN $4::INTEGER END AS b_target,
+            -- Player A: -1 up, +1 down, 0 holds still, NULL = AI plays
+            coalesce($5::INTEGER, CASE WHEN p.HUMAN_SIDE = 'left' THEN $1::INTEGER END) AS a_move,
+            CASE WHEN p.HUMAN_SIDE = 'left' THEN $4::INTEGER END AS a_target,
+            $6::DOUBLE AS ai_roll_a,                        -- Player A's AI: trick shot zone or tracking
+            $7::DOUBLE AS ai_roll_b,                        -- Player B's AI: same
+            $8::DOUBLE AS serve_row,                        -- Where a new serve starts
+            $9::DOUBLE AS serve_angle,                      -- Which way a new serve heads
+            $10::DOUBLE AS serve_side,                      -- Who a random serve heads for
+            least(greatest($11::INTEGER, -3), 3) * 0.05 AS shift_a, -- Adaptive AI: trick odds moved to the edges
+            -shift_a AS shift_b                             -- Player B trails by the opposite
+        FROM p, s
+    ),
+    -- Every ball in play: the primary ball lives in the state columns, extra balls
+    -- (multi-ball mode) in the extra_balls list. All physics below runs per ball row.
+    balls AS (
+        SELECT 0 AS id, s.ball_x, s.ball_y, s.vx, s.vy, s.hits FROM s
+        UNION ALL
+        SELECT e.b.id, e.b.ball_x, e.b.ball_y, e.b.vx, e.b.vy, e.b.hits
+        FROM (SELECT unnest(s.extra_balls) AS b FROM s) e
+    ),
+    -- Each AI watches the most urgent ball: one heading its way, and the closest of those
+    ta AS (SELECT * FROM balls ORDER BY (vx < 0) DESC, ball_x, id LIMIT 1),
+    tb AS (SELECT * FROM balls ORDER BY (vx > 0) DESC, ball_x DESC, id LIMIT 1),
+
+-- STEP 0: PREDICTION - Where each watched ball will reach the front column of the
+-- paddle it heads for, for the predictive AI. Without walls it would get to
+-- ball_y + vy * ticks (half that with ASPECT_CORRECT, near enough); the walls of
+-- STEP 3 reflect its top row between rows 1 and H-1-BALL_SIZE (H-2 for a normal
+-- ball), so the path repeats every 2 * (H-2-BALL_SIZE) rows, going down the first
+-- half and up the second.
+predict AS (
+    SELECT
+        p.H-2-p.BALL_SIZE AS half,
+        2 * half AS period,
+        (ta.ball_x - p.PADDLE_W) / -ta.vx AS ticks_a,
+        (p.W-1-p.PADDLE_W - (tb.ball_x + p.BALL_SIZE - 1)) / tb.vx AS ticks_b,
+        CASE WHEN p.ASPECT_CORRECT THEN 0.5 ELSE 1 END AS rate,
+        ((ta.ball_y + ta.vy * ticks_a * rate - 1) % period + period) % period AS fold_a,
+        ((tb.ball_y + tb.vy * ticks_b * rate - 1) % period + period) % period AS fold_b,
+        -- With WRAP there are no walls, and the path just goes around
+        CASE
+            WHEN p.WRAP THEN wrap_row(ta.ball_y + ta.vy * ticks_a * rate, p.H)
+            ELSE 1 + CASE WHEN fold_a <= half THEN fold_a ELSE period - fold_a END
+        END AS land_a,
+        CASE
+            WHEN p.WRAP THEN wrap_row(tb.ball_y + tb.vy * ticks_b * rate, p.H)
+            ELSE 1 + CASE WHEN fold_b <= half THEN fold_b ELSE period - fold_b END
+        END AS land_b,
+        -- Misjudged by up to AI_ERROR either way. The error only changes with every
+        -- hit and every point, so the paddle doesn't jitter while the ball comes in.
+        (hash('A', ta.id, ta.hits, s.score_a, s.score_b) % 2001 / 1000 - 1) * p.AI_ERROR AS error_a,
+        (hash('B', tb.id, tb.hits, s.score_a, s.score_b) % 2001 / 1000 - 1) * p.AI_ERROR AS error_b,
+        -- The paddle centered on that row, or on the field while the ball heads away
+        least(greatest(CASE
+            WHEN ta.vx < 0 THEN CAST(round(land_a + error_a) AS INTEGER) - s.pa_h // 2
+            ELSE (p.H - s.pa_h) // 2
+        END, 1), p.H - s.pa_h - 1) AS predict_row_a,
+        least(greatest(CASE
+            WHEN tb.vx > 0 THEN CAST(round(land_b + error_b) AS INTEGER) - s.pb_h // 2
+            ELSE (p.H - s.pb_h) // 2
+        END, 1), p.H - s.pb_h - 1) AS predict_row_b
+    FROM p, s, ta, tb
+),
+
+-- STEP 1: AI DECISION - Calculate where each paddle should move
+-- The AI mimics human players: track defensively, then make strategic shots when close.
+-- Like a human it takes a moment to react: after deciding, an AI sticks to that
+-- decision for AI_REACTION ticks, heading for the row it picked at paddle speed.
+decide AS (
+    SELECT
+        s.tick - s.ai_tick_a >= p.AI_REACTION_A AS decide_a,
+        s.tick - s.ai_tick_b >= p.AI_REACTION_B AS decide_b,
+        -- When ball is CLOSE (≤5 pixels away) and approaching: attempt trick shots!
+        -- Position paddle to hit ball at specific zones for different angles; a side
+        -- that is behind goes for the steep edges more often, see $11
+        ta.vx < 0 AND ta.ball_x <= 5 AS trick_a,
+        tb.vx > 0 AND tb.ball_x >= p.W - 6 AS trick_b,
+        -- The zone to hit, 0 (top) to ZONES-1 (bottom); DETERMINISTIC_AI takes them in
+        -- turn
+        CASE
+            WHEN p.DETERMINISTIC_AI THEN s.tick % p.ZONES
+            ELSE trick_zone(i.ai_roll_a, i.shift_a, p.ZONES)
+        END AS zone_a,
+        CASE
+            WHEN p.DETERMINISTIC_AI THEN s.tick % p.ZONES
+            ELSE trick_zone(i.ai_roll_b, i.shift_b, p.ZONES)
+        END AS zone_b,
+        -- The paddle row that puts the ball on that zone of the paddle as tall as it is
+        greatest(ta.ball_y - zone_offset(zone_a, s.pa_h, p.ZONES), 1) AS trick_row_a,
+        greatest(tb.ball_y - zone_offset(zone_b, s.pb_h, p.ZONES), 1) AS trick_row_b,
+        -- When ball is FAR: track defensively, but only with the difficulty's accuracy
+        -- and only once the ball leaves the dead zone in the middle of the paddle
+        (p.DETERMINISTIC_AI OR i.ai_roll_a < i.accuracy)
+            AND ta.ball_y NOT BETWEEN s.ax + i.margin AND s.ax + s.pa_h - 1 - i.margin AS track_a,
+        (p.DETERMINISTIC_AI OR i.ai_roll_b < i.accuracy)
+            AND tb.ball_y NOT BETWEEN s.bx + i.margin AND s.bx + s.pb_h - 1 - i.margin AS track_b,
+        -- Where tracking heads: the paddle centered on the ball
+        least(greatest(ta.ball_y - s.pa_h // 2, 1), p.H - s.pa_h - 1) AS track_row_a,
+        least(greatest(tb.ball_y - s.pb_h // 2, 1), p.H - s.pb_h - 1) AS track_row_b
+    FROM p, s, i, ta, tb
+),
+aim AS (
+    SELECT
+        -- PLAYER A (left side) - Decide where to move the paddle
+        CASE
+        -- A remote player, or a second one at the keyboard, steers this paddle
+        -- instead of the AI
+        WHEN i.a_move IS NOT NULL THEN
+            least(greatest(s.ax + i.a_move * p.PADDLE_SPEED, 1), p.H - s.pa_h - 1)
+        -- A local player on the left steers with the mouse like on the right
+        WHEN i.a_target IS NOT NULL THEN
+            least(greatest(
+                s.ax + least(greatest(i.a_target - s.ax, -p.PADDLE_SPEED), p.PADDLE_SPEED),
+            1), p.H - s.pa_h - 1)
+        -- Still reacting to an earlier decision: keep heading for its row
+        WHEN NOT d.decide_a THEN
+            least(greatest(
+                s.ax + least(greatest(s.ai_target_a - s.ax, -p.PADDLE_SPEED), p.PADDLE_SPEED),
+            1), p.H - s.pa_h - 1)
+        -- The predictive AI heads for the landing row at paddle speed, no tricks
+        WHEN p.AI = 'predictive' THEN
+            least(greatest(
+                s.ax + least(greatest(pr.predict_row_a - s.ax, -p.PADDLE_SPEED), p.PADDLE_SPEED),
+            1), p.H - s.pa_h - 1)
+        WHEN d.trick_a THEN d.trick_row_a
+        WHEN d.track_a THEN
+            CASE
+                WHEN ta.ball_y < s.ax + i.margin THEN greatest(s.ax - p.PADDLE_SPEED, 1)
+                ELSE least(s.ax + p.PADDLE_SPEED, p.H - s.pa_h - 1)
+            END
+        -- Otherwise: don't move (more imperfection for shorter games)
+        ELSE s.ax
+        END AS ax2,
+        -- PLAYER B (right side) - Same logic but mirrored
+        -- Can be controlled by human player: a pressed key overrides the AI
+        CASE
+        WHEN i.b_move IS NOT NULL THEN
+            least(greatest(s.bx + i.b_move * p.PADDLE_SPEED, 1), p.H - s.pb_h - 1)
+        -- The mouse sets a target instead, approached at the same paddle speed
+        WHEN i.b_target IS NOT NULL THEN
+            least(greatest(
+                s.bx + least(greatest(i.b_target - s.bx, -p.PADDLE_SPEED), p.PADDLE_SPEED),
+            1), p.H - s.pb_h - 1)
+        WHEN NOT d.decide_b THEN
+            least(greatest(
+                s.bx + least(greatest(s.ai_target_b - s.bx, -p.PADDLE_SPEED), p.PADDLE_SPEED),
+            1), p.H - s.pb_h - 1)
+        WHEN p.AI = 'predictive' THEN
+            least(greatest(
+                s.bx + least(greatest(pr.predict_row_b - s.bx, -p.PADDLE_SPEED), p.PADDLE_SPEED),
+            1), p.H - s.pb_h - 1)
+        WHEN d.trick_b THEN d.trick_row_b
+        WHEN d.track_b THEN
+            CASE
+                WHEN tb.ball_y < s.bx + i.margin THEN greatest(s.bx - p.PADDLE_SPEED, 1)
+                ELSE least(s.bx + p.PADDLE_SPEED, p.H - s.pb_h - 1)
+            END
+        ELSE s.bx
+        END AS bx2,
+        -- The AIs' decisions, kept until they decide again. A player steering the
+        -- paddle counts as a decision to stay where they left it.
+        CASE
+            WHEN i.a_move IS NOT NULL OR i.a_target IS NOT NULL OR NOT d.decide_a THEN s.ai_target_a
+            WHEN p.AI = 'predictive' THEN pr.predict_row_a
+            WHEN d.trick_a THEN d.trick_row_a
+            WHEN d.track_a THEN d.track_row_a
+            ELSE s.ax
+        END AS ai_target_a,
+        CASE
+            WHEN i.b_move IS NOT NULL OR i.b_target IS NOT NULL OR NOT d.decide_b THEN s.ai_target_b
+            WHEN p.AI = 'predictive' THEN pr.predict_row_b
+            WHEN d.trick_b THEN d.trick_row_b
+            WHEN d.track_b THEN d.track_row_b
+            ELSE s.bx
+        END AS ai_target_b,
+        CASE WHEN d.decide_a THEN s.tick ELSE s.ai_tick_a END AS ai_tick_a,
+        CASE WHEN d.decide_b THEN s.tick ELSE s.ai_tick_b END AS ai_tick_b
+    FROM p, s, i, ta, tb, decide d, predict pr
+),
+
+-- With MOMENTUM on, where a paddle wants to go only decides which way it is pushed:
+-- it speeds up by PADDLE_ACCEL per tick up to PADDLE_SPEED, and with nowhere to go
+-- it slows down by PADDLE_FRICTION until it stops
+pushed AS (
+    SELECT
+        CASE
+            WHEN aim.ax2 <> s.ax THEN
+                least(greatest(s.pa_vel + sign(aim.ax2 - s.ax) * p.PADDLE_ACCEL, -p.PADDLE_SPEED), p.PADDLE_SPEED)
+            ELSE sign(s.pa_vel) * greatest(abs(s.pa_vel) - p.PADDLE_FRICTION, 0)
+        END AS va,
+        CASE
+            WHEN aim.bx2 <> s.bx THEN
+                least(greatest(s.pb_vel + sign(aim.bx2 - s.bx) * p.PADDLE_ACCEL, -p.PADDLE_SPEED), p.PADDLE_SPEED)
+            ELSE sign(s.pb_vel) * greatest(abs(s.pb_vel) - p.PADDLE_FRICTION, 0)
+        END AS vb,
+        -- Unclamped positions after moving at that velocity
+        s.ax + CAST(round(va) AS INTEGER) AS ax1,
+        s.bx + CAST(round(vb) AS INTEGER) AS bx1
+    FROM aim, p, s
+),
+
+-- Final paddle positions: the AI's choice, or where momentum carries the paddle. A
+-- paddle that runs into a wall stops there and loses all its speed.
+ai AS (
+    SELECT
+        CASE WHEN p.MOMENTUM THEN least(greatest(u.ax1, 1), p.H - s.pa_h - 1) ELSE aim.ax2 END AS ax2,
+        CASE WHEN p.MOMENTUM THEN least(greatest(u.bx1, 1), p.H - s.pb_h - 1) ELSE aim.bx2 END AS bx2,
+        CASE
+            WHEN NOT p.MOMENTUM OR u.ax1 NOT BETWEEN 1 AND p.H - s.pa_h - 1 THEN 0
+            ELSE u.va
+        END AS pa_vel,
+        CASE
+            WHEN NOT p.MOMENTUM OR u.bx1 NOT BETWEEN 1 AND p.H - s.pb_h - 1 THEN 0
+            ELSE u.vb
+        END AS pb_vel
+    FROM aim, pushed u, p, s
+),
+
+-- STEP 2: BALL MOVEMENT - Move each ball by its velocity
+-- During the serve countdown after a point every ball is held where it is, and so
+-- while a serve awaits the player, with the primary ball in front of their paddle
+-- (the server's, by serve_dir), following it.
+-- Terminal cells are about twice as tall as wide, so a ball going one row per column
+-- looks steep. With ASPECT_CORRECT balls only take their vertical step on even ticks,
+-- halving how fast they seem to climb and fall; dy is the step this tick, vy stays
+-- what it was. The hi-res field's pixels are already about square, see Settings.
+step AS (
+    SELECT
+        b.id,
+        b.ball_x, b.ball_y,
+        CASE
+            WHEN s.serve_timer > 0 OR s.awaiting_serve THEN 0
+            WHEN p.ASPECT_CORRECT AND s.tick % 2 = 1 THEN 0
+            ELSE b.vy
+        END AS dy,
+        CASE
+            WHEN s.awaiting_serve AND b.id = 0 THEN
+                CASE WHEN s.serve_dir < 0 THEN p.W-1-p.PADDLE_W-p.BALL_SIZE ELSE p.PADDLE_W + 1 END
+            ELSE b.ball_x + CASE WHEN s.serve_timer > 0 OR s.awaiting_serve THEN 0 ELSE b.vx END
+        END AS nx,
+        CASE
+            WHEN s.awaiting_serve AND b.id = 0 THEN
+                CASE WHEN s.serve_dir < 0 THEN ai.bx2 + s.pb_h // 2 ELSE ai.ax2 + s.pa_h // 2 END
+            ELSE b.ball_y + dy
+        END AS ny,
+        b.vx,
+        b.vy,
+        b.hits
+    FROM balls b, s, p, ai
+),
+
+-- STEP 3: WALL COLLISION - Bounce ball off top/bottom walls
+-- A ball that would end up beyond a wall row is reflected off it, so it lands as far
+-- back inside as it overshot (within the field even if it overshot by more than the
+-- field is high) and keeps its angle instead of sticking to the wall for a tick.
+-- A paddle covers PADDLE_W columns, and a fast ball can start in front of it and end
+-- up behind it, so hits are judged along the swept path instead of at the final
+-- position. entry_x is the first paddle column the path touches (the front one, or
+-- where the ball already is), exit_x the last one (at most the back one); hy_in and
+-- hy_out are the rows the ball crosses them at. A steep ball also sweeps rows on its
+-- way into the front column: edge_x is the edge of that column it crosses, and
+-- edge_top..edge_bottom the rows it is between there, so clipping a paddle's corner
+-- counts even when the ball ends the tick above or below the paddle. In a corner
+-- the wall and the paddle act in the same frame: the rows are clamped to the
+-- playable ones, so a ball pushed against the wall is judged on the wall row, which
+-- a paddle in that corner covers. With WRAP a ball leaving the playable rows comes
+-- back in at the other edge at the same speed instead; its rows are kept as they
+-- are, and the paddle is checked where it would be a field's height away as well.
+-- A BIG_BALL covers two columns and rows from ball_x, ball_y: its leading column,
+-- lead_x, is the one that meets a paddle, and its lower row counts for the overlap.
+cols AS (
+    SELECT
+        step.*,
+        ball_x + CASE WHEN vx > 0 THEN p.BALL_SIZE - 1 ELSE 0 END AS lead_x,
+        nx + CASE WHEN vx > 0 THEN p.BALL_SIZE - 1 ELSE 0 END AS next_lead_x,
+        CASE
+            WHEN next_lead_x <= p.PADDLE_W AND vx < 0 THEN least(lead_x, p.PADDLE_W)
+            WHEN next_lead_x >= p.W-1-p.PADDLE_W AND vx > 0 THEN greatest(lead_x, p.W-1-p.PADDLE_W)
+        END AS entry_x,
+        CASE
+            WHEN next_lead_x <= p.PADDLE_W AND vx < 0 THEN greatest(next_lead_x, 1)
+            WHEN next_lead_x >= p.W-1-p.PADDLE_W AND vx > 0 THEN least(next_lead_x, p.W-2)
+        END AS exit_x,
+        CASE
+            WHEN next_lead_x <= p.PADDLE_W AND vx < 0 THEN least(lead_x, p.PADDLE_W + 0.5)
+            WHEN next_lead_x >= p.W-1-p.PADDLE_W AND vx > 0 THEN greatest(lead_x, p.W-1-p.PADDLE_W - 0.5)
+        END AS edge_x
+    FROM step, p
+),
+wall AS (
+    SELECT
+        id,
+        nx,
+        p.H-1-p.BALL_SIZE AS bottom_row,               -- The lowest row the ball's top row reaches
+        CASE
+            WHEN p.WRAP THEN wrap_row(ny, p.H)
+            ELSE least(greatest(CASE
+                WHEN ny < 1 THEN 1 + (1 - ny)                  -- Overshoot above the top row
+                WHEN ny > bottom_row THEN bottom_row - (ny - bottom_row) -- Overshoot below the bottom row
+                ELSE ny
+            END, 1), bottom_row)
+        END AS ny1,
+        vx AS vx1,
+        -- Flip Y velocity, only toward the field so a ball resting on the wall row
+        -- for a tick without a vertical step doesn't turn back into it
+        CASE WHEN NOT p.WRAP AND ((ny <= 1 AND vy < 0) OR (ny >= bottom_row AND vy > 0)) THEN -vy ELSE vy END AS vy1,
+        hits,
+        ball_x,
+        entry_x,
+        CASE WHEN p.WRAP THEN p.H-2 ELSE 0 END AS wrap_period,
+        swept_row(CAST(coalesce(ball_y + dy * (entry_x - lead_x) / vx, ny) AS INTEGER), bottom_row, p.WRAP) AS hy_in,
+        swept_row(CAST(coalesce(ball_y + dy * (exit_x - lead_x) / vx, ny) AS INTEGER), bottom_row, p.WRAP) AS hy_out,
+        swept_row(CAST(floor(coalesce(ball_y + dy * (edge_x - lead_x) / vx, ny)) AS INTEGER), bottom_row, p.WRAP) AS edge_top,
+        swept_row(CAST(ceil(coalesce(ball_y + dy * (edge_x - lead_x) / vx, ny)) AS INTEGER), bottom_row, p.WRAP) AS edge_bottom
+    FROM cols, p
+),
+
+-- Which balls reach a paddle this frame, and how fast they leave it: every
+-- SPEEDUP_HITS hits in a rally add 1 to the horizontal speed, up to MAX_BALL_SPEED
+contact AS (
+    SELECT
+        w.*,
+        least(p.BALL_SPEED + (w.hits + 1) // p.SPEEDUP_HITS, p.MAX_BALL_SPEED) AS hit_speed,
+        -- A hit when the rows swept through the paddle columns overlap the paddle. Only
+        -- a ball coming from in front of the paddle can hit it: one already on its
+        -- columns got there by missing it, and must not be sent back by a paddle
+        -- that moved onto it since
+        w.nx <= p.PADDLE_W AND w.vx1 < 0 AND NOT p.TRAINING AND w.ball_x > p.PADDLE_W
+            AND sweep_meets(
+                least(w.edge_top, w.hy_in, w.hy_out),
+                greatest(w.edge_bottom, w.hy_in, w.hy_out) + p.BALL_SIZE - 1,
+                ai.ax2, ai.ax2 + s.pa_h - 1, w.wrap_period
+            ) AS hit_a,
+        -- In training every ball reaching column 1 bounces off the wall behind it,
+        -- at the row it crosses that column in (hy_out, as exit_x is then 1)
+        p.TRAINING AND w.nx <= 1 AND w.vx1 < 0 AS hit_wall,
+        w.nx + p.BALL_SIZE - 1 >= p.W-1-p.PADDLE_W AND w.vx1 > 0
+            AND w.ball_x + p.BALL_SIZE - 1 < p.W-1-p.PADDLE_W
+            AND sweep_meets(
+                least(w.edge_top, w.hy_in, w.hy_out),
+                greatest(w.edge_bottom, w.hy_in, w.hy_out) + p.BALL_SIZE - 1,
+                ai.bx2, ai.bx2 + s.pb_h - 1, w.wrap_period
+            ) AS hit_b,
+        -- The first row of that overlap, where the ball meets the paddle
+        least(greatest(wrap_row(w.hy_in, p.H), ai.ax2), ai.ax2 + s.pa_h - 1) AS hy_a,
+        least(greatest(wrap_row(w.hy_in, p.H), ai.bx2), ai.bx2 + s.pb_h - 1) AS hy_b,
+        -- The row a ball that hits is stopped at: on the paddle, and a big ball still
+        -- overlapping it with its lower row, yet above the bottom wall
+        least(greatest(wrap_row(w.hy_in, p.H), ai.ax2 - p.BALL_SIZE + 1), ai.ax2 + s.pa_h - 1,
+            CASE WHEN p.WRAP THEN p.H-2 ELSE w.bottom_row END) AS stop_a,
+        least(greatest(wrap_row(w.hy_in, p.H), ai.bx2 - p.BALL_SIZE + 1), ai.bx2 + s.pb_h - 1,
+            CASE WHEN p.WRAP THEN p.H-2 ELSE w.bottom_row END) AS stop_b,
+        -- Spin: -1 for a paddle that moved up over the last two ticks, +1 for down
+        CASE WHEN p.SPIN THEN sign(ai.ax2 - s.prev_ax) ELSE 0 END AS spin_a,
+        CASE WHEN p.SPIN THEN sign(ai.bx2 - s.prev_bx) ELSE 0 END AS spin_b,
+        ai.ax2, ai.bx2
+    FROM wall w, ai, p, s
+),
+
+-- STEP 4: PADDLE COLLISION - Detect hits and calculate bounce angles
+-- This is the magic! Ball angle depends on WHERE it hits the paddle (classic Pong physics)
+paddle AS (
+    SELECT
+        c.id,
+        -- A ball that hits is stopped at the paddle instead of where it would have gone
+        -- (its leading column on the paddle's front one)
+        CASE
+            WHEN c.hit_wall THEN 1
+            WHEN c.hit_a THEN c.entry_x
+            WHEN c.hit_b THEN c.entry_x - p.BALL_SIZE + 1
+            ELSE c.nx
+        END AS nx,
+        CASE
+            WHEN c.hit_wall THEN wrap_row(c.hy_out, p.H)
+            WHEN c.hit_a THEN c.stop_a
+            WHEN c.hit_b THEN c.stop_b
+            ELSE c.ny1
+        END AS ny1,
+        -- Reverse horizontal direction if paddle hit
+        CASE
+            WHEN c.hit_a OR c.hit_wall THEN c.hit_speed
+            WHEN c.hit_b THEN -c.hit_speed
+            ELSE c.vx1
+        END AS vx2,
+        -- Calculate new vertical velocity based on hit zone (ZONES zones on paddle,
+        -- see zone_vy): Top edge = steep up (-MAX_ANGLE), Center = straight (0),
+        -- Bottom edge = steep down (+MAX_ANGLE). Spin adds at most one step in the
+        -- paddle's direction, never beyond -MAX_ANGLE..MAX_ANGLE
+        CASE
+            WHEN c.hit_a THEN
+                least(greatest(zone_vy(c.hy_a - c.ax2, s.pa_h, p.ZONES) + c.spin_a,
+                    -p.MAX_ANGLE), p.MAX_ANGLE) * p.BALL_SPEED
+            WHEN c.hit_b THEN
+                least(greatest(zone_vy(c.hy_b - c.bx2, s.pb_h, p.ZONES) + c.spin_b,
+                    -p.MAX_ANGLE), p.MAX_ANGLE) * p.BALL_SPEED
+            ELSE c.vy1
+        END AS vy2,
+        CASE WHEN c.hit_a OR c.hit_b OR c.hit_wall THEN c.hits + 1 ELSE c.hits END AS hits2,
+        c.ax2, c.bx2
+    FROM contact c, p, s
+),
+
+-- STEP 5: SCORING - Detect if ball went past a paddle
+-- Only leaving the field behind a paddle's back column scores, with the whole ball
+-- for a BIG_BALL; a ball that slipped past the front of a thick paddle is still in play
+sc AS (
+    SELECT
+        -- A rally that went on for RALLY_TIMEOUT ticks without a point
+        p.RALLY_TIMEOUT > 0 AND s.rally_ticks >= p.RALLY_TIMEOUT AS timed_out,
+        CASE
+            WHEN paddle.nx + p.BALL_SIZE - 1 < 1 THEN 'B' -- Ball past left: Player B scores
+            WHEN paddle.nx > p.W-2 THEN 'A'          -- Ball past right: Player A scores
+            -- A let: every ball is served again, and nobody scores
+            WHEN timed_out AND p.ON_RALLY_TIMEOUT = 'let' THEN 'let'
+            ELSE NULL                                -- NULL = still in play
+        END AS point_to,
+        paddle.*, p.W, p.H
+    FROM paddle, p, s
+),
+
+-- STEP 6: NEXT BALLS - Keep each ball moving, or respawn just that ball after a point
+moved AS (
+    SELECT
+        sc.id,
+        sc.point_to,
+        sc.timed_out,
+        sc.ax2, sc.bx2,
+        -- Which way a ball that scored is served again (-1 toward A, +1 toward B),
+        -- by the SERVE rule. Alternating flips the last serve's direction.
+        CASE
+            WHEN sc.point_to IS NULL THEN NULL
+            WHEN sc.point_to = 'let' THEN s.serve_dir  -- A let serves the same way again
+            WHEN p.SERVE = 'winner' THEN CASE WHEN sc.point_to = 'A' THEN -1 ELSE 1 END
+            WHEN p.SERVE = 'loser' THEN CASE WHEN sc.point_to = 'A' THEN 1 ELSE -1 END
+            WHEN p.SERVE = 'alternate' THEN -s.serve_dir
+            WHEN (i.serve_side + sc.id * 0.618034) % 1 < 0.5 THEN -1
+            ELSE 1
+        END AS serve_dir,
+        -- Ball position: reset to center if scored, one column back from where the
+        -- serve heads, otherwise use new position
+        CAST(CASE
+            WHEN sc.point_to IS NULL THEN sc.nx
+            ELSE serve_column(sc.W, p.PADDLE_W, p.BALL_SIZE, serve_dir)
+        END AS INTEGER) AS ball_x,
+        -- Serves share this tick's rolls, so each ball shifts them by the golden ratio
+        -- to keep balls served together from starting on top of each other
+        CASE
+            WHEN sc.point_to IS NULL THEN sc.ny1
+            ELSE serve_row(sc.H, p.BALL_SIZE, (i.serve_row + sc.id * 0.618034) % 1)
+        END AS ball_y,
+        -- Ball velocity: keep current if in play, otherwise serve. A rally that timed
+        -- out with ON_RALLY_TIMEOUT = 'speedup' goes one column per tick faster instead.
+        CASE
+            WHEN sc.point_to IS NULL AND sc.timed_out THEN
+                sign(sc.vx2) * least(abs(sc.vx2) + 1, p.MAX_BALL_SPEED)
+            WHEN sc.point_to IS NULL THEN sc.vx2
+            ELSE serve_dir * p.BALL_SPEED
+        END AS vx,
+        CASE
+            WHEN sc.point_to IS NULL THEN sc.vy2
+            ELSE serve_vy((i.serve_angle + sc.id * 0.618034) % 1, p.SERVE_ANGLE) * p.BALL_SPEED
+        END AS vy,
+        -- A new serve starts a new rally at the base speed; a speed-up counts as
+        -- SPEEDUP_HITS more hits so the next paddle keeps the pace
+        CASE
+            WHEN sc.point_to IS NULL AND sc.timed_out THEN sc.hits2 + p.SPEEDUP_HITS
+            WHEN sc.point_to IS NULL THEN sc.hits2
+            ELSE 0
+        END AS hits
+    FROM sc, p, i, s
+),
+
+-- Points scored this frame by any ball, and the extra balls packed back into a list
+totals AS (
+    SELECT
+        count(*) FILTER (WHERE point_to = 'A') AS points_a,
+        count(*) FILTER (WHERE point_to = 'B') AS points_b,
+        count(*) FILTER (WHERE point_to = 'let') AS lets,
+        bool_or(timed_out) AS timed_out,
+        -- With several balls served at once, the lowest one's serve counts as the last
+        arg_min(serve_dir, id) FILTER (WHERE point_to IS NOT NULL) AS serve_dir,
+        list({'id': id, 'ball_x': ball_x, 'ball_y': ball_y, 'vx': vx, 'vy': vy, 'hits': hits} ORDER BY id)
+            FILTER (WHERE id > 0) AS extra_balls
+    FROM moved
+),
+
+-- STEP 7: UPDATE STATE - Combine all changes and increment scores
+next_state AS (
+    SELECT
+        s.tick + 1 AS tick,                           -- Increment frame counter
+        m.ax2 AS ax, m.bx2 AS bx,                     -- New paddle positions
+        s.ax AS prev_ax, s.bx AS prev_bx,             -- Old ones, for spin
+        ai.pa_vel, ai.pb_vel,                         -- Paddle velocities (momentum mode)
+        aim.ai_target_a, aim.ai_target_b,             -- AI decisions and when they were made
+        aim.ai_tick_a, aim.ai_tick_b,
+        -- WAIT_FOR_SERVE: a serve away from the player's side is theirs, and waits for
+        -- them to launch it (Game::serve clears the flag)
+        CASE
+            WHEN t.points_a + t.points_b + t.lets > 0 THEN p.WAIT_FOR_SERVE
+                AND coalesce(t.serve_dir, s.serve_dir) = CASE WHEN p.HUMAN_SIDE = 'right' THEN -1 ELSE 1 END
+            ELSE s.awaiting_serve
+        END AS awaiting_serve,
+        -- A point or a let starts the serve countdown, otherwise it runs down to 0. The
+        -- player's own serve goes when they launch it instead.
+        CASE
+            WHEN awaiting_serve THEN 0
+            WHEN t.points_a + t.points_b + t.lets > 0 THEN p.SERVE_TICKS
+            ELSE greatest(s.serve_timer - 1, 0)
+        END AS serve_timer,
+        coalesce(t.serve_dir, s.serve_dir) AS serve_dir,
+        m.ball_x, m.ball_y, m.vx, m.vy, m.hits,       -- Primary ball
+        t.extra_balls,                                -- Multi-ball mode extras (NULL if none)
+        -- Increment score for every ball that got past a paddle; in training a ball
+        -- past Player B costs a life instead (none get past the wall)
+        s.score_a + CASE WHEN p.TRAINING THEN 0 ELSE t.points_a END AS score_a,
+        s.score_b + t.points_b AS score_b,
+        s.lives - CASE WHEN p.TRAINING THEN t.points_a ELSE 0 END AS lives,
+        -- The rally clock starts over with every point and timeout, and stands still
+        -- during the serve countdown and while a serve awaits the player
+        CASE
+            WHEN t.points_a + t.points_b > 0 OR t.timed_out OR s.serve_timer > 0
+                OR s.awaiting_serve THEN 0
+            ELSE s.rally_ticks + 1
+        END AS rally_ticks
+    FROM state s, totals t, moved m, ai, aim, p
+    WHERE m.id = 0
+)
+
+-- Finally, write the new state back to the state table
+UPDATE state
+SET tick = n.tick, ax = n.ax, bx = n.bx, prev_ax = n.prev_ax, prev_bx = n.prev_bx,
+    pa_vel = n.pa_vel, pb_vel = n.pb_vel, serve_timer = n.serve_timer, serve_dir = n.serve_dir,
+    ai_target_a = n.ai_target_a, ai_target_b = n.ai_target_b,
+    ai_tick_a = n.ai_tick_a, ai_tick_b = n.ai_tick_b,
+    ball_x = n.ball_x, ball_y = n.ball_y,
+    vx = n.vx, vy = n.vy, hits = n.hits, extra_balls = n.extra_balls,
+    score_a = n.score_a, score_b = n.score_b, lives = n.lives, rally_ticks = n.rally_ticks,
+    awaiting_serve = n.awaiting_serve
+FROM next_state n;
+"#;
+
+/// Counts the set that was just won and starts the next one like a fresh game: scores
+/// and paddles reset and every ball is served again in a random direction. Only the
+/// set counts carry over, and power-ups on the field or in effect are gone. The set
+/// goes to whoever reached the target two points clear; after a deuce the loser may
+/// be past the target too.
+pub const NEW_SET: &str = r#"
+UPDATE state SET
+    set_a = set_a + (score_a >= p.TARGET_SCORE AND score_a - score_b >= 2)::INTEGER,
+    set_b = set_b + (score_b >= p.TARGET_SCORE AND score_b - score_a >= 2)::INTEGER,
+    score_a = 0,
+    score_b = 0,
+    ax = (p.H-p.PADDLE_H)/2,
+    bx = (p.H-p.PADDLE_H)/2,
+    prev_ax = (p.H-p.PADDLE_H)/2,
+    prev_bx = (p.H-p.PADDLE_H)/2,
+    pa_vel = 0,
+    pb_vel = 0,
+    pa_h = p.PADDLE_H,
+    pb_h = p.PADDLE_H,
+    pa_h_timer = 0,
+    pb_h_timer = 0,
+    ai_target_a = (p.H-p.PADDLE_H)/2,
+    ai_target_b = (p.H-p.PADDLE_H)/2,
+    ball_x = serve_column(p.W, p.PADDLE_W, p.BALL_SIZE, 0),
+    ball_y = serve_row(p.H, p.BALL_SIZE, random()),
+    vx = CASE WHEN random() < 0.5 THEN 1 ELSE -1 END * p.BALL_SPEED,
+    vy = serve_vy(random(), p.SERVE_ANGLE) * p.BALL_SPEED,
+    hits = 0,
+    serve_timer = p.SERVE_TICKS,
+    rally_ticks = 0,
+    extra_balls = list_transform(extra_balls, lambda b: {
+        'id': b.id,
+        'ball_x': serve_column(p.W, p.PADDLE_W, p.BALL_SIZE, 0),
+        'ball_y': serve_row(p.H, p.BALL_SIZE, random()),
+        'vx': CASE WHEN random() < 0.5 THEN 1 ELSE -1 END * p.BALL_SPEED,
+        'vy': serve_vy(random(), p.SERVE_ANGLE) * p.BALL_SPEED,
+        'hits': 0
+    })
+FROM params p;
+
+-- Alternating serves carry on from the set's first serve
+UPDATE state SET serve_dir = sign(vx);
+-- WAIT_FOR_SERVE: the player serves first in every set, like in the first one
+UPDATE state SET
+    vx = abs(vx) * CASE WHEN p.HUMAN_SIDE = 'right' THEN -1 ELSE 1 END,
+    serve_dir = CASE WHEN p.HUMAN_SIDE = 'right' THEN -1 ELSE 1 END,
+    serve_timer = 0,
+    awaiting_serve = true
+FROM params p
+WHERE p.WAIT_FOR_SERVE;
+DELETE FROM powerups;
+"#;
+
+/// Runs after [`TICK`] with `--powerups`: effects wear off, pickups a ball passed over
+/// take effect, and pickups that were caught or timed out leave the field.
+///
+/// | kind     | glyph | effect                                                      |
+/// |----------|-------|-------------------------------------------------------------|
+/// | `grow`   | `+`   | the paddle of whoever hit the ball last grows by 2 rows     |
+/// | `shrink` | `-`   | the other paddle shrinks by 2 rows, down to at least 1 row  |
+///
+/// Either effect lasts `POWERUP_TICKS` (8 seconds), and a new one on the same paddle
+/// replaces it with a fresh timer. New pickups come from [`SPAWN_POWERUP`].
+pub const POWERUPS: &str = r#"
+-- Effects wear off: a paddle whose timer runs out goes back to PADDLE_H
+UPDATE state SET
+    pa_h = CASE WHEN pa_h_timer = 1 THEN p.PADDLE_H ELSE pa_h END,
+    pb_h = CASE WHEN pb_h_timer = 1 THEN p.PADDLE_H ELSE pb_h END,
+    pa_h_timer = greatest(pa_h_timer - 1, 0),
+    pb_h_timer = greatest(pb_h_timer - 1, 0)
+FROM params p;
+
+-- Caught pickups take effect. Growing helps the side that caught it, shrinking hurts
+-- the other one. A paddle grown near the bottom wall is pushed back up onto the field.
+WITH effect AS (
+    SELECT
+        (c.kind = 'grow') = (c.side = 'A') AS on_a,
+        least(greatest(
+            p.PADDLE_H + CASE WHEN c.kind = 'grow' THEN p.POWERUP_ROWS ELSE -p.POWERUP_ROWS END,
+        1), p.H - 2) AS height
+    FROM (SELECT * FROM powerup_catches LIMIT 1) c, params p
+)
+UPDATE state SET
+    pa_h = CASE WHEN e.on_a THEN e.height ELSE pa_h END,
+    pb_h = CASE WHEN e.on_a THEN pb_h ELSE e.height END,
+    pa_h_timer = CASE WHEN e.on_a THEN p.POWERUP_TICKS ELSE pa_h_timer END,
+    pb_h_timer = CASE WHEN e.on_a THEN pb_h_timer ELSE p.POWERUP_TICKS END,
+    ax = CASE WHEN e.on_a THEN least(ax, p.H - e.height - 1) ELSE ax END,
+    bx = CASE WHEN e.on_a THEN bx ELSE least(bx, p.H - e.height - 1) END
+FROM effect e, params p;
+
+-- Caught and timed out pickups leave the field, the rest age by a tick
+DELETE FROM powerups
+WHERE ttl <= 1
+    OR EXISTS (SELECT 1 FROM powerup_catches c WHERE c.x = powerups.x AND c.y = powerups.y);
+UPDATE powerups SET ttl = ttl - 1;
+"#;
+
+/// Puts a new pickup on the field with `--powerups`, with a chance of `POWERUP_CHANCE`
+/// per tick while there is none. It lands anywhere in the middle half of the field,
+/// between the rows the ball can reach.
+///
+/// Bind parameters, each uniform in `[0, 1)`:
+/// - `$1`: whether a pickup appears this tick
+/// - `$2`, `$3`: its column and row
+/// - `$4`: its kind, `grow` or `shrink` with equal chances
+pub const SPAWN_POWERUP: &str = r#"
+INSERT INTO powerups
+SELECT
+    CAST(p.W // 4 + floor($2::DOUBLE * (p.W // 2)) AS INTEGER),
+    CAST(1 + floor($3::DOUBLE * (p.H - 2)) AS INTEGER),
+    CASE WHEN $4::DOUBLE < 0.5 THEN 'grow' ELSE 'shrink' END,
+    p.POWERUP_TTL
+FROM params p
+WHERE p.POWERUPS AND $1::DOUBLE < p.POWERUP_CHANCE AND NOT EXISTS (SELECT 1 FROM powerups);
+"#;
+
+/// Classifies every cell of the field, returning `(y, x, kind, glyph)` rows ordered by
+/// `y` then `x`. Kinds are `empty`, `border`, `wall`, `paddle_a`, `paddle_b`, `ball`,
+/// `countdown`, `powerup` and `center`; the renderer picks colors and, where `glyph`
+/// is NULL, glyphs for them. Only the serve countdown brings its own glyph, the digit
+/// to show, and so do power-up pickups, the glyph of their kind (see [`POWERUPS`]),
+/// and the border and the training wall, whose glyphs depend on the `BORDER` style.
+///
+/// With a row per cell this is the slow way to draw: the game lists only the cells
+/// that aren't empty with [`RENDER_SPARSE`], and `--render grid` keeps this one
+/// around to compare.
+pub const RENDER: &str = r#"
+-- Every ball cell to draw: the primary ball plus any multi-ball extras
+WITH balls AS (SELECT * FROM ball_cells)
+-- Decide what occupies each position of the grid, one row per character cell
+-- This creates an 80x25 grid; Rust turns the kinds into colored characters
+SELECT y, x,
+    CASE
+    WHEN y IN (0,p.H-1) THEN 'border'                                         -- Top/bottom borders
+    WHEN x = 0 AND p.TRAINING THEN 'wall'                                     -- Training wall
+    WHEN x BETWEEN 1 AND p.PADDLE_W AND NOT p.TRAINING
+        AND y BETWEEN s.ax AND s.ax + s.pa_h - 1 THEN 'paddle_a'              -- Player A paddle (left)
+    WHEN x BETWEEN p.W-1-p.PADDLE_W AND p.W-2
+        AND y BETWEEN s.bx AND s.bx + s.pb_h - 1 THEN 'paddle_b'              -- Player B paddle (right)
+    WHEN EXISTS (SELECT 1 FROM balls WHERE ball_x=x AND ball_y=y) THEN 'ball' -- Ball(s)
+    WHEN x=p.W//2 AND y=p.H//2 AND s.serve_timer > 0 THEN 'countdown'         -- Serve countdown
+    WHEN EXISTS (SELECT 1 FROM powerups u
+        WHERE u.x=t_x.x AND u.y=t_y.y) THEN 'powerup'                         -- Power-up pickup
+    WHEN x=p.W/2 AND (y % 3)=1 AND p.CENTER_LINE THEN 'center'                -- Center line (dotted)
+    ELSE 'empty'                                                              -- Empty space
+    END AS kind,
+    -- The countdown runs 3, 2, 1 over the serve delay
+    CASE WHEN kind = 'border' THEN border_glyph(p.BORDER, x, y, p.W)
+    WHEN kind = 'wall' THEN wall_glyph(p.BORDER)
+    WHEN kind = 'countdown' THEN
+        CAST(ceil(3 * s.serve_timer / p.SERVE_TICKS) AS INTEGER)::VARCHAR
+    WHEN kind = 'powerup' THEN
+        (SELECT CASE u.kind WHEN 'grow' THEN '+' ELSE '-' END FROM powerups u
+         WHERE u.x=t_x.x AND u.y=t_y.y)
+    END AS glyph
+FROM params p, state s, range(0,p.H) AS t_y(y), range(0,p.W) AS t_x(x)
+ORDER BY y, x;
+"#;
+
+/// The cells of [`RENDER`] that aren't empty, as `(y, x, kind, glyph)` rows ordered by
+/// `y` then `x`, from the `field_cells` view [`SETUP`] creates.
+pub const RENDER_SPARSE: &str = r#"
+SELECT y, x, kind, glyph FROM field_cells ORDER BY y, x;
+"#;
+
+/// Like [`RENDER`], but for a field simulated at twice the terminal's vertical
+/// resolution (`--hires`): each character row shows two pixel rows with the half-block
+/// glyphs `▀`, `▄` and `█`. Returns one row per character cell with its kind, for the
+/// color, and its glyph.
+pub const RENDER_HIRES: &str = r#"
+WITH balls AS (SELECT * FROM ball_cells),
+-- The same classification as the normal renderer, one row per pixel, tagged with
+-- the character row it lands in and whether it is that row's upper half
+pixels AS (
+    SELECT y // 2 AS char_y, y % 2 = 0 AS is_upper, x,
+        CASE
+        WHEN y IN (0,p.H-1) THEN 'border'
+        WHEN x = 0 AND p.TRAINING THEN 'wall'
+        WHEN x BETWEEN 1 AND p.PADDLE_W AND NOT p.TRAINING
+            AND y BETWEEN s.ax AND s.ax + s.pa_h - 1 THEN 'paddle_a'
+        WHEN x BETWEEN p.W-1-p.PADDLE_W AND p.W-2 AND y BETWEEN s.bx AND s.bx + s.pb_h - 1 THEN 'paddle_b'
+        WHEN EXISTS (SELECT 1 FROM balls WHERE ball_x=x AND ball_y=y) THEN 'ball'
+        WHEN x=p.W//2 AND y=p.H//2 AND s.serve_timer > 0 THEN 'countdown'
+        WHEN EXISTS (SELECT 1 FROM powerups u WHERE u.x=t_x.x AND u.y=t_y.y) THEN 'powerup'
+        WHEN x=p.W/2 AND (y % 3)=1 AND p.CENTER_LINE THEN 'center'
+        ELSE 'empty'
+        END AS kind
+    FROM params p, state s, range(0,p.H) AS t_y(y), range(0,p.W) AS t_x(x)
+),
+-- Pair up the upper (even) and lower (odd) pixel of every character cell
+halves AS (
+    SELECT char_y, x,
+        any_value(kind) FILTER (WHERE is_upper) AS upper,
+        any_value(kind) FILTER (WHERE NOT is_upper) AS lower
+    FROM pixels
+    GROUP BY char_y, x
+)
+SELECT char_y AS y, x,
+    -- A cell has a single color, so the more important half decides it
+    CASE
+    WHEN 'ball' IN (upper, lower) THEN 'ball'
+    WHEN 'countdown' IN (upper, lower) THEN 'countdown'
+    WHEN 'powerup' IN (upper, lower) THEN 'powerup'
+    WHEN 'paddle_a' IN (upper, lower) THEN 'paddle_a'
+    WHEN 'paddle_b' IN (upper, lower) THEN 'paddle_b'
+    WHEN 'center' IN (upper, lower) THEN 'center'
+    WHEN 'border' IN (upper, lower) THEN 'border'
+    WHEN 'wall' IN (upper, lower) THEN 'wall'
+    ELSE 'empty'
+    END AS kind,
+    CASE
+    -- Only block borders are drawn as the half their pixel row is in
+    WHEN kind = 'border' AND p.BORDER <> 'blocks' THEN
+        border_glyph(p.BORDER, x, CASE WHEN char_y = 0 THEN 0 ELSE 1 END, p.W)
+    WHEN kind = 'wall' THEN wall_glyph(p.BORDER)
+    WHEN 'ball' NOT IN (upper, lower) AND 'countdown' IN (upper, lower) THEN
+        CAST(ceil(3 * s.serve_timer / p.SERVE_TICKS) AS INTEGER)::VARCHAR
+    -- A pickup shows its kind's glyph like in the normal renderer
+    WHEN 'ball' NOT IN (upper, lower) AND 'countdown' NOT IN (upper, lower)
+        AND 'powerup' IN (upper, lower) THEN
+        (SELECT CASE u.kind WHEN 'grow' THEN '+' ELSE '-' END FROM powerups u
+         WHERE u.x=halves.x AND u.y // 2=halves.char_y LIMIT 1)
+    WHEN upper <> 'empty' AND lower <> 'empty' THEN '█'
+    WHEN upper <> 'empty' THEN '▀'
+    WHEN lower <> 'empty' THEN '▄'
+    ELSE ' '
+    END AS glyph
+FROM halves, params p, state s
+ORDER BY y, x;
+"#;
+
+/// The values that go into the `params` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    pub width: u32,
+    pub height: u32,
+    pub paddle_h: u32,
+    pub paddle_w: u32,
+    pub paddle_speed: u32,
+    pub ball_speed: u32,
+    pub max_ball_speed: u32,
+    pub speedup_hits: u32,
+    pub spin: bool,
+    pub momentum: bool,
+    pub ai_reaction_a: u32,
+    pub ai_reaction_b: u32,
+    pub serve_ticks: u32,
+    pub first_serve: FirstServe,
+    pub serve: ServeRule,
+    pub serve_angle: ServeAngle,
+    pub target_score: u32,
+    pub sets: u32,
+    pub balls: u32,
+    pub powerups: bool,
+    /// Pixels a power-up adds to or takes from a paddle.
+    pub powerup_rows: u32,
+    pub border: BorderStyle,
+    pub center_line: bool,
+    pub ai: AiStyle,
+    /// Most pixels the predictive AI misjudges where a ball lands by.
+    pub ai_error: u32,
+    pub deterministic_ai: bool,
+    pub human_side: Side,
+    pub training: bool,
+    pub lives: u32,
+    /// Ticks a rally may go on without a point, 0 for as long as it takes.
+    pub rally_timeout: u32,
+    pub on_rally_timeout: TimeoutAction,
+    /// Hold the player's serves at their paddle until they launch them.
+    pub wait_for_serve: bool,
+    /// Sharpen the AIs as the match goes on, see [`TICK`].
+    pub ramp: bool,
+    /// The ball leaves past the top or bottom and comes back in at the other edge.
+    pub wrap: bool,
+    /// Balls are 2x2 blocks instead of single cells.
+    pub big_ball: bool,
+    /// Balls take their vertical step every other tick, so their angles look right
+    /// on terminal cells about twice as tall as wide. Meant for one pixel per cell:
+    /// the hi-res field's pixels are already about square.
+    pub aspect_correct: bool,
+    /// Hit zones on each paddle, an odd number, see [`TICK`].
+    pub zones: u32,
+}
+
+impl Default for Settings {
+    /// The classic 80x25 field with a single ball.
+    fn default() -> Self {
+        Settings {
+            width: 80,
+            height: 25,
+            paddle_h: 7,
+            paddle_w: 1,
+            paddle_speed: 2,
+            ball_speed: 1,
+            max_ball_speed: 4,
+            speedup_hits: 4,
+            spin: false,
+            momentum: false,
+            ai_reaction_a: 0,
+            ai_reaction_b: 0,
+            serve_ticks: 60,
+            first_serve: FirstServe::Random,
+            serve: ServeRule::Winner,
+            serve_angle: ServeAngle::Random,
+            target_score: 11,
+            sets: 1,
+            balls: 1,
+            powerups: false,
+            powerup_rows: 2,
+            border: BorderStyle::Blocks,
+            center_line: true,
+            ai: AiStyle::Reactive,
+            ai_error: 4,
+            deterministic_ai: false,
+            human_side: Side::Right,
+            training: false,
+            lives: 3,
+            rally_timeout: 0,
+            on_rally_timeout: TimeoutAction::Let,
+            wait_for_serve: false,
+            ramp: false,
+            wrap: false,
+            big_ball: false,
+            aspect_correct: false,
+            zones: 5,
+        }
+    }
+}
+
+/// The whole field as one string, rows joined by CRLF (raw mode needs the carriage
+/// return), ready to print in one go. Draws the cells of `field_cells` like [`RENDER`]
+/// classifies them, but returns glyphs instead of kinds, so there are no colors.
+/// `$1` is the [`GlyphSet`](crate::render::GlyphSet) by name: with `ascii` the ball,
+/// paddles and center line get the same 7-bit glyphs as in the other renderers.
+pub const RENDER_FULL: &str = r#"
+-- The glyph of every cell that isn't blank, and how many blanks come before it
+WITH glyphs AS (
+    SELECT y, x,
+        coalesce(glyph, CASE
+            WHEN $1 <> 'ascii' THEN '█'
+            WHEN kind = 'ball' THEN 'O'
+            WHEN kind = 'center' THEN ':'
+            ELSE '|'
+        END) AS glyph,
+        x - coalesce(lag(x) OVER (PARTITION BY y ORDER BY x), -1) - 1 AS gap
+    FROM field_cells
+),
+field_rows AS (SELECT y, p.W FROM params p, range(0,p.H) AS t_y(y)),
+-- Each line is its glyphs with the gaps in between, padded to the field width. The
+-- aggregation is ordered, so the characters within a line can't shuffle.
+lines AS (
+    SELECT r.y, rpad(coalesce(string_agg(repeat(' ', g.gap) || g.glyph, '' ORDER BY g.x), ''), r.W, ' ') AS line
+    FROM field_rows r LEFT JOIN glyphs g ON g.y = r.y
+    GROUP BY r.y, r.W
+)
+SELECT string_agg(line, chr(13) || chr(10) ORDER BY y) FROM lines;
+"#;
+
+/// Fill the [`Settings`] into the [`SETUP`] template. With `persistent` the tables are
+/// regular tables that survive in a database file, otherwise they are temporary.
+pub fn build_setup(settings: &Settings, persistent: bool) -> String {
+    SETUP
+        .replace("{W}", &settings.width.to_string())
+        .replace("{H}", &settings.height.to_string())
+        .replace("{PADDLE_H}", &settings.paddle_h.to_string())
+        .replace("{PADDLE_W}", &settings.paddle_w.to_string())
+        .replace("{PADDLE_SPEED}", &settings.paddle_speed.to_string())
+        .replace("{BALL_SPEED}", &settings.ball_speed.to_string())
+        .replace("{MAX_BALL_SPEED}", &settings.max_ball_speed.to_string())
+        .replace("{SPEEDUP_HITS}", &settings.speedup_hits.to_string())
+        .replace("{SPIN}", &settings.spin.to_string())
+        .replace("{MOMENTUM}", &settings.momentum.to_string())
+        .replace("{AI_REACTION_A}", &settings.ai_reaction_a.to_string())
+        .replace("{AI_REACTION_B}", &settings.ai_reaction_b.to_string())
+        .replace("{SERVE_TICKS}", &settings.serve_ticks.to_string())
+        .replace("{SERVE_ANGLE}", settings.serve_angle.name())
+        .replace("{FIRST_SERVE}", settings.first_serve.name())
+        .replace("{SERVE}", settings.serve.name())
+        .replace("{TARGET_SCORE}", &settings.target_score.to_string())
+        .replace("{SETS}", &settings.sets.to_string())
+        .replace("{BALLS}", &settings.balls.to_string())
+        .replace("{POWERUPS}", &settings.powerups.to_string())
+        .replace("{POWERUP_ROWS}", &settings.powerup_rows.to_string())
+        .replace("{BORDER}", settings.border.name())
+        .replace("{CENTER_LINE}", &settings.center_line.to_string())
+        .replace("{AI}", settings.ai.name())
+        .replace("{AI_ERROR}", &settings.ai_error.to_string())
+        .replace("{DETERMINISTIC_AI}", &settings.deterministic_ai.to_string())
+        .replace("{HUMAN_SIDE}", settings.human_side.name())
+        .replace("{TRAINING}", &settings.training.to_string())
+        .replace("{LIVES}", &settings.lives.to_string())
+        .replace("{RALLY_TIMEOUT}", &settings.rally_timeout.to_string())
+        .replace("{ON_RALLY_TIMEOUT}", settings.on_rally_timeout.name())
+        .replace("{WAIT_FOR_SERVE}", &settings.wait_for_serve.to_string())
+        .replace("{RAMP}", &settings.ramp.to_string())
+        .replace("{WRAP}", &settings.wrap.to_string())
+        .replace("{BIG_BALL}", &settings.big_ball.to_string())
+        .replace("{ASPECT_CORRECT}", &settings.aspect_correct.to_string())
+        .replace("{ZONES}", &settings.zones.to_string())
+        .replace("{TEMP}", if persistent { "" } else { "TEMP" })
+}
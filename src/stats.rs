@@ -0,0 +1,256 @@
+//! Match statistics gathered from the state after every tick, for the summary the
+//! binary prints once the terminal is restored, and the training return rate on the
+//! status line.
+
+use crate::State;
+
+/// Counters for one session, across sets and restarts. Rallies are counted in ticks
+/// from one point to the next, serve countdown included. Only the primary ball is
+/// followed, so extra balls' hits and points don't show up here.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MatchStats {
+    pub ticks: u64,
+    pub points_a: u32,
+    pub points_b: u32,
+    pub longest_rally: u64,
+    /// Ticks of every finished rally together, for the average.
+    pub rally_ticks: u64,
+    pub paddle_hits: u32,
+    /// Paddle hits in the rally going on now, for the status line.
+    pub rally_hits: u32,
+    /// Most paddle hits in any rally so far, the one going on included.
+    pub best_rally_hits: u32,
+    /// `best_rally_hits` when the current rally started: the record it is out to beat.
+    rally_record: u32,
+    rally: u64,
+    last: Option<State>,
+}
+
+impl MatchStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take in the state after a tick.
+    pub fn record(&mut self, state: &State) {
+        self.ticks += 1;
+        self.rally += 1;
+        if let Some(last) = self.last {
+            let scored_a = state.score_a > last.score_a;
+            let scored_b = state.score_b > last.score_b;
+            if scored_a || scored_b {
+                self.points_a += u32::from(scored_a);
+                self.points_b += u32::from(scored_b);
+                self.longest_rally = self.longest_rally.max(self.rally);
+                self.rally_ticks += self.rally;
+                self.new_rally();
+            } else if state.score_a < last.score_a || state.score_b < last.score_b {
+                // A new set or match: the scores start over, and so does the rally
+                self.new_rally();
+            } else if state.vx.signum() == -last.vx.signum() && state.vx != 0 {
+                // Only a paddle turns the ball around, walls flip the vertical speed
+                self.paddle_hits += 1;
+                self.rally_hits += 1;
+                self.best_rally_hits = self.best_rally_hits.max(self.rally_hits);
+            }
+        }
+        self.last = Some(*state);
+    }
+
+    fn new_rally(&mut self) {
+        self.rally = 0;
+        self.rally_hits = 0;
+        self.rally_record = self.best_rally_hits;
+    }
+
+    /// Whether the rally going on has more hits than any before it. The first rally
+    /// has nothing to beat.
+    pub fn new_best_rally(&self) -> bool {
+        self.rally_record > 0 && self.rally_hits > self.rally_record
+    }
+
+    pub fn points(&self) -> u32 {
+        self.points_a + self.points_b
+    }
+
+    /// Mean rally length in ticks, `None` before the first point.
+    pub fn average_rally(&self) -> Option<f64> {
+        (self.points() > 0).then(|| self.rally_ticks as f64 / f64::from(self.points()))
+    }
+
+    /// The summary as a small table, one line per figure.
+    pub fn summary(&self) -> String {
+        let average = self
+            .average_rally()
+            .map_or_else(|| "-".to_string(), |average| format!("{average:.1}"));
+        let rows = [
+            ("Ticks", self.ticks.to_string()),
+            ("Points A", self.points_a.to_string()),
+            ("Points B", self.points_b.to_string()),
+            ("Longest rally", format!("{} ticks", self.longest_rally)),
+            ("Average rally", format!("{average} ticks")),
+            ("Paddle hits", self.paddle_hits.to_string()),
+            ("Best rally", format!("{} hits", self.best_rally_hits)),
+        ];
+        let mut summary = String::from("Match summary\n");
+        for (label, value) in rows {
+            summary.push_str(&format!("  {label:<15}{value:>12}\n"));
+        }
+        summary
+    }
+
+    pub fn print_summary(&self) {
+        print!("{}", self.summary());
+    }
+}
+
+/// How often the player returns the serve in training: each rally counts once, as
+/// returned when the ball first comes back off their paddle, or as missed when it
+/// costs a life before that. Training has the player as B, on the right.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ServeReturns {
+    pub returned: u32,
+    pub missed: u32,
+    /// Whether the rally going on was returned yet; every serve starts it over.
+    rally_returned: bool,
+    last: Option<(State, i32)>,
+}
+
+impl ServeReturns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take in the state and the lives left after a tick.
+    pub fn record(&mut self, state: &State, lives: i32) {
+        if let Some((last, last_lives)) = self.last {
+            if lives != last_lives {
+                // A life lost, or all of them back for a new session: a new serve
+                if lives < last_lives && !self.rally_returned {
+                    self.missed += 1;
+                }
+                self.rally_returned = false;
+            } else if last.vx > 0 && state.vx < 0 && !self.rally_returned {
+                self.returned += 1;
+                self.rally_returned = true;
+            }
+        }
+        self.last = Some((*state, lives));
+    }
+
+    /// The share of rallies returned so far, in whole percent; `None` before the
+    /// first one is returned or missed.
+    pub fn rate(&self) -> Option<u32> {
+        let rallies = self.returned + self.missed;
+        (rallies > 0).then(|| (self.returned * 100 + rallies / 2) / rallies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_stats_count_points_rallies_and_hits() {
+        let state = |tick, vx, score_a, score_b| State {
+            tick,
+            ax: 9,
+            bx: 9,
+            ball_x: 40,
+            ball_y: 12,
+            vx,
+            vy: 1,
+            score_a,
+            score_b,
+        };
+        let mut stats = MatchStats::new();
+        // A 4-tick rally with one return, then a 2-tick rally straight past A
+        for s in [
+            state(1, 1, 0, 0),
+            state(2, -1, 0, 0),
+            state(3, -1, 0, 0),
+            state(4, 1, 1, 0),
+            state(5, -1, 1, 0),
+            state(6, -1, 1, 1),
+        ] {
+            stats.record(&s);
+        }
+        assert_eq!((stats.points_a, stats.points_b), (1, 1));
+        assert_eq!(stats.paddle_hits, 2);
+        assert_eq!(stats.longest_rally, 4);
+        assert_eq!(stats.average_rally(), Some(3.0));
+        assert!(stats.summary().contains("Paddle hits"));
+
+        // A new set resets the scores without counting as points or hits
+        stats.record(&state(7, 1, 0, 0));
+        assert_eq!((stats.points(), stats.paddle_hits, stats.ticks), (2, 2, 7));
+    }
+
+    #[test]
+    fn rally_hits_start_over_with_every_point_and_flag_a_new_best() {
+        let state = |vx, score_a| State {
+            tick: 0,
+            ax: 9,
+            bx: 9,
+            ball_x: 40,
+            ball_y: 12,
+            vx,
+            vy: 1,
+            score_a,
+            score_b: 0,
+        };
+        let mut stats = MatchStats::new();
+        // Two hits, then a point
+        for s in [state(1, 0), state(-1, 0), state(1, 0), state(1, 1)] {
+            stats.record(&s);
+        }
+        assert_eq!((stats.rally_hits, stats.best_rally_hits), (0, 2));
+        assert!(!stats.new_best_rally());
+
+        // Level with the record is not a new best, one more hit is
+        for s in [state(-1, 1), state(1, 1)] {
+            stats.record(&s);
+        }
+        assert_eq!(stats.rally_hits, 2);
+        assert!(!stats.new_best_rally());
+        stats.record(&state(-1, 1));
+        assert_eq!((stats.rally_hits, stats.best_rally_hits), (3, 3));
+        assert!(stats.new_best_rally());
+        assert!(stats.summary().contains("3 hits"));
+    }
+
+    #[test]
+    fn serve_returns_count_each_rally_once() {
+        let state = |vx| State {
+            tick: 0,
+            ax: 9,
+            bx: 9,
+            ball_x: 40,
+            ball_y: 12,
+            vx,
+            vy: 1,
+            score_a: 0,
+            score_b: 0,
+        };
+        let mut returns = ServeReturns::new();
+        assert_eq!(returns.rate(), None);
+        // Returned twice and then missed: one return. Then a serve off the wall that
+        // costs a life before the player touches it: one miss
+        for (vx, lives) in [(1, 3), (-1, 3), (1, 3), (-1, 3), (1, 3), (-1, 2)] {
+            returns.record(&state(vx), lives);
+        }
+        assert_eq!((returns.returned, returns.missed), (1, 0));
+        for (vx, lives) in [(1, 2), (1, 1)] {
+            returns.record(&state(vx), lives);
+        }
+        assert_eq!((returns.returned, returns.missed), (1, 1));
+        assert_eq!(returns.rate(), Some(50));
+
+        // A new session serves again with every life back
+        for (vx, lives) in [(1, 3), (-1, 3)] {
+            returns.record(&state(vx), lives);
+        }
+        assert_eq!((returns.returned, returns.missed), (2, 1));
+        assert_eq!(returns.rate(), Some(67));
+    }
+}
@@ -0,0 +1,116 @@
+//! Live game state for external tools. `--status-socket` serves the latest [`State`]
+//! on a Unix domain socket: every connection gets one JSON object and is closed.
+//!
+//! ```text
+//! $ nc -U /tmp/pong.sock
+//! {"tick":812,"ax":9,"bx":11,"ball_x":23,"ball_y":7,"vx":-1,"vy":1,"score_a":2,"score_b":4}
+//! ```
+
+use anyhow::Result;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::State;
+
+/// Serves snapshots from a background thread, so a slow reader never holds up the
+/// game loop; the loop only swaps in a new state behind a mutex.
+pub struct StatusServer {
+    snapshot: Arc<Mutex<Option<State>>>,
+    #[cfg(unix)]
+    path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl StatusServer {
+    /// Listen on a socket at `path`, replacing a stale one left by an earlier run.
+    pub fn start(path: &Path) -> Result<Self> {
+        use anyhow::Context;
+        use std::io::Write;
+        use std::os::unix::net::UnixListener;
+
+        if path.exists() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("removing old socket {}", path.display()))?;
+        }
+        let listener =
+            UnixListener::bind(path).with_context(|| format!("listening on {}", path.display()))?;
+        let snapshot = Arc::new(Mutex::new(None));
+        let shared = Arc::clone(&snapshot);
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                // Copy the state out so the lock isn't held while writing
+                let state = *shared.lock().unwrap_or_else(|e| e.into_inner());
+                let Some(state) = state else {
+                    continue;
+                };
+                // A reader that went away already is not our problem
+                let _ = serde_json::to_writer(&mut stream, &state)
+                    .map_err(std::io::Error::from)
+                    .and_then(|()| stream.write_all(b"\n"));
+            }
+        });
+        Ok(StatusServer {
+            snapshot,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+#[cfg(not(unix))]
+impl StatusServer {
+    pub fn start(_path: &Path) -> Result<Self> {
+        anyhow::bail!("--status-socket needs Unix domain sockets, which this platform lacks")
+    }
+}
+
+impl StatusServer {
+    /// Make `state` what the next connection receives.
+    pub fn update(&self, state: State) {
+        *self.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = Some(state);
+    }
+}
+
+#[cfg(unix)]
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn status_socket_serves_the_latest_state() -> Result<()> {
+        use std::io::Read;
+        use std::os::unix::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!("duckdb-pong-{}.sock", std::process::id()));
+        let server = StatusServer::start(&path)?;
+        let mut state = State {
+            tick: 1,
+            ax: 9,
+            bx: 9,
+            ball_x: 40,
+            ball_y: 12,
+            vx: 1,
+            vy: 0,
+            score_a: 0,
+            score_b: 0,
+        };
+        server.update(state);
+        state.tick = 2;
+        state.score_b = 3;
+        server.update(state);
+
+        let mut json = String::new();
+        UnixStream::connect(&path)?.read_to_string(&mut json)?;
+        assert_eq!(serde_json::from_str::<State>(&json)?, state);
+
+        drop(server);
+        assert!(!path.exists(), "the socket file should be cleaned up");
+        Ok(())
+    }
+}
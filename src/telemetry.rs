@@ -0,0 +1,127 @@
+//! Structured logs for debugging the AI: one JSON object per tick, with where the
+//! ball is going, where each AI decided to move its paddle and what happened.
+//! Unlike a recording, a log can't be played back, but any JSON tool reads it:
+//!
+//! ```sql
+//! SELECT tick, targets.a, paddles.a FROM read_json_auto('match.jsonl');
+//! ```
+
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::State;
+use crate::sound::SoundEvent;
+
+/// Lines between flushes, a second of play, so a crash loses little of the log.
+const FLUSH_EVERY: u32 = crate::SIM_HZ as u32;
+
+/// Appends one JSON line per tick to a file. Each line is formatted into the same
+/// `String`, so logging doesn't allocate once the buffer has grown to a line.
+pub struct TelemetryLog {
+    out: BufWriter<File>,
+    line: String,
+    unflushed: u32,
+}
+
+impl TelemetryLog {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("creating log {}", path.display()))?;
+        Ok(TelemetryLog {
+            out: BufWriter::new(file),
+            line: String::new(),
+            unflushed: 0,
+        })
+    }
+
+    /// Log the tick that led to `state`, with the AI targets `(a, b)` and `event`.
+    pub fn log(
+        &mut self,
+        state: &State,
+        targets: (i32, i32),
+        event: Option<SoundEvent>,
+    ) -> Result<()> {
+        self.line.clear();
+        format_line(&mut self.line, state, targets, event);
+        self.out.write_all(self.line.as_bytes())?;
+        self.unflushed += 1;
+        if self.unflushed >= FLUSH_EVERY {
+            self.out.flush()?;
+            self.unflushed = 0;
+        }
+        Ok(())
+    }
+
+    /// Write out whatever is still buffered.
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Append the JSON line for one tick to `line`, newline included.
+pub fn format_line(
+    line: &mut String,
+    state: &State,
+    (target_a, target_b): (i32, i32),
+    event: Option<SoundEvent>,
+) {
+    let State {
+        tick,
+        ax,
+        bx,
+        ball_x,
+        ball_y,
+        vx,
+        vy,
+        score_a,
+        score_b,
+    } = state;
+    let events = match event {
+        Some(SoundEvent::Hit) => r#"["hit"]"#,
+        Some(SoundEvent::Score) => r#"["score"]"#,
+        None => "[]",
+    };
+    // Writing to a String can't fail
+    let _ = writeln!(
+        line,
+        r#"{{"tick":{tick},"ball":{{"x":{ball_x},"y":{ball_y},"vx":{vx},"vy":{vy}}},"paddles":{{"a":{ax},"b":{bx}}},"targets":{{"a":{target_a},"b":{target_b}}},"score":{{"a":{score_a},"b":{score_b}}},"events":{events}}}"#
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::{Game, Inputs};
+
+    #[test]
+    fn telemetry_lines_are_json_with_the_ai_targets() -> Result<()> {
+        let mut game = Game::new(GameConfig::default())?;
+        game.tick(Inputs::default())?;
+        let state = game.state()?;
+        let targets = game.ai_targets()?;
+        let mut line = String::new();
+        format_line(&mut line, &state, targets, Some(SoundEvent::Hit));
+        assert!(line.ends_with('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line)?;
+        assert_eq!(json["tick"], state.tick);
+        assert_eq!(json["ball"]["vx"], state.vx);
+        assert_eq!(json["paddles"]["b"], state.bx);
+        assert_eq!(json["targets"]["a"], targets.0);
+        assert_eq!(json["targets"]["b"], targets.1);
+        assert_eq!(json["events"], serde_json::json!(["hit"]));
+
+        // The buffer is reused, so each line starts from a cleared one
+        line.clear();
+        format_line(&mut line, &state, targets, None);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line)?["events"],
+            serde_json::json!([])
+        );
+        Ok(())
+    }
+}
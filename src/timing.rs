@@ -0,0 +1,209 @@
+//! Frame pacing and smoothed timings for the game loop.
+
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Frame rates the FPS keys step between while playing.
+pub const FPS_RANGE: RangeInclusive<u32> = 10..=240;
+
+/// How far one press of an FPS key moves the frame rate.
+pub const FPS_STEP: u32 = 10;
+
+/// The frame rate one step up or down from `fps`, within [`FPS_RANGE`]. Uncapped (0)
+/// counts as above the range: stepping down caps it at the top, stepping up leaves it.
+pub fn step_fps(fps: u32, up: bool) -> u32 {
+    let (min, max) = (*FPS_RANGE.start(), *FPS_RANGE.end());
+    match (fps, up) {
+        (0, true) => 0,
+        (0, false) => max,
+        (fps, true) => fps.saturating_add(FPS_STEP).clamp(min, max),
+        (fps, false) => fps.saturating_sub(FPS_STEP).clamp(min, max),
+    }
+}
+
+/// Keeps frames on a fixed schedule. Each deadline is the previous one plus one frame,
+/// so a frame that runs a little long is made up by sleeping less before the next one
+/// instead of pushing every later frame back.
+pub struct FramePacer {
+    fps: u32,
+    /// `None` when uncapped.
+    frame_dt: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    /// Pace to `fps` frames per second, or not at all when `fps` is 0.
+    pub fn new(fps: u32) -> Self {
+        FramePacer {
+            fps,
+            frame_dt: frame_dt(fps),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// The frame rate paced to, 0 when uncapped.
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// Pace to `fps` from now on. The next deadline is the last one plus a frame at
+    /// the new rate, so the change neither rushes nor stalls a frame; coming from
+    /// uncapped, where there was no last deadline, the schedule starts from now.
+    pub fn set_fps(&mut self, fps: u32) {
+        if self.frame_dt.is_none() {
+            self.next_frame = Instant::now();
+        }
+        self.fps = fps;
+        self.frame_dt = frame_dt(fps);
+    }
+
+    /// Sleep until the next frame is due.
+    pub fn wait(&mut self) {
+        let Some(frame_dt) = self.frame_dt else {
+            return;
+        };
+        self.next_frame += frame_dt;
+        let now = Instant::now();
+        if self.next_frame > now {
+            sleep(self.next_frame - now);
+        } else if now - self.next_frame > frame_dt {
+            // More than a whole frame behind: start over rather than rush to catch up
+            self.next_frame = now;
+        }
+    }
+
+    /// Start a fresh schedule from now, e.g. after the loop was paused.
+    pub fn reset(&mut self) {
+        self.next_frame = Instant::now();
+    }
+}
+
+/// The time between frames at `fps`, `None` for uncapped.
+fn frame_dt(fps: u32) -> Option<Duration> {
+    (fps > 0).then(|| Duration::from_secs_f64(1.0 / f64::from(fps)))
+}
+
+/// `--max-frames`: counts drawn frames, separately from the simulation's ticks so
+/// loops that only draw (replays, network clients) are capped the same way.
+pub struct FrameCap {
+    drawn: u64,
+    max: Option<u64>,
+}
+
+impl FrameCap {
+    /// Stop after `max` frames, or never when `None`.
+    pub fn new(max: Option<u64>) -> Self {
+        FrameCap { drawn: 0, max }
+    }
+
+    /// Count a drawn frame; returns `true` once the cap is reached.
+    pub fn frame_drawn(&mut self) -> bool {
+        self.drawn += 1;
+        self.max.is_some_and(|max| self.drawn >= max)
+    }
+}
+
+/// Average of the last few durations, for numbers that stay readable on screen.
+pub struct RollingAverage {
+    samples: VecDeque<Duration>,
+    total: Duration,
+    capacity: usize,
+}
+
+impl RollingAverage {
+    pub fn new(capacity: usize) -> Self {
+        RollingAverage {
+            samples: VecDeque::with_capacity(capacity),
+            total: Duration::ZERO,
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: Duration) {
+        if self.samples.len() == self.capacity
+            && let Some(oldest) = self.samples.pop_front()
+        {
+            self.total -= oldest;
+        }
+        self.samples.push_back(sample);
+        self.total += sample;
+    }
+
+    pub fn average(&self) -> Duration {
+        match self.samples.len() {
+            0 => Duration::ZERO,
+            n => self.total / n as u32,
+        }
+    }
+
+    /// How many times per second something taking [`average`](Self::average) fits.
+    pub fn per_second(&self) -> f64 {
+        let average = self.average().as_secs_f64();
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+}
+
+/// Where each frame's time goes, shown by `--debug-timing`.
+pub struct FrameTimings {
+    /// Running the tick query (all ticks of the frame together).
+    pub tick: RollingAverage,
+    /// Running the render query and reading the scores.
+    pub render: RollingAverage,
+    /// Writing the frame to the terminal.
+    pub io: RollingAverage,
+    /// Sleeping until the next frame.
+    pub sleep: RollingAverage,
+}
+
+impl FrameTimings {
+    pub fn new(samples: usize) -> Self {
+        FrameTimings {
+            tick: RollingAverage::new(samples),
+            render: RollingAverage::new(samples),
+            io: RollingAverage::new(samples),
+            sleep: RollingAverage::new(samples),
+        }
+    }
+
+    /// Average microseconds per segment, e.g. `tick 180µs render 950µs io 60µs sleep 7100µs`.
+    pub fn summary(&self) -> String {
+        let us = |average: &RollingAverage| average.average().as_micros();
+        format!(
+            "tick {}µs render {}µs io {}µs sleep {}µs",
+            us(&self.tick),
+            us(&self.render),
+            us(&self.io),
+            us(&self.sleep)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_cap_stops_after_the_given_frames() {
+        let mut cap = FrameCap::new(Some(3));
+        assert_eq!([(); 3].map(|()| cap.frame_drawn()), [false, false, true]);
+
+        let mut uncapped = FrameCap::new(None);
+        assert!((0..1000).all(|_| !uncapped.frame_drawn()));
+    }
+
+    #[test]
+    fn fps_keys_step_the_frame_rate_within_range() {
+        assert_eq!(step_fps(60, true), 70);
+        assert_eq!(step_fps(60, false), 50);
+        assert_eq!((step_fps(10, false), step_fps(240, true)), (10, 240));
+        // Configured rates outside the range are pulled into it
+        assert_eq!((step_fps(5, true), step_fps(300, false)), (15, 240));
+        assert_eq!((step_fps(0, true), step_fps(0, false)), (0, 240));
+
+        let mut pacer = FramePacer::new(0);
+        pacer.set_fps(step_fps(pacer.fps(), false));
+        assert_eq!(pacer.fps(), 240);
+    }
+}